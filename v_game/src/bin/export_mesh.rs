@@ -0,0 +1,52 @@
+use v_voxels::*;
+
+use nalgebra::Vector3;
+
+use std::env;
+use std::path::Path;
+use std::process;
+
+/// Meshes a saved world without opening a window and writes it out as .obj or .glb
+/// usage: export_mesh <world file> <output.obj|output.glb> [min_x min_y min_z max_x max_y max_z]
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 && args.len() != 9{
+        eprintln!("usage: export_mesh <world file> <output.obj|output.glb> [min_x min_y min_z max_x max_y max_z]");
+        process::exit(1);
+    }
+
+    let mut chunks = ChunkStorage::new();
+    if let Err(e) = chunks.load_world(&args[1]){
+        eprintln!("couldn't load {}: {:?}", args[1], e);
+        process::exit(1);
+    }
+
+    let export = if args.len() == 9{
+        let corners: Vec<i32> = args[3..].iter().map(|x| x.parse().unwrap_or_else(|_|{
+            eprintln!("{} isn't a block coordinate", x);
+            process::exit(1);
+        })).collect();
+        let region = Region::from_corners(Vector3::new(corners[0], corners[1], corners[2]), Vector3::new(corners[3], corners[4], corners[5]));
+        MeshExport::from_region(&chunks, &region)
+    } else {
+        MeshExport::from_chunks(&chunks, &chunks.chunk_coords())
+    };
+
+    let output = Path::new(&args[2]);
+    let result = match output.extension().and_then(|x| x.to_str()){
+        Some("obj") => export.save_obj(output),
+        Some("glb") => export.save_glb(output),
+        _ => {
+            eprintln!("output has to be a .obj or .glb file");
+            process::exit(1);
+        }
+    };
+
+    match result{
+        Ok(_) => println!("wrote {} triangles to {}", export.triangle_count(), output.display()),
+        Err(e) => {
+            eprintln!("couldn't write {}: {}", output.display(), e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use v_game::*;
+use v_inputs::{InputRecording, InputReplay};
+use v_windowing::*;
+
+use std::env;
+use std::process;
+
+/// Plays an input recording back without a window and prints the checksum of the world it ends with
+/// usage: replay_inputs <input recording> [ticks]
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 2 && args.len() != 3{
+        eprintln!("usage: replay_inputs <input recording> [ticks]");
+        process::exit(1);
+    }
+
+    let recording = InputRecording::load(&args[1]).unwrap_or_else(|e|{
+        eprintln!("couldn't load {}: {:?}", args[1], e);
+        process::exit(1);
+    });
+    let ticks = match args.get(2){
+        Some(ticks) => ticks.parse().unwrap_or_else(|_|{
+            eprintln!("{} isn't a number of ticks", ticks);
+            process::exit(1);
+        }),
+        None => recording.ticks(),
+    };
+
+    let headless = HeadlessState::new();
+    let options = GameOptions{input_log: InputLog::Replay(InputReplay::new(recording)), ..GameOptions::default()};
+    let mut game = Game::with_options(headless.display.clone(), headless.input_queues(), options);
+    headless.run_ticks(&mut game, ticks);
+    println!("{} ticks, checksum {:016x}", ticks, game.checksum());
+}
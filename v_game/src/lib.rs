@@ -0,0 +1,259 @@
+use v_inputs::*;
+use v_renderer::*;
+use v_transform::*;
+use v_windowing::*;
+use v_agents::*;
+use v_voxels::*;
+use v_commands::*;
+
+use std::sync::{Arc, Mutex};
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use glutin::event::DeviceEvent;
+use specs::prelude::*;
+
+/// Where rebound controls are read from, the default bindings are used when it's missing
+pub const CONTROLS_FILE: &str = "controls.cfg";
+
+/// Window size, fullscreen and vsync settings, written back when they're changed in game
+pub const WINDOW_CONFIG_FILE: &str = "window.cfg";
+
+/// The shader sources in the repository, read as the shaders directory under the resource packs while hot reloading shaders
+pub const DEV_SHADER_PACK: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders/");
+
+/// Ticks per second the simulation runs at unless the Time resource is replaced
+pub const DEFAULT_TICK_RATE: f64 = 60.0;
+
+/// Whether the input events and console commands a game takes are written out or read back from a recording.
+/// Recordings don't hold gamepad input, so gamepads are turned off while recording or replaying
+/// to keep replays ending up where the recording did.
+pub enum InputLog{
+    Off,
+    Record(InputRecorder),
+    Replay(InputReplay),
+}
+
+pub struct GameOptions{
+    pub tick_rate: f64,
+    pub input_log: InputLog,
+    /// What the window was created with, the WindowInfo resource starts out from it
+    pub window: WindowConfig,
+    /// Vertex layout chunk meshes are uploaded in
+    pub vertex_format: VertexFormat,
+    /// Real seconds a day and night takes
+    pub day_length: f32,
+    /// Builds the post process chain the scene is drawn through, games can add their own passes to the standard ones
+    pub post_process: fn(&Display, &ResourceManager) -> PostPipeline,
+    /// Resource pack directories and zip archives stacked on the game's assets, later packs win
+    pub resource_packs: Vec<PathBuf>,
+    /// Read shaders from DEV_SHADER_PACK and recompile them whenever they're saved
+    pub shader_hot_reload: bool,
+    /// Where command lines come from, lines typed into the terminal if None and there's a window
+    pub console: Option<ConsoleLines>,
+}
+
+impl Default for GameOptions{
+    fn default() -> Self{
+        GameOptions{
+            tick_rate: DEFAULT_TICK_RATE,
+            input_log: InputLog::Off,
+            window: WindowConfig::default(),
+            vertex_format: VertexFormat::default(),
+            day_length: DEFAULT_DAY_LENGTH,
+            post_process: PostPipeline::standard,
+            resource_packs: Vec::new(),
+            shader_hot_reload: false,
+            console: None,
+        }
+    }
+}
+
+pub struct Game<'a, 'b>{
+    pub world: World,
+    /// Systems run every tick even while paused: keyboard, mouse and gamepad input
+    pub input_dispatcher: Dispatcher<'a, 'b>,
+    /// Systems run every tick the game isn't paused: movement, editing and chunk placement
+    pub fixed_dispatcher: Dispatcher<'a, 'b>,
+    /// Systems run once per frame: interpolation, meshing and drawing
+    pub frame_dispatcher: Dispatcher<'a, 'b>,
+}
+
+impl<'a, 'b> Game<'a, 'b>{
+    /// Set up the world and systems. Without a display the game runs headless,
+    /// chunks aren't meshed, nothing is drawn and commands only come in through the Commands resource.
+    pub fn new(display: WindowDisplay, input_queues: (Arc<Mutex<Vec<ApplicationEvent>>>, Arc<Mutex<Vec<DeviceEvent>>>)) -> Self{
+        Self::with_options(display, input_queues, GameOptions::default())
+    }
+
+    pub fn with_tick_rate(display: WindowDisplay, input_queues: (Arc<Mutex<Vec<ApplicationEvent>>>, Arc<Mutex<Vec<DeviceEvent>>>), tick_rate: f64) -> Self{
+        Self::with_options(display, input_queues, GameOptions{tick_rate, ..GameOptions::default()})
+    }
+
+    pub fn with_options(display: WindowDisplay, (window_inputs, hardware_inputs): (Arc<Mutex<Vec<ApplicationEvent>>>, Arc<Mutex<Vec<DeviceEvent>>>), options: GameOptions) -> Self{
+        let mut world = World::new();
+        let mut resources = ResourceManager::with_packs(&options.resource_packs);
+        if options.shader_hot_reload{
+            resources.push_base(ResourcePack::Mapped(SHADER_DIRECTORY.to_string(), PathBuf::from(DEV_SHADER_PACK)));
+        }
+
+        world.register::<MeshRenderer<VoxelVertex>>();
+        world.register::<Position>();
+        world.register::<Scale>();
+        world.register::<Rotation>();
+        world.register::<TransformMatrix>();
+        world.register::<Interpolation>();
+        world.register::<Camera>();
+        world.register::<Player>();
+        world.register::<ChunkMarker>();
+
+        world.insert(Time::new(options.tick_rate));
+        let mut inputs = Inputs::default();
+        match ActionMap::load(CONTROLS_FILE){
+            Ok(actions) => inputs.set_action_map(actions),
+            Err(ActionMapError::Io(std::io::ErrorKind::NotFound)) => {},
+            Err(error) => println!("Couldn't load {}: {:?}", CONTROLS_FILE, error),
+        }
+        world.insert(inputs);
+        world.insert(display.clone());
+        world.insert(CursorState::default());
+        let mut window_info = WindowInfo::new(&options.window, 1.0);
+        if let Some(display) = display.as_ref(){
+            let display = display.lock().unwrap();
+            let gl_window = display.gl_window();
+            window_info.set_size(gl_window.window().inner_size());
+            window_info.set_scale_factor(gl_window.window().scale_factor());
+        }
+        world.insert(window_info);
+        world.insert(ChunkStorage::new());
+        world.insert(ChunkGeometry::new(options.vertex_format));
+        world.insert(WorldTime::new(options.day_length));
+        world.insert(TimeOfDay::default());
+        world.insert(Fog::default());
+        world.insert(PostSettings::default());
+        world.insert(RenderTextures::default());
+        world.insert(BlockTextures::default());
+        world.insert(Commands::default());
+        world.insert(WorldEdit::default());
+
+        world.create_entity().with(Camera::new(1.57, 0.001, 4096.0)).with(Position::new(0.0, 0.0, 0.0)).with(Rotation::new()).with(Player{}).with(TransformMatrix::default()).with(Interpolation::default()).build();
+
+        let mut input_system = InputSystem::new(window_inputs, hardware_inputs);
+        let logging_inputs = !matches!(options.input_log, InputLog::Off);
+        input_system = match options.input_log{
+            InputLog::Off => input_system,
+            InputLog::Record(recorder) => input_system.with_recorder(recorder),
+            InputLog::Replay(replay) => input_system.with_replay(replay),
+        };
+
+        //Command lines go through the input system so they're recorded and replayed with the tick they're handled on
+        let typed_lines = options.console.or_else(|| display.as_ref().map(|_| read_console()));
+        let console = if typed_lines.is_some() || logging_inputs{
+            let console_lines = ConsoleLines::default();
+            input_system = input_system.with_console(typed_lines.unwrap_or_default(), console_lines.clone());
+            Some(ConsoleSystem::with_lines(console_lines))
+        } else {
+            None
+        };
+
+        let mut input_dispatcher = DispatcherBuilder::new()
+            .with_thread_local(input_system);
+
+        let mut fixed_dispatcher = DispatcherBuilder::new()
+            .with(InterpolationSnapshotSystem, "interpolation_snapshot", &[])
+            .with(PlayerMovement{}, "player_movement", &["interpolation_snapshot"]);
+
+        let mut world_edit_dependencies = vec!["player_movement"];
+        if let Some(console) = console{
+            fixed_dispatcher.add(console, "console", &[]);
+            world_edit_dependencies.push("console");
+        }
+        if display.is_some() && logging_inputs{
+            println!("Gamepads are off while recording or replaying inputs");
+        } else if display.is_some(){
+            if let Some(backend) = default_gamepad_backend(){
+                input_dispatcher.add_thread_local(GamepadSystem::new(backend));
+            }
+        }
+
+        fixed_dispatcher.add(WorldEditSystem{}, "world_edit", &world_edit_dependencies);
+        fixed_dispatcher.add(WorldTimeSystem::default(), "world_time", &["world_edit"]);
+        fixed_dispatcher.add(NewChunkPlacementSystem{}, "chunk_placer", &[]);
+        fixed_dispatcher.add(TransformSystem, "transform_system", &["player_movement"]);
+
+        let mut frame_dispatcher = DispatcherBuilder::new()
+            .with(InterpolationSystem, "interpolation", &[])
+            .with(ChunkOcclusionSystem::default(), "chunk_occlusion", &["interpolation"])
+            .with(ChunkLodSystem::default(), "chunk_lod", &["interpolation"])
+            .with_thread_local(CursorLockSystem::default())
+            .with_thread_local(ChunkMesherSystem{});
+
+        if let Some(display) = display.as_ref(){
+            frame_dispatcher.add_thread_local(WindowSettingsSystem::new(options.window, WINDOW_CONFIG_FILE));
+            let display = display.lock().unwrap();
+            let renderer = VoxelRenderingSystem::with_post_process(display.deref(), &resources, (options.post_process)(display.deref(), &resources))
+                .with_hot_reload(options.shader_hot_reload);
+            match BlockTextures::load(&resources, |name| renderer.textures().layer(name)){
+                Ok(textures) => world.insert(textures),
+                Err(error) => println!("Couldn't load block definitions: {}", error),
+            }
+            frame_dispatcher.add_thread_local(renderer);
+        }
+
+        world.insert(resources);
+
+        Game{
+            world,
+            input_dispatcher: input_dispatcher.build(),
+            fixed_dispatcher: fixed_dispatcher.build(),
+            frame_dispatcher: frame_dispatcher.build(),
+        }
+    }
+}
+
+impl Game<'_, '_>{
+    /// The simulation stops while the window doesn't have focus
+    pub fn paused(&self) -> bool{
+        self.world.read_resource::<CursorState>().paused()
+    }
+
+    /// Hash of the world's blocks and every player's transform, for checking a replay ended up where the recording did
+    pub fn checksum(&self) -> u64{
+        let mut checksum = Checksum::new();
+        checksum.write(&self.world.read_resource::<ChunkStorage>().checksum().to_le_bytes());
+        let players = self.world.read_storage::<Player>();
+        let positions = self.world.read_storage::<Position>();
+        let rotations = self.world.read_storage::<Rotation>();
+        for (_, position, rotation) in (&players, &positions, &rotations).join(){
+            for value in [position[0], position[1], position[2]].iter().chain(rotation.forward().iter()).chain(rotation.up().iter()){
+                checksum.write(&value.to_bits().to_le_bytes());
+            }
+        }
+        checksum.finish()
+    }
+}
+
+impl GameState for Game<'_, '_>{
+    fn tick_length(&self) -> Duration{
+        self.world.read_resource::<Time>().tick_length()
+    }
+
+    fn fixed_update(&mut self){
+        //Input keeps being read while paused so the game can see focus coming back
+        self.input_dispatcher.dispatch(&self.world);
+        if !self.paused(){
+            self.world.write_resource::<Time>().begin_tick();
+            self.fixed_dispatcher.dispatch(&self.world);
+        }
+        self.world.maintain();
+    }
+
+    fn frame(&mut self, frame_time: Duration, alpha: f32){
+        //Nothing moves while paused, so draw where the last tick left things
+        let alpha = if self.paused() {1.0} else {alpha};
+        self.world.write_resource::<Time>().begin_frame(frame_time, alpha);
+        self.frame_dispatcher.dispatch(&self.world);
+        self.world.maintain();
+    }
+}
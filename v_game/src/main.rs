@@ -1,69 +1,37 @@
-use v_inputs::*;
-use v_renderer::*;
-use v_transform::*;
+use v_game::*;
+use v_inputs::InputRecorder;
 use v_windowing::*;
-use v_agents::*;
-use v_voxels::*;
 
-use std::sync::{Arc, Mutex};
-
-use nalgebra as na;
-use na::{Matrix4, Vector3};
-
-use specs::prelude::*;
-
-use glium::buffer::BufferType::TransformFeedbackBuffer;
-use glium::uniforms::EmptyUniforms;
-use glium::*;
-use glutin::CreationError::Window;
-use std::ops::Deref;
-use std::{time, thread};
+use std::env;
 
+/// usage: v_game [--record <input recording>] [--pack <resource pack>]... [--dev-shaders]
+/// Packs are directories or zip archives, ones given later override earlier ones.
+/// --dev-shaders reads the shaders from src/shaders in place of the built in ones, packs still override them, and recompiles them when they're saved.
 fn main() {
-    let glium_state = GliumState::new();
-    let mut world = World::new();
-
-
-    world.register::<MeshRenderer<VoxelVertex>>();
-    world.register::<Position>();
-    world.register::<Scale>();
-    world.register::<Rotation>();
-    world.register::<TransformMatrix>();
-    world.register::<Camera>();
-    world.register::<Player>();
-    world.register::<ChunkMarker>();
-
-    world.insert(Inputs::default());
-    world.insert(glium_state.display.clone());
-    world.insert(CursorState::default());
-    world.insert(ChunkStorage::new());
-
-    world.create_entity().with(Camera{fov: 1.57, znear: 0.001, zfar: 4096.0}).with(Position::new(0.0, 0.0, 0.0)).with(Rotation::new()).with(Player{}).with(TransformMatrix::default()).build();
-
-    let (window_inputs, hardware_inputs) = glium_state.input_queues();
+    let mut args = env::args().skip(1);
+    let mut options = GameOptions::default();
+    while let Some(arg) = args.next(){
+        match arg.as_str(){
+            "--dev-shaders" => options.shader_hot_reload = true,
+            "--record" | "--pack" => match (arg.as_str(), args.next()){
+                ("--record", Some(path)) => match InputRecorder::create(&path){
+                    Ok(recorder) => options.input_log = InputLog::Record(recorder),
+                    Err(e) => println!("Couldn't record inputs to {}: {:?}", path, e),
+                },
+                (_, Some(path)) => options.resource_packs.push(path.into()),
+                (_, None) => println!("{} needs a path", arg),
+            },
+            _ => println!("Ignoring argument {}", arg),
+        }
+    }
 
-    let mut dispatcher = DispatcherBuilder::new()
-        .with_thread_local(InputSystem::new(window_inputs.clone(), hardware_inputs.clone()))
-        .with_thread_local(CursorLockSystem{})
-        .with(PlayerMovement{}, "player_movement", &[])
-        .with(NewChunkPlacementSystem{}, "chunk_placer", &[])
-        .with_thread_local(ChunkMesherSystem{})
-        .with(TransformSystem, "transform_system", &["player_movement"])
-        .with_thread_local(VoxelRenderingSystem::new(glium_state.display.as_ref().unwrap().lock().unwrap().deref()))
-        .build();
+    match WindowConfig::load(WINDOW_CONFIG_FILE){
+        Ok(config) => options.window = config,
+        Err(WindowConfigError::Io(std::io::ErrorKind::NotFound)) => {},
+        Err(e) => println!("Couldn't load {}: {:?}", WINDOW_CONFIG_FILE, e),
+    }
 
-    let game = Game{world, dispatcher};
+    let glium_state = GliumState::with_config(&options.window);
+    let game = Game::with_options(glium_state.display.clone(), glium_state.input_queues(), options);
     glium_state.run_event_loop(game);
 }
-
-struct Game<'a, 'b>{
-    world: World,
-    dispatcher: Dispatcher<'a, 'b>,
-}
-
-impl GameState for Game<'_, '_>{
-    fn game_loop(&mut self) {
-        self.dispatcher.dispatch(&self.world);
-        self.world.maintain();
-    }
-}
\ No newline at end of file
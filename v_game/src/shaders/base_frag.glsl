@@ -2,11 +2,99 @@
 
 in vec2 o_tex_coord;
 flat in uint o_tex_index;
+flat in uint o_face;
+in vec3 o_world_position;
 
 uniform sampler2DArray tex;
+//Frame count and milliseconds per frame for each layer, see TextureSet::animations
+uniform usampler1D animations;
+uniform float animation_time;
+
+uniform vec3 light_direction;
+uniform vec3 light_color;
+uniform vec3 ambient_color;
+uniform vec3 camera_position;
+uniform vec3 fog_color;
+uniform float fog_start;
+uniform float fog_end;
+
+//Shadow cascades, see ShadowMaps
+uniform int cascade_count;
+uniform vec4 cascade_splits;
+uniform vec3 camera_forward;
+uniform mat4 light_view_projection0;
+uniform mat4 light_view_projection1;
+uniform mat4 light_view_projection2;
+uniform mat4 light_view_projection3;
+uniform sampler2DShadow shadow_map0;
+uniform sampler2DShadow shadow_map1;
+uniform sampler2DShadow shadow_map2;
+uniform sampler2DShadow shadow_map3;
 
 out vec4 color;
 
+//Indexed by face, the same order as VoxelVertex's lighting
+const vec3 FACE_NORMALS[6] = vec3[6](
+    vec3(0.0, 1.0, 0.0), vec3(0.0, -1.0, 0.0),
+    vec3(1.0, 0.0, 0.0), vec3(-1.0, 0.0, 0.0),
+    vec3(0.0, 0.0, 1.0), vec3(0.0, 0.0, -1.0));
+
+const float SHADOW_BIAS = 0.0005;
+//World units positions are moved off their face before looking them up, so faces don't shadow themselves
+const float NORMAL_OFFSET = 0.05;
+
+//How lit a position is in one cascade, 3x3 PCF on top of the hardware's 2x2
+float cascade_light(sampler2DShadow shadow_map, mat4 light_view_projection, vec3 position) {
+    vec4 light_position = light_view_projection * vec4(position, 1.0);
+    vec3 coords = light_position.xyz / light_position.w * 0.5 + 0.5;
+    if (any(lessThan(coords, vec3(0.0))) || any(greaterThan(coords, vec3(1.0)))) {
+        return 1.0;
+    }
+    vec2 texel = 1.0 / vec2(textureSize(shadow_map, 0));
+    float lit = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            lit += texture(shadow_map, vec3(coords.xy + vec2(x, y) * texel, coords.z - SHADOW_BIAS));
+        }
+    }
+    return lit / 9.0;
+}
+
+//Picks the cascade from the distance along the view, past the last one there are no shadows
+float shadow_light(vec3 normal) {
+    float depth = dot(o_world_position - camera_position, camera_forward);
+    vec3 position = o_world_position + normal * NORMAL_OFFSET;
+    if (cascade_count > 0 && depth < cascade_splits[0]) {
+        return cascade_light(shadow_map0, light_view_projection0, position);
+    }
+    if (cascade_count > 1 && depth < cascade_splits[1]) {
+        return cascade_light(shadow_map1, light_view_projection1, position);
+    }
+    if (cascade_count > 2 && depth < cascade_splits[2]) {
+        return cascade_light(shadow_map2, light_view_projection2, position);
+    }
+    if (cascade_count > 3 && depth < cascade_splits[3]) {
+        return cascade_light(shadow_map3, light_view_projection3, position);
+    }
+    return 1.0;
+}
+
+//Animated textures step through the layers after their first
+uint texture_layer() {
+    uvec2 animation = texelFetch(animations, int(o_tex_index), 0).rg;
+    if (animation.x > 1u) {
+        return o_tex_index + uint(animation_time * 1000.0 / float(animation.y)) % animation.x;
+    }
+    return o_tex_index;
+}
+
 void main() {
-    color = texture(tex, vec3(o_tex_coord, float(o_tex_index)));
+    vec4 albedo = texture(tex, vec3(o_tex_coord, float(texture_layer())));
+    vec3 normal = FACE_NORMALS[min(o_face, 5u)];
+    float facing = max(dot(normal, light_direction), 0.0);
+    vec3 light = ambient_color + light_color * facing * (facing > 0.0 ? shadow_light(normal) : 1.0);
+    vec3 lit = albedo.rgb * light;
+
+    float fog = clamp((distance(o_world_position, camera_position) - fog_start) / max(fog_end - fog_start, 0.0001), 0.0, 1.0);
+    color = vec4(mix(lit, fog_color, fog), albedo.a);
 }
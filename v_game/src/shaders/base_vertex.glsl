@@ -4,14 +4,20 @@ in vec3 position;
 in vec2 tex_coord;
 in uint tex_index;
 in int lighting;
+//Per chunk, chunk meshes are drawn together out of shared buffers
+in vec3 chunk_offset;
 
-uniform mat4 mvp;
+uniform mat4 view_projection;
 
 flat out uint o_tex_index;
 out vec2 o_tex_coord;
+flat out uint o_face;
+out vec3 o_world_position;
 
 void main() {
-    gl_Position = mvp * vec4(position, 1.0);
+    o_world_position = position + chunk_offset;
+    gl_Position = view_projection * vec4(o_world_position, 1.0);
     o_tex_index = tex_index;
     o_tex_coord = tex_coord;
+    o_face = uint(lighting);
 }
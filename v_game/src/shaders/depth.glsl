@@ -0,0 +1,5 @@
+//View space position of the surface in a depth map
+vec3 view_position_at(sampler2D depth_map, mat4 inverse_projection_matrix, vec2 uv) {
+    vec4 view = inverse_projection_matrix * vec4(vec3(uv, texture(depth_map, uv).r) * 2.0 - 1.0, 1.0);
+    return view.xyz / view.w;
+}
@@ -0,0 +1,4 @@
+//Corner of one triangle covering the screen for gl_VertexID 0 to 2, from (0, 0) past (1, 1)
+vec2 fullscreen_corner() {
+    return vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2));
+}
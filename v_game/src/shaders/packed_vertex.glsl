@@ -0,0 +1,29 @@
+#version 450
+
+//See PackedVoxelVertex for the layout
+in uvec2 data;
+//Per chunk, chunk meshes are drawn together out of shared buffers
+in vec3 chunk_offset;
+
+uniform mat4 view_projection;
+uniform float block_size;
+
+flat out uint o_tex_index;
+out vec2 o_tex_coord;
+flat out uint o_face;
+out vec3 o_world_position;
+
+const vec2 CORNER_TEX_COORDS[4] = vec2[4](vec2(1.0, 1.0), vec2(1.0, 0.0), vec2(0.0, 0.0), vec2(0.0, 1.0));
+
+void main() {
+    uvec3 grid_position = uvec3(data.x & 63u, (data.x >> 6) & 63u, (data.x >> 12) & 63u);
+    uint face = (data.x >> 18) & 7u;
+    uint corner = (data.x >> 21) & 3u;
+    vec3 position = (vec3(grid_position) - 0.5) * block_size;
+
+    o_world_position = position + chunk_offset;
+    gl_Position = view_projection * vec4(o_world_position, 1.0);
+    o_tex_index = data.y & 65535u;
+    o_tex_coord = CORNER_TEX_COORDS[corner];
+    o_face = face;
+}
@@ -0,0 +1,49 @@
+#version 450
+
+in vec2 o_uv;
+
+uniform sampler2D color;
+uniform vec2 texel_size;
+
+out vec4 out_color;
+
+#define REDUCE_MIN (1.0 / 128.0)
+#define REDUCE_MUL (1.0 / 8.0)
+#define SPAN_MAX 8.0
+
+const vec3 LUMA = vec3(0.299, 0.587, 0.114);
+
+//Blurs along edges found from the brightness of the corners around each pixel
+void main() {
+    vec3 north_west = texture(color, o_uv + vec2(-1.0, -1.0) * texel_size).rgb;
+    vec3 north_east = texture(color, o_uv + vec2(1.0, -1.0) * texel_size).rgb;
+    vec3 south_west = texture(color, o_uv + vec2(-1.0, 1.0) * texel_size).rgb;
+    vec3 south_east = texture(color, o_uv + vec2(1.0, 1.0) * texel_size).rgb;
+    vec4 middle = texture(color, o_uv);
+
+    float luma_north_west = dot(north_west, LUMA);
+    float luma_north_east = dot(north_east, LUMA);
+    float luma_south_west = dot(south_west, LUMA);
+    float luma_south_east = dot(south_east, LUMA);
+    float luma_middle = dot(middle.rgb, LUMA);
+    float luma_min = min(luma_middle, min(min(luma_north_west, luma_north_east), min(luma_south_west, luma_south_east)));
+    float luma_max = max(luma_middle, max(max(luma_north_west, luma_north_east), max(luma_south_west, luma_south_east)));
+
+    vec2 direction = vec2(
+        (luma_south_west + luma_south_east) - (luma_north_west + luma_north_east),
+        (luma_north_west + luma_south_west) - (luma_north_east + luma_south_east));
+    float reduce = max((luma_north_west + luma_north_east + luma_south_west + luma_south_east) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    float scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * scale, -SPAN_MAX, SPAN_MAX) * texel_size;
+
+    vec3 inner = 0.5 * (
+        texture(color, o_uv + direction * (1.0 / 3.0 - 0.5)).rgb +
+        texture(color, o_uv + direction * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 outer = inner * 0.5 + 0.25 * (
+        texture(color, o_uv - direction * 0.5).rgb +
+        texture(color, o_uv + direction * 0.5).rgb);
+
+    //The wider blur is only used if it didn't pull in colours from past the edge
+    float luma_outer = dot(outer, LUMA);
+    out_color = vec4(luma_outer < luma_min || luma_outer > luma_max ? inner : outer, middle.a);
+}
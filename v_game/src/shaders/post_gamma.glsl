@@ -0,0 +1,13 @@
+#version 450
+
+in vec2 o_uv;
+
+uniform sampler2D color;
+uniform float gamma;
+
+out vec4 out_color;
+
+void main() {
+    vec4 linear = texture(color, o_uv);
+    out_color = vec4(pow(max(linear.rgb, vec3(0.0)), vec3(1.0 / gamma)), linear.a);
+}
@@ -0,0 +1,60 @@
+#version 450
+#include "depth.glsl"
+
+in vec2 o_uv;
+
+uniform sampler2D color;
+uniform sampler2D depth;
+uniform mat4 projection;
+uniform mat4 inverse_projection;
+uniform float ssao_radius;
+uniform float ssao_strength;
+
+out vec4 out_color;
+
+#define SAMPLES 16
+
+vec3 view_position(vec2 uv) {
+    return view_position_at(depth, inverse_projection, uv);
+}
+
+//Darkens points by how much of the hemisphere above them is behind other surfaces
+void main() {
+    vec4 scene = texture(color, o_uv);
+    //The sky has nothing to occlude it
+    if (texture(depth, o_uv).r >= 1.0) {
+        out_color = scene;
+        return;
+    }
+
+    vec3 position = view_position(o_uv);
+    vec3 normal = normalize(cross(dFdx(position), dFdy(position)));
+
+    //Samples are turned a different way for every pixel so the banding turns into fine noise
+    float angle = fract(sin(dot(gl_FragCoord.xy, vec2(12.9898, 78.233))) * 43758.5453) * 6.2831853;
+    vec3 tangent = normalize(cross(normal, abs(normal.y) < 0.99 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0)));
+    tangent = tangent * cos(angle) + cross(normal, tangent) * sin(angle);
+    vec3 bitangent = cross(normal, tangent);
+
+    float bias = 0.02 * ssao_radius - 0.001 * position.z;
+    float occlusion = 0.0;
+    for (int i = 0; i < SAMPLES; i++) {
+        //Spiral over the hemisphere, later samples reach further out
+        float fraction = (float(i) + 0.5) / float(SAMPLES);
+        float spin = float(i) * 2.3999632;
+        float height = sqrt(1.0 - fraction);
+        float across = sqrt(1.0 - height * height);
+        vec3 direction = tangent * cos(spin) * across + bitangent * sin(spin) * across + normal * height;
+        vec3 sample_position = position + direction * ssao_radius * mix(0.1, 1.0, fraction * fraction);
+
+        vec4 clip = projection * vec4(sample_position, 1.0);
+        vec2 sample_uv = clip.xy / clip.w * 0.5 + 0.5;
+        float surface = view_position(sample_uv).z;
+        //Surfaces far in front of the point are something else and don't count
+        float range = smoothstep(0.0, 1.0, ssao_radius / max(abs(position.z - surface), 0.0001));
+        occlusion += (surface >= sample_position.z + bias ? 1.0 : 0.0) * range;
+    }
+
+    float ambient = clamp(1.0 - occlusion / float(SAMPLES) * ssao_strength, 0.0, 1.0);
+    out_color = vec4(scene.rgb * ambient, scene.a);
+}
@@ -0,0 +1,18 @@
+#version 450
+
+in vec2 o_uv;
+
+uniform sampler2D color;
+uniform float exposure;
+
+out vec4 out_color;
+
+//Filmic curve fitted to ACES, maps any brightness into 0 to 1
+vec3 aces(vec3 x) {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main() {
+    vec4 scene = texture(color, o_uv);
+    out_color = vec4(aces(scene.rgb * exposure), scene.a);
+}
@@ -0,0 +1,22 @@
+#version 450
+#include "depth.glsl"
+
+in vec2 o_uv;
+
+uniform sampler2D color;
+uniform sampler2D depth;
+uniform mat4 inverse_projection;
+uniform vec3 tint_color;
+uniform float tint_density;
+
+out vec4 out_color;
+
+void main() {
+    vec4 scene = texture(color, o_uv);
+    float distance = length(view_position_at(depth, inverse_projection, o_uv));
+
+    //Water soaks up red first, then everything fades into the tint with distance
+    vec3 absorbed = scene.rgb * mix(vec3(1.0), normalize(tint_color + 0.001) * 1.5, 0.5);
+    float amount = 1.0 - exp(-distance * tint_density);
+    out_color = vec4(mix(absorbed, tint_color, amount), scene.a);
+}
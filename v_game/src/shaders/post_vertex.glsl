@@ -0,0 +1,11 @@
+#version 450
+#include "fullscreen.glsl"
+
+out vec2 o_uv;
+
+//One triangle covering the screen
+void main() {
+    vec2 corner = fullscreen_corner();
+    o_uv = corner;
+    gl_Position = vec4(corner * 2.0 - 1.0, 0.0, 1.0);
+}
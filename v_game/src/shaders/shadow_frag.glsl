@@ -0,0 +1,5 @@
+#version 450
+
+//Only depth is written into shadow maps
+void main() {
+}
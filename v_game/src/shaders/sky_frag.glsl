@@ -0,0 +1,23 @@
+#version 450
+
+in vec3 o_direction;
+
+uniform vec3 zenith_color;
+uniform vec3 horizon_color;
+uniform vec3 sun_direction;
+uniform vec3 moon_direction;
+
+out vec4 color;
+
+void main() {
+    vec3 direction = normalize(o_direction);
+    vec3 sky = mix(horizon_color, zenith_color, sqrt(clamp(direction.y, 0.0, 1.0)));
+
+    float sun_angle = dot(direction, sun_direction);
+    float sun = smoothstep(0.9990, 0.9995, sun_angle) + pow(max(sun_angle, 0.0), 64.0) * 0.4;
+    float moon = smoothstep(0.9994, 0.9997, dot(direction, moon_direction));
+    //Nothing shows through the ground
+    float above = smoothstep(-0.05, 0.0, direction.y);
+
+    color = vec4(sky + (vec3(1.0, 0.9, 0.7) * sun + vec3(0.8, 0.85, 0.9) * moon) * above, 1.0);
+}
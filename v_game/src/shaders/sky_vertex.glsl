@@ -0,0 +1,16 @@
+#version 450
+#include "fullscreen.glsl"
+
+uniform mat4 inverse_view_projection;
+
+out vec3 o_direction;
+
+//One triangle covering the screen, drawn at the far plane
+void main() {
+    vec2 corner = fullscreen_corner() * 2.0 - 1.0;
+    gl_Position = vec4(corner, 1.0, 1.0);
+
+    vec4 near = inverse_view_projection * vec4(corner, -1.0, 1.0);
+    vec4 far = inverse_view_projection * vec4(corner, 1.0, 1.0);
+    o_direction = far.xyz / far.w - near.xyz / near.w;
+}
@@ -0,0 +1,185 @@
+use v_game::*;
+use v_windowing::*;
+use v_voxels::*;
+use v_transform::*;
+use v_agents::*;
+use v_commands::*;
+use v_inputs::{InputRecorder, InputRecording, InputReplay, CursorState, CursorMode};
+use v_renderer::{MeshRenderer, VoxelVertex, TimeOfDay};
+
+use glutin::event::{DeviceEvent, DeviceId, ElementState, KeyboardInput, MouseButton};
+use nalgebra::Vector3;
+use specs::prelude::*;
+
+#[test]
+fn world_edit_without_a_window(){
+    let headless = HeadlessState::new();
+    let mut game = Game::new(headless.display.clone(), headless.input_queues());
+
+    {
+        let mut commands = game.world.write_resource::<Commands>();
+        commands.push("pos1 0 0 0");
+        commands.push("pos2 40 3 3");
+        commands.push("fill rock");
+    }
+    headless.run_ticks(&mut game, 5);
+
+    let chunks = game.world.read_resource::<ChunkStorage>();
+    assert_eq!(chunks.get_block_at(Vector3::new(40, 3, 3)), BlockType::Rock);
+    assert_eq!(chunks.get_block_at(Vector3::new(41, 3, 3)), BlockType::Air);
+    assert!(game.world.read_storage::<ChunkMarker>().join().count() > 0);
+    assert_eq!(game.world.read_storage::<MeshRenderer<VoxelVertex>>().join().count(), 0);
+}
+
+#[test]
+#[allow(deprecated)]
+fn player_walks_forward(){
+    let headless = HeadlessState::new();
+    let mut game = Game::new(headless.display.clone(), headless.input_queues());
+
+    headless.hardware_inputs.lock().unwrap().push(DeviceEvent::Key(KeyboardInput{
+        scancode: 17,
+        state: ElementState::Pressed,
+        virtual_keycode: None,
+        modifiers: Default::default(),
+    }));
+    headless.run_ticks(&mut game, 10);
+
+    let players = game.world.read_storage::<Player>();
+    let positions = game.world.read_storage::<Position>();
+    let (_, position) = (&players, &positions).join().next().unwrap();
+    assert!(position[2] < 0.0);
+    assert_eq!(position[0], 0.0);
+}
+
+#[test]
+fn ticks_follow_tick_rate(){
+    let headless = HeadlessState::new();
+    let mut game = Game::with_tick_rate(headless.display.clone(), headless.input_queues(), 20.0);
+    headless.run_ticks(&mut game, 10);
+
+    let time = game.world.read_resource::<Time>();
+    assert_eq!(time.tick_count(), 10);
+    assert_eq!(time.frame_count(), 10);
+    assert!((time.elapsed() - 0.5).abs() < 0.001);
+    assert!((time.fixed_delta() - 0.05).abs() < 0.0001);
+}
+
+/// The block at the point the player places blocks at
+fn placed_block(game: &Game) -> BlockType{
+    let players = game.world.read_storage::<Player>();
+    let positions = game.world.read_storage::<Position>();
+    let rotations = game.world.read_storage::<Rotation>();
+    let (_, position, rotation) = (&players, &positions, &rotations).join().next().unwrap();
+    game.world.read_resource::<ChunkStorage>().get_block_at(ChunkStorage::world_to_block(&target_point(position, rotation)))
+}
+
+#[test]
+#[allow(deprecated)]
+fn replay_matches_recording(){
+    let path = std::env::temp_dir().join("v_game_replay_test.vinput");
+    let press = |scancode, state| DeviceEvent::Key(KeyboardInput{scancode, state, virtual_keycode: None, modifiers: Default::default()});
+
+    let headless = HeadlessState::new();
+    let options = GameOptions{input_log: InputLog::Record(InputRecorder::create(&path).unwrap()), ..GameOptions::default()};
+    let mut game = Game::with_options(headless.display.clone(), headless.input_queues(), options);
+    headless.hardware_inputs.lock().unwrap().push(press(17, ElementState::Pressed));
+    headless.hardware_inputs.lock().unwrap().push(DeviceEvent::MouseMotion{delta: (120.0, -35.5)});
+    headless.run_ticks(&mut game, 20);
+    assert_eq!(placed_block(&game), BlockType::Air);
+    headless.hardware_inputs.lock().unwrap().push(press(17, ElementState::Released));
+    headless.window_inputs.lock().unwrap().push(ApplicationEvent::MouseInput{
+        device_id: unsafe {DeviceId::dummy()},
+        state: ElementState::Pressed,
+        button: MouseButton::Left,
+        modifiers: Default::default(),
+    });
+    headless.run_ticks(&mut game, 5);
+    let placed = placed_block(&game);
+    assert_eq!(placed, BlockType::Grass);
+    let recorded = game.checksum();
+    drop(game);
+
+    let recording = InputRecording::load(&path).unwrap();
+    assert_eq!(recording.len(), 4);
+    let headless = HeadlessState::new();
+    let options = GameOptions{input_log: InputLog::Replay(InputReplay::new(recording)), ..GameOptions::default()};
+    let mut replayed = Game::with_options(headless.display.clone(), headless.input_queues(), options);
+    headless.run_ticks(&mut replayed, 25);
+    assert_eq!(placed_block(&replayed), placed);
+    assert_eq!(replayed.checksum(), recorded);
+
+    let untouched = Game::new(headless.display.clone(), headless.input_queues());
+    assert_ne!(untouched.checksum(), recorded);
+}
+
+#[test]
+fn replay_includes_console_commands(){
+    let path = std::env::temp_dir().join("v_game_console_replay_test.vinput");
+    let typed = ConsoleLines::default();
+
+    let headless = HeadlessState::new();
+    let options = GameOptions{input_log: InputLog::Record(InputRecorder::create(&path).unwrap()), console: Some(typed.clone()), ..GameOptions::default()};
+    let mut game = Game::with_options(headless.display.clone(), headless.input_queues(), options);
+    headless.run_ticks(&mut game, 3);
+    typed.lock().unwrap().push("time set noon".to_string());
+    typed.lock().unwrap().push("  ".to_string());
+    headless.run_ticks(&mut game, 3);
+    let recorded = game.world.read_resource::<WorldTime>().time_of_day();
+    assert!((recorded.fraction() - 0.5).abs() < 0.01);
+    drop(game);
+
+    //The replay has no console of its own, the recorded command still reaches the game on the tick it was typed on
+    let recording = InputRecording::load(&path).unwrap();
+    assert_eq!(recording.len(), 1);
+    let headless = HeadlessState::new();
+    let options = GameOptions{input_log: InputLog::Replay(InputReplay::new(recording)), ..GameOptions::default()};
+    let mut replayed = Game::with_options(headless.display.clone(), headless.input_queues(), options);
+    headless.run_ticks(&mut replayed, 6);
+    assert_eq!(replayed.world.read_resource::<WorldTime>().time_of_day(), recorded);
+}
+
+#[test]
+fn focus_loss_pauses(){
+    let headless = HeadlessState::new();
+    let mut game = Game::new(headless.display.clone(), headless.input_queues());
+    headless.run_ticks(&mut game, 2);
+
+    headless.window_inputs.lock().unwrap().push(ApplicationEvent::Focused(false));
+    headless.run_ticks(&mut game, 5);
+    assert!(game.paused());
+    assert_eq!(game.world.read_resource::<CursorState>().mode(), CursorMode::Free);
+    assert_eq!(game.world.read_resource::<Time>().tick_count(), 2);
+
+    headless.window_inputs.lock().unwrap().push(ApplicationEvent::Focused(true));
+    headless.run_ticks(&mut game, 3);
+    assert!(!game.paused());
+    assert_eq!(game.world.read_resource::<CursorState>().mode(), CursorMode::Free);
+    assert_eq!(game.world.read_resource::<Time>().tick_count(), 5);
+}
+
+#[test]
+fn day_night_cycle_without_a_window(){
+    let headless = HeadlessState::new();
+    let options = GameOptions{tick_rate: 20.0, day_length: 10.0, ..GameOptions::default()};
+    let mut game = Game::with_options(headless.display.clone(), headless.input_queues(), options);
+
+    game.world.write_resource::<Commands>().push("time set noon");
+    headless.run_ticks(&mut game, 1);
+    //Half a day at 20 ticks a second
+    headless.run_ticks(&mut game, 100);
+    {
+        let time = game.world.read_resource::<WorldTime>();
+        assert!(time.time_of_day().fraction() < 0.01);
+        assert_eq!(time.day(), 1);
+        assert_eq!(time.sky_light(), 0.0);
+        assert_eq!(*game.world.read_resource::<TimeOfDay>(), time.time_of_day());
+    }
+
+    game.world.write_resource::<Commands>().push("time set dawn");
+    game.world.write_resource::<Commands>().push("time freeze");
+    headless.run_ticks(&mut game, 50);
+    let time = game.world.read_resource::<WorldTime>();
+    assert!(time.frozen());
+    assert_eq!(time.time_of_day().fraction(), 0.25);
+}
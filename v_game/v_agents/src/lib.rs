@@ -1,11 +1,15 @@
 use v_transform::*;
 use v_voxels::*;
-use v_inputs::{Inputs, KeyCode, KeyState, ButtonCode};
+use v_inputs::Inputs;
+use v_commands::{Commands, Command, CommandError};
 use nalgebra as na;
 use specs::prelude::*;
 use specs::storage::BTreeStorage;
 use nalgebra::Vector3;
 
+mod world_time;
+pub use world_time::*;
+
 #[derive(Default)]
 pub struct Player{}
 
@@ -13,18 +17,23 @@ impl Component for Player{
     type Storage = NullStorage<Self>;
 }
 
+/// World units per second the player flies at
+pub const PLAYER_SPEED: f32 = 4.0;
+
 pub struct PlayerMovement{}
 
 impl<'a> System<'a> for PlayerMovement{
     type SystemData = (
         Read<'a, Inputs>,
+        Read<'a, Time>,
         ReadStorage<'a, Player>,
         WriteStorage<'a, Position>,
         WriteStorage<'a, Rotation>,
         Write<'a, ChunkStorage>
     );
 
-    fn run(&mut self, (inputs, players, mut positions, mut rotations, mut chunks) : Self::SystemData){
+    fn run(&mut self, (inputs, time, players, mut positions, mut rotations, mut chunks) : Self::SystemData){
+        let speed = PLAYER_SPEED * time.fixed_delta();
         for (player, position, rotation) in (&players, &mut positions, &mut rotations).join(){
             let mut delta = (*inputs.get_mouse_delta()).clone();
             delta /= 1000.0;
@@ -33,24 +42,19 @@ impl<'a> System<'a> for PlayerMovement{
             rotation.apply_axis_angle_rotation(-delta.x, Vector3::new(0.0, 1.0, 0.0));
             rotation.apply_axis_angle_rotation(-delta.y, rotation.right());
 
-            let y: f32 =
-                if *inputs.get_key(&KeyCode::W) == KeyState::Down {-0.0025} else {0.0} +
-                if *inputs.get_key(&KeyCode::S) == KeyState::Down {0.0025} else {0.0};
+            let y = -inputs.axis("move_forward") * speed;
+            let x = inputs.axis("move_x") * speed;
 
-            let x: f32 =
-                if *inputs.get_key(&KeyCode::D) == KeyState::Down {0.0025} else {0.0} +
-                if *inputs.get_key(&KeyCode::A) == KeyState::Down {-0.0025} else {0.0};
-
-            let place_coord = *position.value() + -rotation.forward() * 3.0;
-            if *inputs.get_button(&ButtonCode::MB0) == KeyState::Pressed{
+            let place_coord = target_point(position, rotation);
+            if inputs.action_pressed("place_grass"){
                 chunks.set_block(&BlockType::Grass, &place_coord);
             }
 
-            if *inputs.get_button(&ButtonCode::MB1) == KeyState::Pressed{
+            if inputs.action_pressed("place_rock"){
                 chunks.set_block(&BlockType::Rock, &place_coord)
             }
 
-            if *inputs.get_button(&ButtonCode::MB2) == KeyState::Pressed{
+            if inputs.action_pressed("break_block"){
                 chunks.set_block(&BlockType::Air, &place_coord)
             }
 
@@ -58,4 +62,212 @@ impl<'a> System<'a> for PlayerMovement{
             let pos =  position.value();
         }
     }
-}
\ No newline at end of file
+}
+
+/// The point in front of a player that blocks get placed at
+pub fn target_point(position: &Position, rotation: &Rotation) -> Vector3<f32>{
+    Vector3::new(position[0], position[1], position[2]) + -rotation.forward() * 3.0
+}
+
+/// Selection and clipboard used by the world edit commands
+#[derive(Default)]
+pub struct WorldEdit{
+    pub selection: Selection,
+    pub clipboard: Option<BlockVolume>,
+    pub vox_table: VoxBlockTable,
+}
+
+const WORLD_EDIT_COMMANDS: &[&str] = &["pos1", "pos2", "fill", "replace", "hollow", "walls", "sphere", "cyl", "copy", "paste", "rotate", "mirror", "stack", "loadvox", "savevox", "saveworld", "loadworld"];
+
+/// Runs world edit commands, positions default to the block the player is looking at
+pub struct WorldEditSystem{}
+
+impl WorldEditSystem{
+    fn block_arg(command: &Command, index: usize) -> Result<BlockType, CommandError>{
+        let name = command.arg(index).ok_or(CommandError::MissingArgument(index))?;
+        BlockType::from_name(name).ok_or(CommandError::InvalidArgument(name.to_string()))
+    }
+
+    fn axis_arg(command: &Command, index: usize) -> Result<Vector3<i32>, CommandError>{
+        match command.arg(index).ok_or(CommandError::MissingArgument(index))?{
+            "x" => Ok(Vector3::new(1, 0, 0)),
+            "-x" => Ok(Vector3::new(-1, 0, 0)),
+            "y" => Ok(Vector3::new(0, 1, 0)),
+            "-y" => Ok(Vector3::new(0, -1, 0)),
+            "z" => Ok(Vector3::new(0, 0, 1)),
+            "-z" => Ok(Vector3::new(0, 0, -1)),
+            arg => Err(CommandError::InvalidArgument(arg.to_string())),
+        }
+    }
+
+    fn position_arg(command: &Command, target: Vector3<i32>) -> Result<Vector3<i32>, CommandError>{
+        if command.args.is_empty(){
+            return Ok(target);
+        }
+        Ok(Vector3::new(command.parse_arg(0)?, command.parse_arg(1)?, command.parse_arg(2)?))
+    }
+
+    fn radius_arg(command: &Command, index: usize) -> Result<f32, CommandError>{
+        let radius: f32 = command.parse_arg(index)?;
+        if radius.is_nan() || radius > MAX_BRUSH_RADIUS{
+            return Err(CommandError::Failed(format!("a radius of {} is too big, brushes go up to {}", radius, MAX_BRUSH_RADIUS)));
+        }
+        Ok(radius)
+    }
+
+    fn height_arg(command: &Command, index: usize) -> Result<usize, CommandError>{
+        let height: usize = command.parse_arg(index)?;
+        if height > MAX_BRUSH_HEIGHT{
+            return Err(CommandError::Failed(format!("a height of {} is too big, brushes go up to {}", height, MAX_BRUSH_HEIGHT)));
+        }
+        Ok(height)
+    }
+
+    fn region(edit: &WorldEdit) -> Result<Region, CommandError>{
+        edit.selection.region().ok_or(CommandError::Failed("select two corners with pos1 and pos2 first".to_string()))
+    }
+
+    fn clipboard(edit: &WorldEdit) -> Result<&BlockVolume, CommandError>{
+        edit.clipboard.as_ref().ok_or(CommandError::Failed("nothing has been copied".to_string()))
+    }
+
+    pub fn run_command(command: &Command, target: Vector3<i32>, edit: &mut WorldEdit, chunks: &mut ChunkStorage) -> Result<String, CommandError>{
+        match command.name.as_str(){
+            "pos1" => {
+                let position = Self::position_arg(command, target)?;
+                edit.selection.first = Some(position);
+                Ok(format!("first corner set to {:?}", position.as_slice()))
+            },
+            "pos2" => {
+                let position = Self::position_arg(command, target)?;
+                edit.selection.second = Some(position);
+                Ok(format!("second corner set to {:?}", position.as_slice()))
+            },
+            "fill" => {
+                let count = chunks.fill(&Self::region(edit)?, &Self::block_arg(command, 0)?);
+                Ok(format!("{} blocks filled", count))
+            },
+            "replace" => {
+                let count = chunks.replace(&Self::region(edit)?, &Self::block_arg(command, 0)?, &Self::block_arg(command, 1)?);
+                Ok(format!("{} blocks replaced", count))
+            },
+            "hollow" => {
+                chunks.hollow(&Self::region(edit)?, &Self::block_arg(command, 0)?);
+                Ok("selection hollowed".to_string())
+            },
+            "walls" => {
+                chunks.walls(&Self::region(edit)?, &Self::block_arg(command, 0)?);
+                Ok("walls built".to_string())
+            },
+            "sphere" => {
+                let hollow = command.arg(2) == Some("hollow");
+                chunks.sphere(target, Self::radius_arg(command, 1)?, &Self::block_arg(command, 0)?, hollow);
+                Ok("sphere placed".to_string())
+            },
+            "cyl" => {
+                let hollow = command.arg(3) == Some("hollow");
+                chunks.cylinder(target, Self::radius_arg(command, 1)?, Self::height_arg(command, 2)?, &Self::block_arg(command, 0)?, hollow);
+                Ok("cylinder placed".to_string())
+            },
+            "copy" => {
+                let region = Self::region(edit)?;
+                edit.clipboard = Some(chunks.copy(&region, target));
+                Ok(format!("{} blocks copied", region.volume()))
+            },
+            "paste" => {
+                let skip_air = command.arg(0) == Some("-a");
+                let region = chunks.paste(Self::clipboard(edit)?, target, skip_air);
+                Ok(format!("pasted from {:?} to {:?}", region.min.as_slice(), region.max.as_slice()))
+            },
+            "rotate" => {
+                let degrees: i32 = command.parse_arg(0)?;
+                if degrees % 90 != 0{
+                    return Err(CommandError::InvalidArgument(degrees.to_string()));
+                }
+                edit.clipboard = Some(Self::clipboard(edit)?.rotate_y(degrees / 90));
+                Ok(format!("clipboard rotated {} degrees", degrees))
+            },
+            "mirror" => {
+                let axis = Self::axis_arg(command, 0)?.iamax();
+                edit.clipboard = Some(Self::clipboard(edit)?.mirror(axis));
+                Ok("clipboard mirrored".to_string())
+            },
+            "stack" => {
+                let region = chunks.stack(&Self::region(edit)?, Self::axis_arg(command, 1)?, command.parse_arg(0)?);
+                edit.selection.first = Some(region.min);
+                edit.selection.second = Some(region.max);
+                Ok(format!("stacked up to {:?}", region.max.as_slice()))
+            },
+            "loadvox" => {
+                let path: String = command.parse_arg(0)?;
+                let mut volume = load_vox(&path, &edit.vox_table).map_err(|e| CommandError::Failed(format!("couldn't load {}: {:?}", path, e)))?;
+                volume.set_offset(Vector3::new(0, 0, 0));
+                edit.clipboard = Some(volume);
+                Ok(format!("{} loaded into the clipboard", path))
+            },
+            "savevox" => {
+                let path: String = command.parse_arg(0)?;
+                chunks.export_vox(&path, &Self::region(edit)?, &edit.vox_table).map_err(|e| CommandError::Failed(format!("couldn't save {}: {:?}", path, e)))?;
+                Ok(format!("selection saved to {}", path))
+            },
+            "saveworld" => {
+                let path: String = command.parse_arg(0)?;
+                chunks.save_world(&path).map_err(|e| CommandError::Failed(format!("couldn't save {}: {:?}", path, e)))?;
+                Ok(format!("world saved to {}", path))
+            },
+            "loadworld" => {
+                let path: String = command.parse_arg(0)?;
+                let count = chunks.load_world(&path).map_err(|e| CommandError::Failed(format!("couldn't load {}: {:?}", path, e)))?;
+                Ok(format!("{} chunks loaded from {}", count, path))
+            },
+            _ => Err(CommandError::Failed(format!("{} is not a world edit command", command.name))),
+        }
+    }
+}
+
+impl<'a> System<'a> for WorldEditSystem{
+    type SystemData = (
+        Write<'a, Commands>,
+        Write<'a, WorldEdit>,
+        Write<'a, ChunkStorage>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Rotation>
+    );
+
+    fn run(&mut self, (mut commands, mut edit, mut chunks, players, positions, rotations): Self::SystemData){
+        let commands = commands.take(WORLD_EDIT_COMMANDS);
+        if commands.is_empty(){
+            return;
+        }
+
+        let target = match (&players, &positions, &rotations).join().next(){
+            Some((_, position, rotation)) => ChunkStorage::world_to_block(&target_point(position, rotation)),
+            None => Vector3::new(0, 0, 0),
+        };
+
+        for command in commands.iter(){
+            match Self::run_command(command, target, &mut edit, &mut chunks){
+                Ok(message) => println!("{}: {}", command.name, message),
+                Err(error) => println!("{}: {}", command.name, error),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn brushes_past_the_largest_size_are_refused(){
+        let mut edit = WorldEdit::default();
+        let mut chunks = ChunkStorage::new();
+        let mut run = |line: &str| WorldEditSystem::run_command(&Command::parse(line).unwrap(), Vector3::new(0, 0, 0), &mut edit, &mut chunks);
+        assert!(matches!(run("sphere rock 65"), Err(CommandError::Failed(_))));
+        assert!(matches!(run("sphere rock NaN"), Err(CommandError::Failed(_))));
+        assert!(matches!(run("cyl rock 2 257"), Err(CommandError::Failed(_))));
+        assert_eq!(run("cyl rock 2 256"), Ok("cylinder placed".to_string()));
+        assert_eq!(chunks.get_block_at(Vector3::new(0, 255, 0)), BlockType::Rock);
+    }
+}
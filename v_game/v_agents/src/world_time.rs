@@ -0,0 +1,190 @@
+use crate::*;
+use v_transform::{TimeOfDay, SkyLighting};
+
+/// Real seconds in a day unless the game picks something else
+pub const DEFAULT_DAY_LENGTH: f32 = 1200.0;
+
+const WORLD_TIME_COMMANDS: &[&str] = &["time"];
+
+/// Game time counted in days, the fraction is the time of day.
+/// Advanced every tick by WorldTimeSystem, which also hands the time of day on to the renderer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldTime{
+    days: f64,
+    day_length: f32,
+    frozen: bool,
+}
+
+impl WorldTime{
+    pub fn new(day_length: f32) -> Self{
+        WorldTime{
+            days: TimeOfDay::default().fraction() as f64,
+            day_length: day_length.max(1.0),
+            frozen: false,
+        }
+    }
+
+    /// Move time on by some real seconds, frozen time stays put
+    pub fn advance(&mut self, seconds: f32){
+        if !self.frozen{
+            self.days += (seconds / self.day_length) as f64;
+        }
+    }
+
+    /// Whole days that have gone by
+    pub fn day(&self) -> u64{
+        self.days as u64
+    }
+
+    pub fn time_of_day(&self) -> TimeOfDay{
+        TimeOfDay::new(self.days.fract() as f32)
+    }
+
+    /// Jump to a time of day, going forwards into the next day if it's already past it
+    pub fn set_time_of_day(&mut self, fraction: f32){
+        let fraction = fraction.rem_euclid(1.0) as f64;
+        let day = if fraction < self.days.fract() {self.days.floor() + 1.0} else {self.days.floor()};
+        self.days = day + fraction;
+    }
+
+    /// Real seconds a day takes
+    pub fn day_length(&self) -> f32{
+        self.day_length
+    }
+
+    pub fn set_day_length(&mut self, seconds: f32){
+        self.day_length = seconds.max(1.0);
+    }
+
+    pub fn frozen(&self) -> bool{
+        self.frozen
+    }
+
+    pub fn set_frozen(&mut self, frozen: bool){
+        self.frozen = frozen;
+    }
+
+    /// How much sky light there is, 1 in the day down to 0 at night. Chunk faces are lit by this much sun.
+    pub fn sky_light(&self) -> f32{
+        self.time_of_day().daylight()
+    }
+
+    /// Points towards the sun
+    pub fn sun_direction(&self) -> Vector3<f32>{
+        self.time_of_day().sun_direction()
+    }
+
+    /// Light and sky colours for the current time
+    pub fn sky(&self) -> SkyLighting{
+        self.time_of_day().lighting()
+    }
+}
+
+impl Default for WorldTime{
+    fn default() -> Self{
+        Self::new(DEFAULT_DAY_LENGTH)
+    }
+}
+
+/// A time of day as a fraction or one of dawn, noon, dusk and midnight
+fn parse_time_of_day(arg: &str) -> Result<f32, CommandError>{
+    match arg{
+        "dawn" => Ok(0.25),
+        "noon" | "day" => Ok(0.5),
+        "dusk" => Ok(0.75),
+        "midnight" | "night" => Ok(0.0),
+        _ => arg.parse().map_err(|_| CommandError::InvalidArgument(arg.to_string())),
+    }
+}
+
+/// Advances WorldTime every tick, copies the time of day into the renderer's TimeOfDay and runs the time command:
+/// time, time set <fraction|dawn|noon|dusk|midnight>, time freeze, time unfreeze and time daylength <seconds>
+#[derive(Default)]
+pub struct WorldTimeSystem{}
+
+impl WorldTimeSystem{
+    fn run_command(command: &Command, time: &mut WorldTime) -> Result<String, CommandError>{
+        match command.arg(0){
+            None => {},
+            Some("set") => time.set_time_of_day(parse_time_of_day(command.arg(1).ok_or(CommandError::MissingArgument(1))?)?),
+            Some("freeze") => time.set_frozen(true),
+            Some("unfreeze") => time.set_frozen(false),
+            Some("daylength") => time.set_day_length(command.parse_arg(1)?),
+            Some(arg) => return Err(CommandError::InvalidArgument(arg.to_string())),
+        }
+        Ok(format!("day {} at {:.3}{}", time.day(), time.time_of_day().fraction(), if time.frozen() {", frozen"} else {""}))
+    }
+}
+
+impl<'a> System<'a> for WorldTimeSystem{
+    type SystemData = (
+        Write<'a, Commands>,
+        Write<'a, WorldTime>,
+        Write<'a, TimeOfDay>,
+        Read<'a, Time>
+    );
+
+    fn run(&mut self, (mut commands, mut world_time, mut time_of_day, time): Self::SystemData){
+        for command in commands.take(WORLD_TIME_COMMANDS).iter(){
+            match Self::run_command(command, &mut world_time){
+                Ok(message) => println!("{}: {}", command.name, message),
+                Err(error) => println!("{}: {}", command.name, error),
+            }
+        }
+        world_time.advance(time.fixed_delta());
+        *time_of_day = world_time.time_of_day();
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn time_advances_unless_frozen(){
+        let mut time = WorldTime::new(100.0);
+        time.set_time_of_day(0.5);
+        time.advance(25.0);
+        assert!((time.time_of_day().fraction() - 0.75).abs() < 0.0001);
+        time.advance(50.0);
+        assert_eq!(time.day(), 1);
+
+        time.set_frozen(true);
+        let before = time.clone();
+        time.advance(30.0);
+        assert_eq!(time, before);
+
+        //Setting an earlier time moves on to the next day
+        time.set_time_of_day(0.1);
+        assert_eq!(time.day(), 2);
+    }
+
+    #[test]
+    fn sky_light_follows_the_sun(){
+        let mut time = WorldTime::default();
+        time.set_time_of_day(0.5);
+        assert_eq!(time.sky_light(), 1.0);
+        assert!(time.sun_direction()[1] > 0.0);
+        time.set_time_of_day(0.0);
+        assert_eq!(time.sky_light(), 0.0);
+        assert!(time.sun_direction()[1] < 0.0);
+        assert_eq!(time.sky(), TimeOfDay::new(0.0).lighting());
+    }
+
+    #[test]
+    fn time_commands(){
+        let mut time = WorldTime::default();
+        let run = |time: &mut WorldTime, line: &str| WorldTimeSystem::run_command(&Command::parse(line).unwrap(), time);
+        assert!(run(&mut time, "time set noon").is_ok());
+        assert_eq!(time.time_of_day().fraction(), 0.5);
+        assert!(run(&mut time, "time set 0.8").is_ok());
+        assert!((time.time_of_day().fraction() - 0.8).abs() < 0.0001);
+        assert!(run(&mut time, "time freeze").is_ok());
+        assert!(time.frozen());
+        assert!(run(&mut time, "time daylength 60").is_ok());
+        assert_eq!(time.day_length(), 60.0);
+        assert_eq!(run(&mut time, "time set later"), Err(CommandError::InvalidArgument("later".to_string())));
+        assert_eq!(run(&mut time, "time set"), Err(CommandError::MissingArgument(1)));
+        assert_eq!(run(&mut time, "time rewind"), Err(CommandError::InvalidArgument("rewind".to_string())));
+    }
+}
@@ -0,0 +1,172 @@
+use specs::prelude::*;
+use std::sync::{Arc, Mutex};
+use std::io::BufRead;
+use std::thread;
+use std::str::FromStr;
+use std::fmt;
+
+/// A single command line split on whitespace, the leading slash is optional
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Command{
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+impl Command{
+    pub fn parse(line: &str) -> Option<Self>{
+        let mut parts = line.trim().trim_start_matches('/').split_whitespace();
+        let name = parts.next()?.to_lowercase();
+        Some(Command{
+            name,
+            args: parts.map(String::from).collect(),
+        })
+    }
+
+    pub fn arg(&self, index: usize) -> Option<&str>{
+        self.args.get(index).map(|x| x.as_str())
+    }
+
+    /// Parse an argument into any type that can be read from a string
+    pub fn parse_arg<T: FromStr>(&self, index: usize) -> Result<T, CommandError>{
+        let arg = self.arg(index).ok_or(CommandError::MissingArgument(index))?;
+        arg.parse().map_err(|_| CommandError::InvalidArgument(arg.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError{
+    MissingArgument(usize),
+    InvalidArgument(String),
+    Failed(String),
+}
+
+impl fmt::Display for CommandError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            CommandError::MissingArgument(index) => write!(f, "missing argument {}", index + 1),
+            CommandError::InvalidArgument(arg) => write!(f, "invalid argument '{}'", arg),
+            CommandError::Failed(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// Commands waiting to be handled this frame.
+/// Systems take the commands they understand, anything left over is reported as unknown.
+#[derive(Default)]
+pub struct Commands{
+    pending: Vec<Command>,
+}
+
+impl Commands{
+    pub fn push(&mut self, line: &str){
+        if let Some(command) = Command::parse(line){
+            self.pending.push(command);
+        }
+    }
+
+    /// Remove and return every pending command whose name is in names
+    pub fn take(&mut self, names: &[&str]) -> Vec<Command>{
+        let (taken, left) = self.pending.drain(..).partition(|c| names.contains(&c.name.as_str()));
+        self.pending = left;
+        taken
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.pending.is_empty()
+    }
+}
+
+/// Command lines waiting to be picked up, filled from another thread
+pub type ConsoleLines = Arc<Mutex<Vec<String>>>;
+
+/// Start a thread reading lines typed into the terminal
+pub fn read_console() -> ConsoleLines{
+    let lines = ConsoleLines::default();
+    let reader_lines = lines.clone();
+    thread::spawn(move ||{
+        let stdin = std::io::stdin();
+        for line in stdin.lock().lines(){
+            match line{
+                Ok(line) => reader_lines.lock().unwrap().push(line),
+                Err(_) => return,
+            }
+        }
+    });
+    lines
+}
+
+/// Queues command lines up in Commands, by default the ones typed into the terminal
+pub struct ConsoleSystem{
+    lines: ConsoleLines,
+}
+
+impl ConsoleSystem{
+    pub fn new() -> Self{
+        Self::with_lines(read_console())
+    }
+
+    /// Read command lines from somewhere other than the terminal, like an input recording
+    pub fn with_lines(lines: ConsoleLines) -> Self{
+        ConsoleSystem{lines}
+    }
+}
+
+impl Default for ConsoleSystem{
+    fn default() -> Self{
+        Self::new()
+    }
+}
+
+impl<'a> System<'a> for ConsoleSystem{
+    type SystemData = Write<'a, Commands>;
+
+    fn run(&mut self, mut commands: Self::SystemData){
+        //Whatever survived a whole frame wasn't picked up by any system
+        for command in commands.pending.drain(..){
+            println!("Unknown command: {}", command.name);
+        }
+
+        for line in self.lines.lock().unwrap().drain(..){
+            commands.push(&line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn lines_are_split_into_commands(){
+        assert_eq!(Command::parse("/TP 1 2 3"), Some(Command{name: "tp".to_string(), args: vec!["1".to_string(), "2".to_string(), "3".to_string()]}));
+        assert_eq!(Command::parse("time set"), Command::parse("/time set"));
+        assert_eq!(Command::parse("  /Time \t set   noon  "), Some(Command{name: "time".to_string(), args: vec!["set".to_string(), "noon".to_string()]}));
+        assert_eq!(Command::parse("   "), None);
+        assert_eq!(Command::parse("/"), None);
+    }
+
+    #[test]
+    fn arguments_are_parsed_by_type(){
+        let command = Command::parse("tp 1.5 up").unwrap();
+        assert_eq!(command.parse_arg::<f32>(0), Ok(1.5));
+        assert_eq!(command.parse_arg::<f32>(1), Err(CommandError::InvalidArgument("up".to_string())));
+        assert_eq!(command.parse_arg::<f32>(2), Err(CommandError::MissingArgument(2)));
+        assert_eq!(CommandError::MissingArgument(2).to_string(), "missing argument 3");
+    }
+
+    #[test]
+    fn take_leaves_other_commands_pending(){
+        let mut commands = Commands::default();
+        commands.push("/time set 0");
+        commands.push("");
+        commands.push("tp 0 0 0");
+        commands.push("TIME speed 2");
+
+        let taken = commands.take(&["time"]);
+        assert_eq!(taken.iter().map(|command| command.arg(0).unwrap()).collect::<Vec<_>>(), vec!["set", "speed"]);
+        assert!(!commands.is_empty());
+        assert_eq!(commands.take(&["time"]), Vec::new());
+        assert_eq!(commands.take(&["tp", "time"]).len(), 1);
+        assert!(commands.is_empty());
+    }
+}
@@ -0,0 +1,292 @@
+use crate::*;
+use std::path::Path;
+use std::fmt;
+
+/// Anything an action or axis can be bound to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding{
+    /// A key by its position, so bindings stay put on other keyboard layouts
+    Key(KeyCode),
+    /// A key by what the keyboard layout says it is
+    LogicalKey(KeyCode),
+    Button(ButtonCode),
+    WheelUp,
+    WheelDown,
+    GamepadButton(GamepadButton),
+    /// Counts as down when pushed past halfway either way, as an axis it keeps its sign
+    GamepadAxis(GamepadAxis),
+}
+
+impl Binding{
+    /// Bindings are written as key:W, logical:W, mouse:MB0, wheel:up, wheel:down, pad:South or padaxis:LeftStickX
+    pub fn parse(text: &str) -> Option<Self>{
+        let (kind, name) = text.split_once(':')?;
+        match kind{
+            "key" => KeyCode::from_name(name).map(Binding::Key),
+            "logical" => KeyCode::from_name(name).map(Binding::LogicalKey),
+            "mouse" => ButtonCode::from_name(name).map(Binding::Button),
+            "wheel" => match name{
+                "up" => Some(Binding::WheelUp),
+                "down" => Some(Binding::WheelDown),
+                _ => None,
+            },
+            "pad" => GamepadButton::from_name(name).map(Binding::GamepadButton),
+            "padaxis" => GamepadAxis::from_name(name).map(Binding::GamepadAxis),
+            _ => None,
+        }
+    }
+
+    pub fn state(&self, inputs: &Inputs) -> KeyState{
+        match self{
+            Binding::Key(key) => *inputs.get_key(key),
+            Binding::LogicalKey(key) => *inputs.get_logical_key(key),
+            Binding::Button(button) => *inputs.get_button(button),
+            //The wheel has no held state, a scroll is a press that lasts one tick
+            Binding::WheelUp => if inputs.mouse_wheel[1] > 0.0 {KeyState::Pressed} else {KeyState::Up},
+            Binding::WheelDown => if inputs.mouse_wheel[1] < 0.0 {KeyState::Pressed} else {KeyState::Up},
+            Binding::GamepadButton(button) => inputs.get_gamepad_button(*button),
+            Binding::GamepadAxis(axis) => inputs.get_gamepad_axis_state(*axis),
+        }
+    }
+
+    /// How far the binding is pushed, digital bindings are either 0 or 1
+    pub fn value(&self, inputs: &Inputs) -> f32{
+        if let Binding::GamepadAxis(axis) = self{
+            return inputs.get_gamepad_axis(*axis);
+        }
+        match self.state(inputs){
+            KeyState::Pressed | KeyState::Down => 1.0,
+            _ => 0.0,
+        }
+    }
+}
+
+impl fmt::Display for Binding{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            Binding::Key(key) => write!(f, "key:{}", key),
+            Binding::LogicalKey(key) => write!(f, "logical:{}", key),
+            Binding::Button(button) => write!(f, "mouse:{}", button),
+            Binding::WheelUp => write!(f, "wheel:up"),
+            Binding::WheelDown => write!(f, "wheel:down"),
+            Binding::GamepadButton(button) => write!(f, "pad:{}", button),
+            Binding::GamepadAxis(axis) => write!(f, "padaxis:{}", axis),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActionMapError{
+    Io(std::io::ErrorKind),
+    InvalidLine(usize),
+}
+
+impl From<std::io::Error> for ActionMapError{
+    fn from(error: std::io::Error) -> Self{
+        ActionMapError::Io(error.kind())
+    }
+}
+
+/// Named actions and axes and the inputs bound to them.
+/// An action is down when any of its bindings are, an axis is the sum of its bindings times their scale.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActionMap{
+    actions: Vec<(String, Vec<Binding>)>,
+    axes: Vec<(String, Vec<(Binding, f32)>)>,
+}
+
+impl ActionMap{
+    /// A map with nothing bound
+    pub fn empty() -> Self{
+        ActionMap{
+            actions: Vec::new(),
+            axes: Vec::new(),
+        }
+    }
+
+    pub fn bind_action(&mut self, name: &str, binding: Binding){
+        match self.actions.iter_mut().find(|(action, _)| action == name){
+            Some((_, bindings)) => bindings.push(binding),
+            None => self.actions.push((name.to_string(), vec![binding])),
+        }
+    }
+
+    pub fn bind_axis(&mut self, name: &str, binding: Binding, scale: f32){
+        match self.axes.iter_mut().find(|(axis, _)| axis == name){
+            Some((_, bindings)) => bindings.push((binding, scale)),
+            None => self.axes.push((name.to_string(), vec![(binding, scale)])),
+        }
+    }
+
+    pub fn with_action(mut self, name: &str, binding: Binding) -> Self{
+        self.bind_action(name, binding);
+        self
+    }
+
+    pub fn with_axis(mut self, name: &str, binding: Binding, scale: f32) -> Self{
+        self.bind_axis(name, binding, scale);
+        self
+    }
+
+    /// Remove every binding from an action or axis
+    pub fn unbind(&mut self, name: &str){
+        self.actions.retain(|(action, _)| action != name);
+        self.axes.retain(|(axis, _)| axis != name);
+    }
+
+    pub fn action_bindings(&self, name: &str) -> &[Binding]{
+        match self.actions.iter().find(|(action, _)| action == name){
+            Some((_, bindings)) => bindings,
+            None => &[],
+        }
+    }
+
+    pub fn axis_bindings(&self, name: &str) -> &[(Binding, f32)]{
+        match self.axes.iter().find(|(axis, _)| axis == name){
+            Some((_, bindings)) => bindings,
+            None => &[],
+        }
+    }
+
+    /// Read a map from lines of "action <name> <binding>" or "axis <name> <binding> <scale>",
+    /// a name can be given on more than one line to bind it more than once. Lines starting with # are ignored.
+    pub fn parse(text: &str) -> Result<Self, ActionMapError>{
+        let mut map = Self::empty();
+        for (number, line) in text.lines().enumerate(){
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#'){
+                continue;
+            }
+            let invalid = ActionMapError::InvalidLine(number + 1);
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match parts.as_slice(){
+                ["action", name, binding] => {
+                    map.bind_action(name, Binding::parse(binding).ok_or(invalid)?);
+                },
+                ["axis", name, binding, scale] => {
+                    let scale = scale.parse().map_err(|_| invalid.clone())?;
+                    map.bind_axis(name, Binding::parse(binding).ok_or(invalid)?, scale);
+                },
+                _ => return Err(invalid),
+            }
+        }
+        Ok(map)
+    }
+
+    pub fn to_text(&self) -> String{
+        let mut text = String::new();
+        for (name, bindings) in self.actions.iter(){
+            for binding in bindings.iter(){
+                text.push_str(&format!("action {} {}\n", name, binding));
+            }
+        }
+        for (name, bindings) in self.axes.iter(){
+            for (binding, scale) in bindings.iter(){
+                text.push_str(&format!("axis {} {} {}\n", name, binding, scale));
+            }
+        }
+        text
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ActionMapError>{
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ActionMapError>{
+        std::fs::write(path, self.to_text())?;
+        Ok(())
+    }
+}
+
+/// The controls the game ships with
+impl Default for ActionMap{
+    fn default() -> Self{
+        Self::empty()
+            .with_action("place_grass", Binding::Button(ButtonCode::MB0))
+            .with_action("place_rock", Binding::Button(ButtonCode::MB1))
+            .with_action("break_block", Binding::Button(ButtonCode::MB2))
+            .with_action("place_grass", Binding::GamepadButton(GamepadButton::RightTrigger))
+            .with_action("place_rock", Binding::GamepadButton(GamepadButton::RightBumper))
+            .with_action("break_block", Binding::GamepadButton(GamepadButton::LeftTrigger))
+            .with_action(RELEASE_CURSOR_ACTION, Binding::Key(KeyCode::ESC))
+            .with_action(TOGGLE_FULLSCREEN_ACTION, Binding::Key(KeyCode::F11))
+            .with_action(TOGGLE_VSYNC_ACTION, Binding::Key(KeyCode::F10))
+            .with_axis("move_x", Binding::Key(KeyCode::D), 1.0)
+            .with_axis("move_x", Binding::Key(KeyCode::A), -1.0)
+            .with_axis("move_x", Binding::GamepadAxis(GamepadAxis::LeftStickX), 1.0)
+            .with_axis("move_forward", Binding::Key(KeyCode::W), 1.0)
+            .with_axis("move_forward", Binding::Key(KeyCode::S), -1.0)
+            .with_axis("move_forward", Binding::GamepadAxis(GamepadAxis::LeftStickY), 1.0)
+    }
+}
+
+impl Inputs{
+    pub fn action_map(&self) -> &ActionMap{
+        &self.actions
+    }
+
+    pub fn action_map_mut(&mut self) -> &mut ActionMap{
+        &mut self.actions
+    }
+
+    pub fn set_action_map(&mut self, actions: ActionMap){
+        self.actions = actions;
+    }
+
+    /// True on the tick any binding of the action went down
+    pub fn action_pressed(&self, name: &str) -> bool{
+        self.actions.action_bindings(name).iter().any(|binding| binding.state(self) == KeyState::Pressed)
+    }
+
+    /// True while any binding of the action is held
+    pub fn action_down(&self, name: &str) -> bool{
+        self.actions.action_bindings(name).iter().any(|binding|{
+            let state = binding.state(self);
+            state == KeyState::Pressed || state == KeyState::Down
+        })
+    }
+
+    /// True on the tick a binding of the action was let go
+    pub fn action_released(&self, name: &str) -> bool{
+        self.actions.action_bindings(name).iter().any(|binding| binding.state(self) == KeyState::Unpressed)
+    }
+
+    /// Value of an axis, clamped between -1 and 1
+    pub fn axis(&self, name: &str) -> f32{
+        self.actions.axis_bindings(name).iter()
+            .map(|(binding, scale)| binding.value(self) * scale)
+            .sum::<f32>()
+            .clamp(-1.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn parse_and_save(){
+        let map = ActionMap::parse("# controls\naction jump key:Space\naction jump logical:Z\naction jump wheel:up\naxis move_x key:D 1\naxis move_x key:A -1\naxis move_x padaxis:LeftStickX 0.5\n").unwrap();
+        assert_eq!(map.action_bindings("jump"), &[Binding::Key(KeyCode::Space), Binding::LogicalKey(KeyCode::Z), Binding::WheelUp]);
+        assert_eq!(map.axis_bindings("move_x").len(), 3);
+        assert_eq!(ActionMap::parse(&map.to_text()).unwrap(), map);
+        assert_eq!(ActionMap::parse("action jump key:Nope").unwrap_err(), ActionMapError::InvalidLine(1));
+        assert_eq!(ActionMap::parse("\naxis move_x key:D").unwrap_err(), ActionMapError::InvalidLine(2));
+    }
+
+    #[test]
+    fn actions_follow_bindings(){
+        let mut inputs = Inputs::default();
+        inputs.keys.insert(KeyCode::A, KeyState::Down);
+        inputs.mouse_buttons.insert(ButtonCode::MB2, KeyState::Pressed);
+        assert_eq!(inputs.axis("move_x"), -1.0);
+        assert_eq!(inputs.axis("move_forward"), 0.0);
+        assert!(inputs.action_pressed("break_block"));
+        assert!(inputs.action_down("break_block"));
+        assert!(!inputs.action_down("place_grass"));
+        assert!(!inputs.action_pressed("missing"));
+
+        inputs.keys.insert(KeyCode::D, KeyState::Pressed);
+        assert_eq!(inputs.axis("move_x"), 0.0);
+    }
+}
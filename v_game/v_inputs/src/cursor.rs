@@ -0,0 +1,113 @@
+use crate::*;
+
+/// The action that frees the cursor, bound to Escape by default
+pub const RELEASE_CURSOR_ACTION: &str = "release_cursor";
+
+/// What the window does with the mouse cursor
+#[derive(Display, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorMode{
+    /// Hidden and held in the window, mouse movement turns the camera
+    Captured,
+    /// Visible but can't leave the window
+    Confined,
+    /// Visible and free to leave, mouse movement and clicks go to whatever is under the cursor
+    Free,
+}
+
+/// Cursor mode along with the window focus it depends on.
+/// Losing focus or releasing the cursor frees it, clicking back in the window returns it to the mode it was in.
+pub struct CursorState{
+    mode: CursorMode,
+    resume_mode: CursorMode,
+    focused: bool,
+    pub pause_on_focus_loss: bool,
+}
+
+impl Default for CursorState{
+    fn default() -> Self{
+        CursorState{
+            mode: CursorMode::Captured,
+            resume_mode: CursorMode::Captured,
+            focused: true,
+            pause_on_focus_loss: true,
+        }
+    }
+}
+
+impl CursorState{
+    pub fn mode(&self) -> CursorMode{
+        self.mode
+    }
+
+    /// Switch modes, a mode other than Free is also the one clicking back in the window returns to
+    pub fn set_mode(&mut self, mode: CursorMode){
+        if mode != CursorMode::Free{
+            self.resume_mode = mode;
+        }
+        self.mode = mode;
+    }
+
+    /// Free the cursor until the window is clicked again
+    pub fn release(&mut self){
+        self.mode = CursorMode::Free;
+    }
+
+    /// Go back to the mode the cursor was in before it was released
+    pub fn resume(&mut self){
+        self.mode = self.resume_mode;
+    }
+
+    pub fn focused(&self) -> bool{
+        self.focused
+    }
+
+    pub(crate) fn set_focused(&mut self, focused: bool){
+        self.focused = focused;
+        if !focused{
+            self.release();
+        }
+    }
+
+    /// Whether mouse movement should turn the camera
+    pub fn captured(&self) -> bool{
+        self.focused && self.mode != CursorMode::Free
+    }
+
+    /// True while the window is unfocused and the game should stop ticking
+    pub fn paused(&self) -> bool{
+        self.pause_on_focus_loss && !self.focused
+    }
+}
+
+/// Grabs and hides the cursor to match CursorState, the window is only touched when the mode changes
+/// or the window was rebuilt
+#[derive(Default)]
+pub struct CursorLockSystem{
+    /// The mode last set and WindowDisplay::rebuilds at the time
+    applied: Option<(CursorMode, usize)>,
+}
+
+impl<'a> System<'a> for CursorLockSystem{
+    type SystemData = (
+        Read<'a, CursorState>,
+        Write<'a, WindowDisplay>);
+
+    fn run(&mut self, (cursor_state, window_display): Self::SystemData){
+        let applied = (cursor_state.mode(), window_display.rebuilds());
+        let window_display = match window_display.as_ref(){
+            Some(display) => display.lock().unwrap(),
+            None => return,
+        };
+        let mode = cursor_state.mode();
+        if self.applied == Some(applied){
+            return;
+        }
+        let gl_window = window_display.gl_window();
+        let window = gl_window.window();
+        if let Err(error) = window.set_cursor_grab(mode != CursorMode::Free){
+            println!("Couldn't change cursor grab: {:?}", error);
+        }
+        window.set_cursor_visible(mode != CursorMode::Captured);
+        self.applied = Some(applied);
+    }
+}
@@ -0,0 +1,448 @@
+use crate::*;
+
+pub type GamepadId = usize;
+
+#[derive(Display, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadButton{
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    Mode,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+impl GamepadButton{
+    pub const ALL: &'static [GamepadButton] = &[
+        GamepadButton::South, GamepadButton::East, GamepadButton::North, GamepadButton::West,
+        GamepadButton::LeftBumper, GamepadButton::RightBumper, GamepadButton::LeftTrigger, GamepadButton::RightTrigger,
+        GamepadButton::Select, GamepadButton::Start, GamepadButton::Mode, GamepadButton::LeftStick, GamepadButton::RightStick,
+        GamepadButton::DPadUp, GamepadButton::DPadDown, GamepadButton::DPadLeft, GamepadButton::DPadRight,
+    ];
+
+    pub fn from_name(name: &str) -> Option<GamepadButton>{
+        GamepadButton::ALL.iter().copied().find(|button| button.to_string() == name)
+    }
+}
+
+/// Sticks go from -1 to 1 with up and right positive, triggers go from 0 to 1
+#[derive(Display, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadAxis{
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+impl GamepadAxis{
+    pub const ALL: &'static [GamepadAxis] = &[
+        GamepadAxis::LeftStickX, GamepadAxis::LeftStickY, GamepadAxis::RightStickX, GamepadAxis::RightStickY,
+        GamepadAxis::LeftTrigger, GamepadAxis::RightTrigger,
+    ];
+
+    pub fn from_name(name: &str) -> Option<GamepadAxis>{
+        GamepadAxis::ALL.iter().copied().find(|axis| axis.to_string() == name)
+    }
+
+    /// The other axis of the same stick, triggers have none
+    fn partner(self) -> Option<GamepadAxis>{
+        match self{
+            GamepadAxis::LeftStickX => Some(GamepadAxis::LeftStickY),
+            GamepadAxis::LeftStickY => Some(GamepadAxis::LeftStickX),
+            GamepadAxis::RightStickX => Some(GamepadAxis::RightStickY),
+            GamepadAxis::RightStickY => Some(GamepadAxis::RightStickX),
+            _ => None,
+        }
+    }
+}
+
+/// Raw values below these are treated as zero, values above are rescaled to still reach 1.
+/// Sticks use the length of both axes together so diagonals aren't cut off.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Deadzones{
+    pub stick: f32,
+    pub trigger: f32,
+}
+
+impl Default for Deadzones{
+    fn default() -> Self{
+        Deadzones{
+            stick: 0.15,
+            trigger: 0.05,
+        }
+    }
+}
+
+fn rescale(magnitude: f32, deadzone: f32) -> f32{
+    if magnitude <= deadzone{
+        0.0
+    } else {
+        ((magnitude - deadzone) / (1.0 - deadzone)).min(1.0)
+    }
+}
+
+/// How far past its deadzone an axis has to go before it counts as a press
+const AXIS_PRESS_THRESHOLD: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GamepadEvent{
+    Connected(GamepadId),
+    Disconnected(GamepadId),
+    Button(GamepadId, GamepadButton, bool),
+    Axis(GamepadId, GamepadAxis, f32),
+}
+
+/// Somewhere gamepad events come from, polled once per tick
+pub trait GamepadBackend{
+    fn poll(&mut self) -> Vec<GamepadEvent>;
+}
+
+/// The buttons and axes of one connected gamepad
+#[derive(Debug, Clone, Default)]
+pub struct GamepadState{
+    buttons: ButtonStates<GamepadButton>,
+    axes: HashMap<GamepadAxis, f32>,
+    previous_axes: HashMap<GamepadAxis, f32>,
+}
+
+impl GamepadState{
+    pub fn button(&self, button: GamepadButton) -> KeyState{
+        *self.buttons.get(&button)
+    }
+
+    /// Axis value with deadzones applied
+    pub fn axis(&self, axis: GamepadAxis, deadzones: &Deadzones) -> f32{
+        Self::deadzoned(&self.axes, axis, deadzones)
+    }
+
+    pub fn raw_axis(&self, axis: GamepadAxis) -> f32{
+        self.axes.get(&axis).copied().unwrap_or(0.0)
+    }
+
+    /// An axis pushed past halfway acts like a button, in either direction
+    pub fn axis_state(&self, axis: GamepadAxis, deadzones: &Deadzones) -> KeyState{
+        let now = Self::deadzoned(&self.axes, axis, deadzones).abs() > AXIS_PRESS_THRESHOLD;
+        let before = Self::deadzoned(&self.previous_axes, axis, deadzones).abs() > AXIS_PRESS_THRESHOLD;
+        match (before, now){
+            (false, true) => KeyState::Pressed,
+            (true, true) => KeyState::Down,
+            (true, false) => KeyState::Unpressed,
+            (false, false) => KeyState::Up,
+        }
+    }
+
+    fn deadzoned(axes: &HashMap<GamepadAxis, f32>, axis: GamepadAxis, deadzones: &Deadzones) -> f32{
+        let value = axes.get(&axis).copied().unwrap_or(0.0);
+        match axis.partner(){
+            Some(partner) => {
+                let other = axes.get(&partner).copied().unwrap_or(0.0);
+                let magnitude = (value * value + other * other).sqrt();
+                if magnitude == 0.0{
+                    0.0
+                } else {
+                    value / magnitude * rescale(magnitude, deadzones.stick)
+                }
+            },
+            None => rescale(value.abs(), deadzones.trigger) * value.signum(),
+        }
+    }
+
+    fn advance(&mut self){
+        self.buttons.advance();
+        self.previous_axes = self.axes.clone();
+    }
+}
+
+/// Combine the states of a button on several gamepads, a fresh press counts over one that is held
+fn strongest(states: impl Iterator<Item=KeyState>) -> KeyState{
+    let rank = |state: &KeyState| match state{
+        KeyState::Pressed => 3,
+        KeyState::Down => 2,
+        KeyState::Unpressed => 1,
+        KeyState::Up => 0,
+    };
+    states.max_by_key(rank).unwrap_or(KeyState::Up)
+}
+
+impl Inputs{
+    pub fn gamepads(&self) -> impl Iterator<Item=(&GamepadId, &GamepadState)>{
+        self.gamepads.iter()
+    }
+
+    pub fn gamepad(&self, id: GamepadId) -> Option<&GamepadState>{
+        self.gamepads.get(&id)
+    }
+
+    pub fn deadzones(&self) -> &Deadzones{
+        &self.deadzones
+    }
+
+    pub fn set_deadzones(&mut self, deadzones: Deadzones){
+        self.deadzones = deadzones;
+    }
+
+    /// Button state across every connected gamepad
+    pub fn get_gamepad_button(&self, button: GamepadButton) -> KeyState{
+        strongest(self.gamepads.values().map(|pad| pad.button(button)))
+    }
+
+    /// Axis value from whichever connected gamepad is pushing it furthest
+    pub fn get_gamepad_axis(&self, axis: GamepadAxis) -> f32{
+        self.gamepads.values()
+            .map(|pad| pad.axis(axis, &self.deadzones))
+            .fold(0.0, |a: f32, b: f32| if b.abs() > a.abs() {b} else {a})
+    }
+
+    pub fn get_gamepad_axis_state(&self, axis: GamepadAxis) -> KeyState{
+        strongest(self.gamepads.values().map(|pad| pad.axis_state(axis, &self.deadzones)))
+    }
+}
+
+/// Moves gamepad events from a backend into Inputs
+pub struct GamepadSystem{
+    backend: Box<dyn GamepadBackend>,
+}
+
+impl GamepadSystem{
+    pub fn new(backend: Box<dyn GamepadBackend>) -> Self{
+        GamepadSystem{backend}
+    }
+}
+
+impl<'a> System<'a> for GamepadSystem{
+    type SystemData = Write<'a, Inputs>;
+
+    fn run(&mut self, mut inputs: Self::SystemData){
+        for pad in inputs.gamepads.values_mut(){
+            pad.advance();
+        }
+
+        for event in self.backend.poll(){
+            match event{
+                GamepadEvent::Connected(id) => {
+                    inputs.gamepads.entry(id).or_default();
+                },
+                //Dropping the state means nothing stays held down after a pad is unplugged
+                GamepadEvent::Disconnected(id) => {
+                    inputs.gamepads.remove(&id);
+                },
+                GamepadEvent::Button(id, button, pressed) => {
+                    let pad = inputs.gamepads.entry(id).or_default();
+                    pad.buttons.set(button, pressed);
+                },
+                GamepadEvent::Axis(id, axis, value) => {
+                    let pad = inputs.gamepads.entry(id).or_default();
+                    pad.axes.insert(axis, value.clamp(-1.0, 1.0));
+                },
+            }
+        }
+    }
+}
+
+/// Gamepads driven by hand, for tests and tools.
+/// Clones share the same queue so one can be kept after handing another to GamepadSystem.
+#[derive(Clone, Default)]
+pub struct MockGamepads{
+    events: Arc<Mutex<Vec<GamepadEvent>>>,
+}
+
+impl MockGamepads{
+    pub fn new() -> Self{
+        Self::default()
+    }
+
+    pub fn push(&self, event: GamepadEvent){
+        self.events.lock().unwrap().push(event);
+    }
+
+    pub fn connect(&self, id: GamepadId){
+        self.push(GamepadEvent::Connected(id));
+    }
+
+    pub fn disconnect(&self, id: GamepadId){
+        self.push(GamepadEvent::Disconnected(id));
+    }
+
+    pub fn press(&self, id: GamepadId, button: GamepadButton){
+        self.push(GamepadEvent::Button(id, button, true));
+    }
+
+    pub fn release(&self, id: GamepadId, button: GamepadButton){
+        self.push(GamepadEvent::Button(id, button, false));
+    }
+
+    pub fn set_axis(&self, id: GamepadId, axis: GamepadAxis, value: f32){
+        self.push(GamepadEvent::Axis(id, axis, value));
+    }
+}
+
+impl GamepadBackend for MockGamepads{
+    fn poll(&mut self) -> Vec<GamepadEvent>{
+        self.events.lock().unwrap().drain(..).collect()
+    }
+}
+
+#[cfg(feature = "gilrs")]
+pub struct GilrsBackend{
+    gilrs: gilrs::Gilrs,
+    connected: Vec<GamepadId>,
+}
+
+#[cfg(feature = "gilrs")]
+impl GilrsBackend{
+    pub fn new() -> Result<Self, gilrs::Error>{
+        let gilrs = gilrs::Gilrs::new()?;
+        //Pads plugged in before startup don't get a connected event
+        let connected = gilrs.gamepads().map(|(id, _)| id.into()).collect();
+        Ok(GilrsBackend{gilrs, connected})
+    }
+
+    fn button(button: gilrs::Button) -> Option<GamepadButton>{
+        use gilrs::Button;
+        Some(match button{
+            Button::South => GamepadButton::South,
+            Button::East => GamepadButton::East,
+            Button::North => GamepadButton::North,
+            Button::West => GamepadButton::West,
+            Button::LeftTrigger => GamepadButton::LeftBumper,
+            Button::RightTrigger => GamepadButton::RightBumper,
+            Button::LeftTrigger2 => GamepadButton::LeftTrigger,
+            Button::RightTrigger2 => GamepadButton::RightTrigger,
+            Button::Select => GamepadButton::Select,
+            Button::Start => GamepadButton::Start,
+            Button::Mode => GamepadButton::Mode,
+            Button::LeftThumb => GamepadButton::LeftStick,
+            Button::RightThumb => GamepadButton::RightStick,
+            Button::DPadUp => GamepadButton::DPadUp,
+            Button::DPadDown => GamepadButton::DPadDown,
+            Button::DPadLeft => GamepadButton::DPadLeft,
+            Button::DPadRight => GamepadButton::DPadRight,
+            _ => return None,
+        })
+    }
+
+    fn axis(axis: gilrs::Axis) -> Option<GamepadAxis>{
+        use gilrs::Axis;
+        Some(match axis{
+            Axis::LeftStickX => GamepadAxis::LeftStickX,
+            Axis::LeftStickY => GamepadAxis::LeftStickY,
+            Axis::RightStickX => GamepadAxis::RightStickX,
+            Axis::RightStickY => GamepadAxis::RightStickY,
+            Axis::LeftZ => GamepadAxis::LeftTrigger,
+            Axis::RightZ => GamepadAxis::RightTrigger,
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(feature = "gilrs")]
+impl GamepadBackend for GilrsBackend{
+    fn poll(&mut self) -> Vec<GamepadEvent>{
+        use gilrs::EventType;
+        let mut events: Vec<GamepadEvent> = self.connected.drain(..).map(GamepadEvent::Connected).collect();
+        while let Some(gilrs::Event{id, event, ..}) = self.gilrs.next_event(){
+            let id: GamepadId = id.into();
+            match event{
+                EventType::Connected => events.push(GamepadEvent::Connected(id)),
+                EventType::Disconnected => events.push(GamepadEvent::Disconnected(id)),
+                EventType::ButtonPressed(button, _) => if let Some(button) = Self::button(button){
+                    events.push(GamepadEvent::Button(id, button, true));
+                },
+                EventType::ButtonReleased(button, _) => if let Some(button) = Self::button(button){
+                    events.push(GamepadEvent::Button(id, button, false));
+                },
+                //Most pads report analog triggers as buttons with a value
+                EventType::ButtonChanged(gilrs::Button::LeftTrigger2, value, _) => events.push(GamepadEvent::Axis(id, GamepadAxis::LeftTrigger, value)),
+                EventType::ButtonChanged(gilrs::Button::RightTrigger2, value, _) => events.push(GamepadEvent::Axis(id, GamepadAxis::RightTrigger, value)),
+                EventType::AxisChanged(axis, value, _) => if let Some(axis) = Self::axis(axis){
+                    events.push(GamepadEvent::Axis(id, axis, value));
+                },
+                _ => {},
+            }
+        }
+        events
+    }
+}
+
+/// The gamepad backend to use when running with a window, None when it can't start or is compiled out
+pub fn default_gamepad_backend() -> Option<Box<dyn GamepadBackend>>{
+    #[cfg(feature = "gilrs")]
+    {
+        match GilrsBackend::new(){
+            Ok(backend) => return Some(Box::new(backend)),
+            Err(error) => println!("Gamepads unavailable: {}", error),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn run(system: &mut GamepadSystem, world: &World){
+        system.run_now(world);
+    }
+
+    #[test]
+    fn mock_pad_drives_inputs(){
+        let mut world = World::new();
+        world.insert(Inputs::default());
+        let pads = MockGamepads::new();
+        let mut system = GamepadSystem::new(Box::new(pads.clone()));
+
+        pads.connect(0);
+        pads.press(0, GamepadButton::RightTrigger);
+        pads.set_axis(0, GamepadAxis::LeftStickX, 0.1);
+        pads.set_axis(0, GamepadAxis::LeftStickY, 1.0);
+        run(&mut system, &world);
+        {
+            let inputs = world.read_resource::<Inputs>();
+            assert_eq!(inputs.get_gamepad_button(GamepadButton::RightTrigger), KeyState::Pressed);
+            assert!(inputs.action_pressed("place_grass"));
+            assert!(inputs.axis("move_forward") > 0.99);
+            assert!(inputs.axis("move_x") > 0.0 && inputs.axis("move_x") < 0.1);
+            assert_eq!(inputs.get_gamepad_axis_state(GamepadAxis::LeftStickY), KeyState::Pressed);
+        }
+
+        pads.set_axis(0, GamepadAxis::LeftStickY, 0.1);
+        run(&mut system, &world);
+        {
+            let inputs = world.read_resource::<Inputs>();
+            assert_eq!(inputs.get_gamepad_button(GamepadButton::RightTrigger), KeyState::Down);
+            assert_eq!(inputs.axis("move_forward"), 0.0);
+            assert_eq!(inputs.axis("move_x"), 0.0);
+            assert_eq!(inputs.get_gamepad_axis_state(GamepadAxis::LeftStickY), KeyState::Unpressed);
+        }
+
+        pads.disconnect(0);
+        run(&mut system, &world);
+        let inputs = world.read_resource::<Inputs>();
+        assert_eq!(inputs.gamepads().count(), 0);
+        assert!(!inputs.action_down("place_grass"));
+    }
+
+    #[test]
+    fn trigger_deadzone(){
+        let mut pad = GamepadState::default();
+        let deadzones = Deadzones::default();
+        pad.axes.insert(GamepadAxis::RightTrigger, 0.04);
+        assert_eq!(pad.axis(GamepadAxis::RightTrigger, &deadzones), 0.0);
+        pad.axes.insert(GamepadAxis::RightTrigger, 1.0);
+        assert_eq!(pad.axis(GamepadAxis::RightTrigger, &deadzones), 1.0);
+        assert_eq!(pad.raw_axis(GamepadAxis::LeftTrigger), 0.0);
+    }
+}
@@ -0,0 +1,341 @@
+use crate::*;
+use glutin::event::VirtualKeyCode;
+
+/// Declares KeyCode along with its mapping to and from glutin's VirtualKeyCode
+macro_rules! key_codes{
+    ($($key: ident => $virtual: ident,)*) => {
+        #[derive(Display, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum KeyCode{
+            Undefined,
+            $($key,)*
+        }
+
+        impl KeyCode{
+            /// Every key except Undefined
+            pub const ALL: &'static [KeyCode] = &[$(KeyCode::$key,)*];
+
+            /// The key the keyboard layout says was pressed
+            pub fn from_virtual(key: VirtualKeyCode) -> KeyCode{
+                match key{
+                    $(VirtualKeyCode::$virtual => KeyCode::$key,)*
+                }
+            }
+
+            pub fn to_virtual(self) -> Option<VirtualKeyCode>{
+                match self{
+                    $(KeyCode::$key => Some(VirtualKeyCode::$virtual),)*
+                    KeyCode::Undefined => None,
+                }
+            }
+        }
+    }
+}
+
+key_codes!{
+    KB1 => Key1,
+    KB2 => Key2,
+    KB3 => Key3,
+    KB4 => Key4,
+    KB5 => Key5,
+    KB6 => Key6,
+    KB7 => Key7,
+    KB8 => Key8,
+    KB9 => Key9,
+    KB0 => Key0,
+    A => A,
+    B => B,
+    C => C,
+    D => D,
+    E => E,
+    F => F,
+    G => G,
+    H => H,
+    I => I,
+    J => J,
+    K => K,
+    L => L,
+    M => M,
+    N => N,
+    O => O,
+    P => P,
+    Q => Q,
+    R => R,
+    S => S,
+    T => T,
+    U => U,
+    V => V,
+    W => W,
+    X => X,
+    Y => Y,
+    Z => Z,
+    ESC => Escape,
+    F1 => F1,
+    F2 => F2,
+    F3 => F3,
+    F4 => F4,
+    F5 => F5,
+    F6 => F6,
+    F7 => F7,
+    F8 => F8,
+    F9 => F9,
+    F10 => F10,
+    F11 => F11,
+    F12 => F12,
+    F13 => F13,
+    F14 => F14,
+    F15 => F15,
+    F16 => F16,
+    F17 => F17,
+    F18 => F18,
+    F19 => F19,
+    F20 => F20,
+    F21 => F21,
+    F22 => F22,
+    F23 => F23,
+    F24 => F24,
+    PrintScreen => Snapshot,
+    ScrollLock => Scroll,
+    Pause => Pause,
+    Insert => Insert,
+    Home => Home,
+    Delete => Delete,
+    End => End,
+    PageDown => PageDown,
+    PageUp => PageUp,
+    Left => Left,
+    Up => Up,
+    Right => Right,
+    Down => Down,
+    Backspace => Back,
+    Enter => Return,
+    Space => Space,
+    Compose => Compose,
+    Caret => Caret,
+    NumLock => Numlock,
+    Numpad0 => Numpad0,
+    Numpad1 => Numpad1,
+    Numpad2 => Numpad2,
+    Numpad3 => Numpad3,
+    Numpad4 => Numpad4,
+    Numpad5 => Numpad5,
+    Numpad6 => Numpad6,
+    Numpad7 => Numpad7,
+    Numpad8 => Numpad8,
+    Numpad9 => Numpad9,
+    NumpadAdd => Add,
+    NumpadSubtract => Subtract,
+    NumpadMultiply => Multiply,
+    NumpadDivide => Divide,
+    NumpadDecimal => Decimal,
+    NumpadComma => NumpadComma,
+    NumpadEnter => NumpadEnter,
+    NumpadEquals => NumpadEquals,
+    AbntC1 => AbntC1,
+    AbntC2 => AbntC2,
+    Quote => Apostrophe,
+    Apps => Apps,
+    At => At,
+    Ax => Ax,
+    BackSlash => Backslash,
+    Calculator => Calculator,
+    CapsLock => Capital,
+    Colon => Colon,
+    Comma => Comma,
+    Convert => Convert,
+    EqualPlus => Equals,
+    Grave => Grave,
+    Kana => Kana,
+    Kanji => Kanji,
+    LeftAlt => LAlt,
+    OpenBracket => LBracket,
+    LeftControl => LControl,
+    LeftShift => LShift,
+    LeftWin => LWin,
+    Mail => Mail,
+    MediaSelect => MediaSelect,
+    MediaStop => MediaStop,
+    MinusUnderscore => Minus,
+    Mute => Mute,
+    MyComputer => MyComputer,
+    NavigateForward => NavigateForward,
+    NavigateBackward => NavigateBackward,
+    NextTrack => NextTrack,
+    NoConvert => NoConvert,
+    OEM102 => OEM102,
+    Period => Period,
+    PlayPause => PlayPause,
+    Power => Power,
+    PrevTrack => PrevTrack,
+    RightAlt => RAlt,
+    CloseBracket => RBracket,
+    RightControl => RControl,
+    RightShift => RShift,
+    RightWin => RWin,
+    Semicolon => Semicolon,
+    Slash => Slash,
+    Sleep => Sleep,
+    Stop => Stop,
+    SysRq => Sysrq,
+    Tab => Tab,
+    Underline => Underline,
+    Unlabeled => Unlabeled,
+    VolumeDown => VolumeDown,
+    VolumeUp => VolumeUp,
+    Wake => Wake,
+    WebBack => WebBack,
+    WebFavorites => WebFavorites,
+    WebForward => WebForward,
+    WebHome => WebHome,
+    WebRefresh => WebRefresh,
+    WebSearch => WebSearch,
+    WebStop => WebStop,
+    Yen => Yen,
+    Copy => Copy,
+    Paste => Paste,
+    Cut => Cut,
+}
+
+impl KeyCode{
+    /// The key at this position on a US QWERTY keyboard, whatever the layout is.
+    /// Scancodes are the PC set 1 codes Windows and Linux both report,
+    /// extended keys are given as Linux reports them and as Windows does with 0xE0 in the high byte.
+    pub fn from_scancode(scancode: u32) -> KeyCode{
+        use KeyCode::*;
+        match scancode{
+            1 => ESC,
+            2 => KB1,
+            3 => KB2,
+            4 => KB3,
+            5 => KB4,
+            6 => KB5,
+            7 => KB6,
+            8 => KB7,
+            9 => KB8,
+            10 => KB9,
+            11 => KB0,
+            12 => MinusUnderscore,
+            13 => EqualPlus,
+            14 => Backspace,
+            15 => Tab,
+            16 => Q,
+            17 => W,
+            18 => E,
+            19 => R,
+            20 => T,
+            21 => Y,
+            22 => U,
+            23 => I,
+            24 => O,
+            25 => P,
+            26 => OpenBracket,
+            27 => CloseBracket,
+            28 => Enter,
+            29 => LeftControl,
+            30 => A,
+            31 => S,
+            32 => D,
+            33 => F,
+            34 => G,
+            35 => H,
+            36 => J,
+            37 => K,
+            38 => L,
+            39 => Semicolon,
+            40 => Quote,
+            41 => Grave,
+            42 => LeftShift,
+            43 => BackSlash,
+            44 => Z,
+            45 => X,
+            46 => C,
+            47 => V,
+            48 => B,
+            49 => N,
+            50 => M,
+            51 => Comma,
+            52 => Period,
+            53 => Slash,
+            54 => RightShift,
+            55 => NumpadMultiply,
+            56 => LeftAlt,
+            57 => Space,
+            58 => CapsLock,
+            59 => F1,
+            60 => F2,
+            61 => F3,
+            62 => F4,
+            63 => F5,
+            64 => F6,
+            65 => F7,
+            66 => F8,
+            67 => F9,
+            68 => F10,
+            69 => NumLock,
+            70 => ScrollLock,
+            71 => Numpad7,
+            72 => Numpad8,
+            73 => Numpad9,
+            74 => NumpadSubtract,
+            75 => Numpad4,
+            76 => Numpad5,
+            77 => Numpad6,
+            78 => NumpadAdd,
+            79 => Numpad1,
+            80 => Numpad2,
+            81 => Numpad3,
+            82 => Numpad0,
+            83 => NumpadDecimal,
+            86 => OEM102,
+            87 => F11,
+            88 => F12,
+            96 | 0xE01C => NumpadEnter,
+            97 | 0xE01D => RightControl,
+            98 | 0xE035 => NumpadDivide,
+            99 | 0xE037 => PrintScreen,
+            100 | 0xE038 => RightAlt,
+            102 | 0xE047 => Home,
+            103 | 0xE048 => Up,
+            104 | 0xE049 => PageUp,
+            105 | 0xE04B => Left,
+            106 | 0xE04D => Right,
+            107 | 0xE04F => End,
+            108 | 0xE050 => Down,
+            109 | 0xE051 => PageDown,
+            110 | 0xE052 => Insert,
+            111 | 0xE053 => Delete,
+            119 => Pause,
+            125 | 0xE05B => LeftWin,
+            126 | 0xE05C => RightWin,
+            127 | 0xE05D => Apps,
+            _ => Undefined,
+        }
+    }
+
+    /// Look a key up by the name it displays as
+    pub fn from_name(name: &str) -> Option<KeyCode>{
+        KeyCode::ALL.iter().copied().find(|code| code.to_string() == name)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn virtual_keys_round_trip(){
+        for key in KeyCode::ALL.iter(){
+            assert_eq!(KeyCode::from_virtual(key.to_virtual().unwrap()), *key);
+            assert_eq!(KeyCode::from_name(&key.to_string()), Some(*key));
+        }
+        assert_eq!(KeyCode::Undefined.to_virtual(), None);
+    }
+
+    #[test]
+    fn scancodes_are_physical(){
+        assert_eq!(KeyCode::from_scancode(17), KeyCode::W);
+        assert_eq!(KeyCode::from_scancode(57), KeyCode::Space);
+        assert_eq!(KeyCode::from_scancode(103), KeyCode::Up);
+        assert_eq!(KeyCode::from_scancode(0xE048), KeyCode::Up);
+        assert_eq!(KeyCode::from_scancode(5000), KeyCode::Undefined);
+    }
+}
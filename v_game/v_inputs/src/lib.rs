@@ -7,48 +7,156 @@ use std::fmt::Display;
 use std::collections::HashMap;
 use glutin::event::ElementState;
 use glutin::event::DeviceEvent;
-pub use glutin::{dpi::PhysicalPosition};
-use std::collections::hash_map::Entry;
+use glutin::event::MouseScrollDelta;
+pub use glutin::{dpi::PhysicalPosition, event::ModifiersState};
+use glutin::event::MouseButton;
+use std::hash::Hash;
 use nalgebra as na;
 use na::{Vector2};
-use v_windowing::{ApplicationEvent, WindowDisplay};
+use v_windowing::{ApplicationEvent, WindowDisplay, WindowInfo};
+use v_commands::ConsoleLines;
 
+mod actions;
+mod keys;
+mod gamepad;
+mod recording;
+mod cursor;
+pub use actions::*;
+pub use keys::*;
+pub use gamepad::*;
+pub use recording::*;
+pub use cursor::*;
+
+/// The action that switches between windowed and fullscreen, bound to F11 by default
+pub const TOGGLE_FULLSCREEN_ACTION: &str = "toggle_fullscreen";
+
+/// The action that turns vsync on and off, bound to F10 by default
+pub const TOGGLE_VSYNC_ACTION: &str = "toggle_vsync";
+
+/// Button style inputs by key. A release that comes in on the same tick as its press
+/// is held back a tick so the press is still seen.
+pub(crate) struct ButtonStates<K>{
+    states: HashMap<K, KeyState>,
+    deferred_releases: Vec<K>,
+}
+
+impl<K: Hash + Eq + Copy> ButtonStates<K>{
+    pub(crate) fn get(&self, key: &K) -> &KeyState{
+        self.states.get(key).unwrap_or(&KeyState::Up)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn insert(&mut self, key: K, state: KeyState){
+        self.states.insert(key, state);
+    }
+
+    pub(crate) fn set(&mut self, key: K, pressed: bool){
+        let current = *self.get(&key);
+        if pressed{
+            self.deferred_releases.retain(|deferred| *deferred != key);
+            //Key repeat sends more presses while a key is held
+            if current != KeyState::Down{
+                self.states.insert(key, KeyState::Pressed);
+            }
+        } else if current == KeyState::Pressed{
+            self.deferred_releases.push(key);
+        } else if current == KeyState::Down{
+            self.states.insert(key, KeyState::Unpressed);
+        }
+    }
+
+    /// Move the previous tick's edges on to held or released states
+    pub(crate) fn advance(&mut self){
+        for state in self.states.values_mut(){
+            match state{
+                KeyState::Pressed => *state = KeyState::Down,
+                KeyState::Unpressed => *state = KeyState::Up,
+                _ => {},
+            }
+        }
+        for key in self.deferred_releases.drain(..){
+            self.states.insert(key, KeyState::Unpressed);
+        }
+    }
+
+    pub(crate) fn clear(&mut self){
+        self.states.clear();
+        self.deferred_releases.clear();
+    }
+}
+
+impl<K> Default for ButtonStates<K>{
+    fn default() -> Self{
+        ButtonStates{
+            states: HashMap::new(),
+            deferred_releases: Vec::new(),
+        }
+    }
+}
+
+impl<K: std::fmt::Debug> std::fmt::Debug for ButtonStates<K>{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result{
+        f.debug_map().entries(self.states.iter()).finish()
+    }
+}
+
+impl<K: Clone> Clone for ButtonStates<K>{
+    fn clone(&self) -> Self{
+        ButtonStates{
+            states: self.states.clone(),
+            deferred_releases: self.deferred_releases.clone(),
+        }
+    }
+}
+
+/// Keys are tracked twice, by where they are on the keyboard and by what the keyboard layout says they are
 pub struct Inputs{
-    keys: HashMap<KeyCode, KeyState>,
-    mouse_buttons: HashMap<ButtonCode, KeyState>,
+    keys: ButtonStates<KeyCode>,
+    logical_keys: ButtonStates<KeyCode>,
+    mouse_buttons: ButtonStates<ButtonCode>,
     mouse_position: Vector2<f32>,
     mouse_delta: Vector2<f32>,
+    mouse_wheel: Vector2<f32>,
+    cursor_in_window: bool,
+    modifiers: ModifiersState,
+    text: String,
+    gamepads: HashMap<GamepadId, GamepadState>,
+    deadzones: Deadzones,
+    actions: ActionMap,
 }
 
 impl Default for Inputs{
     fn default() -> Self{
-        let mut map = HashMap::new();
-        for key in (0..54){
-            map.insert(KeyCode::from_key(key), KeyState::Up);
-        }
-        let mut mouse_map = HashMap::new();
-        mouse_map.insert(ButtonCode::MB0, KeyState::Up);
-        mouse_map.insert(ButtonCode::MB1, KeyState::Up);
-        mouse_map.insert(ButtonCode::MB2, KeyState::Up);
         Inputs{
-            keys: map,
-            mouse_buttons: mouse_map,
+            keys: ButtonStates::default(),
+            logical_keys: ButtonStates::default(),
+            mouse_buttons: ButtonStates::default(),
             mouse_position: Vector2::<f32>::new(0.0, 0.0),
             mouse_delta: Vector2::<f32>::new(0.0, 0.0),
+            mouse_wheel: Vector2::<f32>::new(0.0, 0.0),
+            cursor_in_window: false,
+            modifiers: ModifiersState::default(),
+            text: String::new(),
+            gamepads: HashMap::new(),
+            deadzones: Deadzones::default(),
+            actions: ActionMap::default(),
         }
     }
 }
 
 impl Inputs{
+    /// State of the key at this position on a US QWERTY keyboard, keys that haven't been touched are Up
     pub fn get_key(&self, key_code: &KeyCode) -> &KeyState{
-        self.keys.get(key_code).unwrap()
+        self.keys.get(key_code)
+    }
+
+    /// State of the key the keyboard layout maps to this key code
+    pub fn get_logical_key(&self, key_code: &KeyCode) -> &KeyState{
+        self.logical_keys.get(key_code)
     }
 
     pub fn get_button(&self, mouse_botton: &ButtonCode) -> &KeyState{
-        match self.mouse_buttons.get(mouse_botton){
-            None => &KeyState::Unpressed,
-            Some(item) => item,
-        }
+        self.mouse_buttons.get(mouse_botton)
     }
 
     pub fn get_mouse_position(&self) -> &Vector2<f32>{
@@ -58,93 +166,184 @@ impl Inputs{
     pub fn get_mouse_delta(&self) -> &Vector2<f32>{
         &self.mouse_delta
     }
+
+    /// Lines scrolled this tick, positive y is away from the user
+    pub fn get_mouse_wheel(&self) -> &Vector2<f32>{
+        &self.mouse_wheel
+    }
+
+    pub fn cursor_in_window(&self) -> bool{
+        self.cursor_in_window
+    }
+
+    pub fn get_modifiers(&self) -> ModifiersState{
+        self.modifiers
+    }
+
+    /// Characters typed this tick, control characters like backspace are left out
+    pub fn text_input(&self) -> &str{
+        &self.text
+    }
 }
 
 pub struct InputSystem{
     window_input_queue: Arc<Mutex<Vec<ApplicationEvent>>>,
     hardware_input_queue: Arc<Mutex<Vec<DeviceEvent>>>,
+    /// Number of times the system has run, recordings are tagged with it
+    tick: u64,
+    recorder: Option<InputRecorder>,
+    replay: Option<InputReplay>,
+    /// Command lines are passed from typed_lines on to console_lines so they're recorded with the tick they're handled on
+    typed_lines: Option<ConsoleLines>,
+    console_lines: Option<ConsoleLines>,
 }
 
 impl InputSystem{
     pub fn new(window_input_queue: Arc<Mutex<Vec<ApplicationEvent>>>, hardware_input_queue: Arc<Mutex<Vec<DeviceEvent>>>) -> Self{
-        InputSystem{window_input_queue, hardware_input_queue}
+        InputSystem{
+            window_input_queue,
+            hardware_input_queue,
+            tick: 0,
+            recorder: None,
+            replay: None,
+            typed_lines: None,
+            console_lines: None,
+        }
+    }
+
+    /// Take command lines from typed and hand them on to the ConsoleSystem reading console, along with any replayed ones
+    pub fn with_console(mut self, typed: ConsoleLines, console: ConsoleLines) -> Self{
+        self.typed_lines = Some(typed);
+        self.console_lines = Some(console);
+        self
     }
 }
 
 impl<'a> System<'a> for InputSystem{
     type SystemData = (
         Write<'a, Inputs>,
-        Write<'a, CursorState>);
+        Write<'a, CursorState>,
+        Write<'a, WindowInfo>);
 
-    fn run(&mut self, (mut inputs, mut cursor): Self::SystemData){
-
-        //Update keyboard inputs
-        for (key_code, key_state) in inputs.keys.iter_mut(){
-            match key_state{
-                KeyState::Pressed => *key_state = KeyState::Down,
-                KeyState::Unpressed => *key_state = KeyState::Up,
-                _ => {},
-            }
+    fn run(&mut self, (mut inputs, mut cursor, mut window_info): Self::SystemData){
+        if let Some(replay) = self.replay.as_mut(){
+            replay.push(self.tick, &self.window_input_queue, &self.hardware_input_queue, self.console_lines.as_deref());
         }
 
-        //Update mouse inputs
-        for (key_code, key_state) in inputs.mouse_buttons.iter_mut(){
-            match key_state{
-                KeyState::Pressed => *key_state = KeyState::Down,
-                KeyState::Unpressed => *key_state = KeyState::Up,
-                _ => {},
+        if let (Some(typed), Some(console)) = (self.typed_lines.as_ref(), self.console_lines.as_ref()){
+            for line in typed.lock().unwrap().drain(..){
+                if let Some(recorder) = self.recorder.as_mut(){
+                    recorder.record(self.tick, command_text(&line));
+                }
+                console.lock().unwrap().push(line);
             }
         }
 
+        //Update keyboard and mouse inputs
+        inputs.keys.advance();
+        inputs.logical_keys.advance();
+        inputs.mouse_buttons.advance();
+
+        //Needs to be set to 0 because not every frame will have a delta
+        inputs.mouse_delta = Vector2::new(0.0, 0.0);
+        inputs.mouse_wheel = Vector2::new(0.0, 0.0);
+        inputs.text.clear();
+
         //Get new inputs
         for input in self.window_input_queue.lock().unwrap().drain(..){
+            if let Some(recorder) = self.recorder.as_mut(){
+                recorder.record(self.tick, window_text(&input));
+            }
+            window_info.handle_event(&input);
             match input{
-                ApplicationEvent::CursorMoved {device_id, position, modifiers} =>{
+                ApplicationEvent::CursorMoved {position, ..} =>{
                     inputs.mouse_position = Vector2::new(position.x as f32, position.y as f32);
                 }
                 ApplicationEvent::Focused(focused) => {
-                    cursor.locked = focused;
+                    cursor.set_focused(focused);
+                    //Keys let go while another window had focus would otherwise stay down
+                    if !focused{
+                        inputs.keys.clear();
+                        inputs.logical_keys.clear();
+                        inputs.mouse_buttons.clear();
+                    }
+                }
+                ApplicationEvent::CursorEntered {..} => inputs.cursor_in_window = true,
+                ApplicationEvent::CursorLeft {..} => inputs.cursor_in_window = false,
+                ApplicationEvent::ModifiersChanged(modifiers) => inputs.modifiers = modifiers,
+                ApplicationEvent::ReceivedCharacter(character) if !character.is_control() => inputs.text.push(character),
+                ApplicationEvent::MouseWheel {delta, ..} => {
+                    inputs.mouse_wheel += match delta{
+                        MouseScrollDelta::LineDelta(x, y) => Vector2::new(x, y),
+                        //Roughly how many pixels a line is on most platforms
+                        MouseScrollDelta::PixelDelta(position) => Vector2::new(position.x as f32, position.y as f32) / 20.0,
+                    };
+                }
+                ApplicationEvent::MouseInput {state, button, ..} => {
+                    let button = ButtonCode::from_mouse_button(button);
+                    let pressed = state == ElementState::Pressed;
+                    //The click that takes the cursor back doesn't count as a click in the game
+                    if cursor.mode() == CursorMode::Free{
+                        if pressed && cursor.focused(){
+                            cursor.resume();
+                        }
+                    } else if button != ButtonCode::Undefined{
+                        inputs.mouse_buttons.set(button, pressed);
+                    }
                 }
                 _ => {},
             }
         }
 
-        //Needs to be set to 0 because not every frame will have a delta
-        inputs.mouse_delta = Vector2::new(0.0, 0.0);
-
         //Get hardware inputs
         for input in self.hardware_input_queue.lock().unwrap().drain(..){
-            if cursor.locked{
+            if let Some(recorder) = self.recorder.as_mut(){
+                recorder.record(self.tick, device_text(&input));
+            }
+            //Some platforms send device events to windows that don't have focus
+            if cursor.focused(){
                 match input{
                     DeviceEvent::Key(key) =>{
-                        match inputs.keys.entry(KeyCode::from_key(key.scancode)){
-                            Entry::Occupied(mut e) => {
-                                e.insert(if key.state == ElementState::Pressed {KeyState::Pressed}  else {KeyState::Unpressed});
-                            },
-                            _ => {println!("{}", key.scancode)},
-                        } ;
-                    },
-                    DeviceEvent::Button {button, state} =>{
-                        match inputs.mouse_buttons.entry(ButtonCode::from_key(button)){
-                            Entry::Occupied(mut e) => {
-                                e.insert(if state == ElementState::Pressed {KeyState::Pressed}  else {KeyState::Unpressed});
-                            },
-                            Entry::Vacant(mut e) => {
-                                e.insert(if state == ElementState::Pressed {KeyState::Pressed}  else {KeyState::Unpressed});
-                            }
+                        let pressed = key.state == ElementState::Pressed;
+                        let logical = key.virtual_keycode.map(KeyCode::from_virtual).unwrap_or(KeyCode::Undefined);
+                        //Scancodes we don't know, like every one on macOS, fall back to the layout's key and the other way around
+                        let physical = match KeyCode::from_scancode(key.scancode){
+                            KeyCode::Undefined => logical,
+                            code => code,
+                        };
+                        let logical = if logical == KeyCode::Undefined {physical} else {logical};
+                        if physical != KeyCode::Undefined{
+                            inputs.keys.set(physical, pressed);
+                            inputs.logical_keys.set(logical, pressed);
                         }
                     },
-                        DeviceEvent::MouseMotion {delta} => {
-                        inputs.mouse_delta = Vector2::new(delta.0 as f32, delta.1 as f32);
+                    //Several motion events can arrive in one tick
+                    DeviceEvent::MouseMotion {delta} if cursor.captured() => {
+                        inputs.mouse_delta += Vector2::new(delta.0 as f32, delta.1 as f32);
                     },
                     _ => {},
                 }
             }
         }
+
+        if inputs.action_pressed(RELEASE_CURSOR_ACTION){
+            cursor.release();
+        }
+        if inputs.action_pressed(TOGGLE_FULLSCREEN_ACTION){
+            window_info.toggle_fullscreen();
+        }
+        if inputs.action_pressed(TOGGLE_VSYNC_ACTION){
+            window_info.toggle_vsync();
+        }
+
+        if let Some(recorder) = self.recorder.as_mut(){
+            recorder.flush();
+        }
+        self.tick += 1;
     }
 }
 
-#[derive(Display, PartialEq, Eq)]
+#[derive(Display, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum KeyState{
     Pressed,
     Down,
@@ -152,118 +351,165 @@ pub enum KeyState{
     Unpressed
 }
 
-#[derive(Display, FromPrimitive, PartialEq, Eq, Hash)]
-pub enum KeyCode{
+#[derive(Display, FromPrimitive, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ButtonCode{
     Undefined = 0,
-    ESC,
-    KB1,
-    KB2,
-    KB3,
-    KB4,
-    KB5,
-    KB6,
-    KB7,
-    KB8,
-    KB9,
-    KB0,
-    MinusUnderscore,
-    EqualPlus,
-    Backspace,
-    Tab,
-    Q,
-    W,
-    E,
-    R,
-    T,
-    Y,
-    U,
-    I,
-    O,
-    P,
-    OpenBracket,
-    CloseBracket,
-    Enter,
-    LeftControl,
-    A,
-    S,
-    D,
-    F,
-    G,
-    H,
-    J,
-    K,
-    L,
-    Colon,
-    Quote,
-    Dunno,
-    LeftShift,
-    BackSlash,
-    Z,
-    X,
-    C,
-    V,
-    B,
-    N,
-    M,
-    Comma,
-    Period,
-    Slash,
-    RightShift,
+    MB0 = 1,
+    MB1,
+    MB2,
 }
 
-impl KeyCode{
-    pub fn from_key(key: u32) -> KeyCode{
-        match KeyCode::from_u32(key){
+impl ButtonCode{
+    pub fn from_key(key: u32) -> ButtonCode{
+        match ButtonCode::from_u32(key){
             Some(code) => code,
-            None => KeyCode::Undefined
+            None => ButtonCode::Undefined
         }
     }
-}
-
-pub struct CursorState{
-    pub visible: bool,
-    pub locked: bool,
-}
 
-impl Default for CursorState{
-    fn default() -> Self{
-        CursorState{
-            visible: false,
-            locked: true,
+    /// Buttons are numbered the way X11 numbers them, left, middle then right
+    pub fn from_mouse_button(button: MouseButton) -> ButtonCode{
+        match button{
+            MouseButton::Left => ButtonCode::MB0,
+            MouseButton::Middle => ButtonCode::MB1,
+            MouseButton::Right => ButtonCode::MB2,
+            MouseButton::Other(_) => ButtonCode::Undefined,
         }
     }
+
+    pub fn from_name(name: &str) -> Option<ButtonCode>{
+        (1..=ButtonCode::MB2 as u32).map(ButtonCode::from_key).find(|code| code.to_string() == name)
+    }
 }
 
-pub struct CursorLockSystem{}
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use glutin::dpi::PhysicalSize;
+    use v_windowing::FullscreenMode;
 
-impl<'a> System<'a> for CursorLockSystem{
-    type SystemData = (
-        Read<'a, CursorState>,
-        Write<'a, WindowDisplay>);
-
-    fn run(&mut self, (cursor_state, window_display): Self::SystemData){
-        let window_display = window_display.as_ref().unwrap().lock().unwrap();
-        let window_size = window_display.gl_window().window().inner_size();
-        if cursor_state.locked{
-            window_display.gl_window().window().set_cursor_grab(true);
+    fn queue_events(window_queue: &Arc<Mutex<Vec<ApplicationEvent>>>, hardware_queue: &Arc<Mutex<Vec<DeviceEvent>>>, events: &[&str]){
+        for event in events.iter(){
+            match RecordedEvent::parse(event).unwrap(){
+                RecordedEvent::Window(event) => window_queue.lock().unwrap().push(event),
+                RecordedEvent::Device(event) => hardware_queue.lock().unwrap().push(event),
+                RecordedEvent::Command(_) => panic!("{} isn't an input event", event),
+            }
         }
-        window_display.gl_window().window().set_cursor_visible(cursor_state.visible);
     }
-}
 
-#[derive(Display, FromPrimitive, PartialEq, Eq, Hash)]
-pub enum ButtonCode{
-    Undefined = 0,
-    MB0 = 1,
-    MB1,
-    MB2,
-}
+    #[test]
+    fn tapped_inputs_are_seen(){
+        let mut world = World::new();
+        world.insert(Inputs::default());
+        world.insert(CursorState::default());
+        world.insert(WindowInfo::default());
+        let window_queue = Arc::new(Mutex::new(Vec::new()));
+        let hardware_queue = Arc::new(Mutex::new(Vec::new()));
+        let mut system = InputSystem::new(window_queue.clone(), hardware_queue.clone());
 
-impl ButtonCode{
-    pub fn from_key(key: u32) -> ButtonCode{
-        match ButtonCode::from_u32(key){
-            Some(code) => code,
-            None => ButtonCode::Undefined
+        queue_events(&window_queue, &hardware_queue, &["key 17 pressed W", "key 17 released W", "mouse left pressed", "mouse left released"]);
+        system.run_now(&world);
+        assert_eq!(*world.read_resource::<Inputs>().get_key(&KeyCode::W), KeyState::Pressed);
+        assert_eq!(*world.read_resource::<Inputs>().get_button(&ButtonCode::MB0), KeyState::Pressed);
+
+        system.run_now(&world);
+        assert_eq!(*world.read_resource::<Inputs>().get_key(&KeyCode::W), KeyState::Unpressed);
+        assert_eq!(*world.read_resource::<Inputs>().get_button(&ButtonCode::MB0), KeyState::Unpressed);
+
+        //Pressing again before the deferred release goes through keeps the key held
+        queue_events(&window_queue, &hardware_queue, &["key 17 pressed W", "key 17 released W", "key 17 pressed W"]);
+        system.run_now(&world);
+        system.run_now(&world);
+        assert_eq!(*world.read_resource::<Inputs>().get_key(&KeyCode::W), KeyState::Down);
+        assert_eq!(*world.read_resource::<Inputs>().get_button(&ButtonCode::MB0), KeyState::Up);
+    }
+
+    #[test]
+    fn window_events_update_inputs(){
+        let mut world = World::new();
+        world.insert(Inputs::default());
+        world.insert(CursorState::default());
+        world.insert(WindowInfo::default());
+        let window_queue = Arc::new(Mutex::new(Vec::new()));
+        let hardware_queue = Arc::new(Mutex::new(Vec::new()));
+        let mut system = InputSystem::new(window_queue.clone(), hardware_queue.clone());
+
+        queue_events(&window_queue, &hardware_queue, &[
+            "entered", "char 104", "char 8", "char 105",
+            "window_wheel lines 0 1 moved", "window_wheel pixels 0 20 moved",
+            &format!("modifiers {}", (ModifiersState::SHIFT | ModifiersState::CTRL).bits()),
+            "motion 1 2", "motion 3 4",
+            "resized 640 480", "key 87 pressed F11",
+        ]);
+        system.run_now(&world);
+        {
+            let inputs = world.read_resource::<Inputs>();
+            assert!(inputs.cursor_in_window());
+            assert_eq!(inputs.text_input(), "hi");
+            assert_eq!(*inputs.get_mouse_wheel(), Vector2::new(0.0, 2.0));
+            assert_eq!(*inputs.get_mouse_delta(), Vector2::new(4.0, 6.0));
+            assert!(inputs.get_modifiers().shift() && inputs.get_modifiers().ctrl() && !inputs.get_modifiers().alt());
+            let window_info = world.read_resource::<WindowInfo>();
+            assert_eq!(window_info.size(), PhysicalSize::new(640, 480));
+            assert_eq!(window_info.fullscreen(), FullscreenMode::Borderless);
         }
+
+        queue_events(&window_queue, &hardware_queue, &["left"]);
+        system.run_now(&world);
+        let inputs = world.read_resource::<Inputs>();
+        assert!(!inputs.cursor_in_window());
+        assert_eq!(inputs.text_input(), "");
+        assert_eq!(*inputs.get_mouse_wheel(), Vector2::new(0.0, 0.0));
+        assert!(inputs.get_modifiers().shift());
+    }
+
+    #[test]
+    fn command_lines_reach_the_console_on_their_tick(){
+        let mut world = World::new();
+        world.insert(Inputs::default());
+        world.insert(CursorState::default());
+        world.insert(WindowInfo::default());
+        let typed = ConsoleLines::default();
+        let console = ConsoleLines::default();
+        let recording = InputRecording::parse("VINPUT 1\n1 command time set noon\n").unwrap();
+        let mut system = InputSystem::new(Arc::default(), Arc::default())
+            .with_replay(InputReplay::new(recording))
+            .with_console(typed.clone(), console.clone());
+
+        typed.lock().unwrap().push("tp 0 0 0".to_string());
+        system.run_now(&world);
+        assert_eq!(*console.lock().unwrap(), vec!["tp 0 0 0".to_string()]);
+        assert!(typed.lock().unwrap().is_empty());
+
+        console.lock().unwrap().clear();
+        system.run_now(&world);
+        assert_eq!(*console.lock().unwrap(), vec!["time set noon".to_string()]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn escape_frees_cursor_until_clicked(){
+        let mut world = World::new();
+        world.insert(Inputs::default());
+        world.insert(CursorState::default());
+        world.insert(WindowInfo::default());
+        let window_queue = Arc::new(Mutex::new(Vec::new()));
+        let hardware_queue = Arc::new(Mutex::new(Vec::new()));
+        let mut system = InputSystem::new(window_queue.clone(), hardware_queue.clone());
+
+        queue_events(&window_queue, &hardware_queue, &["key 1 pressed ESC", "key 1 released ESC"]);
+        system.run_now(&world);
+        assert_eq!(world.read_resource::<CursorState>().mode(), CursorMode::Free);
+
+        //Moving a free cursor doesn't turn the camera, keys still work
+        queue_events(&window_queue, &hardware_queue, &["motion 5 5", "key 17 pressed W"]);
+        system.run_now(&world);
+        assert_eq!(*world.read_resource::<Inputs>().get_mouse_delta(), Vector2::new(0.0, 0.0));
+        assert_eq!(*world.read_resource::<Inputs>().get_key(&KeyCode::W), KeyState::Pressed);
+
+        queue_events(&window_queue, &hardware_queue, &["mouse left pressed"]);
+        system.run_now(&world);
+        assert_eq!(world.read_resource::<CursorState>().mode(), CursorMode::Captured);
+        assert_eq!(*world.read_resource::<Inputs>().get_button(&ButtonCode::MB0), KeyState::Up);
+    }
+}
@@ -0,0 +1,358 @@
+use crate::*;
+use glutin::event::{DeviceId, KeyboardInput, ModifiersState, MouseButton, TouchPhase};
+use glutin::dpi::{LogicalPosition, PhysicalSize};
+use std::collections::VecDeque;
+use std::io::Write as IoWrite;
+use std::path::Path;
+
+const RECORDING_HEADER: &str = "VINPUT 1";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordingError{
+    Io(std::io::ErrorKind),
+    InvalidHeader,
+    InvalidLine(usize),
+}
+
+impl From<std::io::Error> for RecordingError{
+    fn from(error: std::io::Error) -> Self{
+        RecordingError::Io(error.kind())
+    }
+}
+
+/// An input event as it came out of one of the GliumState queues, or a command line typed into the console
+pub enum RecordedEvent{
+    Window(ApplicationEvent),
+    Device(DeviceEvent),
+    Command(String),
+}
+
+fn state_name(state: ElementState) -> &'static str{
+    match state{
+        ElementState::Pressed => "pressed",
+        ElementState::Released => "released",
+    }
+}
+
+fn parse_state(text: &str) -> Option<ElementState>{
+    match text{
+        "pressed" => Some(ElementState::Pressed),
+        "released" => Some(ElementState::Released),
+        _ => None,
+    }
+}
+
+fn scroll_text(delta: &MouseScrollDelta) -> String{
+    match delta{
+        MouseScrollDelta::LineDelta(x, y) => format!("lines {} {}", x, y),
+        MouseScrollDelta::PixelDelta(position) => format!("pixels {} {}", position.x, position.y),
+    }
+}
+
+fn parse_scroll(kind: &str, x: &str, y: &str) -> Option<MouseScrollDelta>{
+    match kind{
+        "lines" => Some(MouseScrollDelta::LineDelta(x.parse().ok()?, y.parse().ok()?)),
+        "pixels" => Some(MouseScrollDelta::PixelDelta(LogicalPosition::new(x.parse().ok()?, y.parse().ok()?))),
+        _ => None,
+    }
+}
+
+fn phase_name(phase: TouchPhase) -> &'static str{
+    match phase{
+        TouchPhase::Started => "started",
+        TouchPhase::Moved => "moved",
+        TouchPhase::Ended => "ended",
+        TouchPhase::Cancelled => "cancelled",
+    }
+}
+
+fn parse_phase(text: &str) -> Option<TouchPhase>{
+    match text{
+        "started" => Some(TouchPhase::Started),
+        "moved" => Some(TouchPhase::Moved),
+        "ended" => Some(TouchPhase::Ended),
+        "cancelled" => Some(TouchPhase::Cancelled),
+        _ => None,
+    }
+}
+
+fn mouse_button_name(button: MouseButton) -> String{
+    match button{
+        MouseButton::Left => "left".to_string(),
+        MouseButton::Right => "right".to_string(),
+        MouseButton::Middle => "middle".to_string(),
+        MouseButton::Other(index) => index.to_string(),
+    }
+}
+
+fn parse_mouse_button(text: &str) -> Option<MouseButton>{
+    match text{
+        "left" => Some(MouseButton::Left),
+        "right" => Some(MouseButton::Right),
+        "middle" => Some(MouseButton::Middle),
+        _ => text.parse().ok().map(MouseButton::Other),
+    }
+}
+
+pub(crate) fn window_text(event: &ApplicationEvent) -> Option<String>{
+    Some(match event{
+        ApplicationEvent::Focused(focused) => format!("focused {}", focused),
+        ApplicationEvent::CursorMoved{position, ..} => format!("cursor {} {}", position.x, position.y),
+        ApplicationEvent::CursorEntered{..} => "entered".to_string(),
+        ApplicationEvent::CursorLeft{..} => "left".to_string(),
+        ApplicationEvent::ReceivedCharacter(character) => format!("char {}", *character as u32),
+        ApplicationEvent::ModifiersChanged(modifiers) => format!("modifiers {}", modifiers.bits()),
+        ApplicationEvent::MouseWheel{delta, phase, ..} => format!("window_wheel {} {}", scroll_text(delta), phase_name(*phase)),
+        ApplicationEvent::MouseInput{state, button, ..} => format!("mouse {} {}", mouse_button_name(*button), state_name(*state)),
+        ApplicationEvent::Resized(size) => format!("resized {} {}", size.width, size.height),
+        ApplicationEvent::ScaleFactorChanged{scale_factor, new_inner_size} => format!("scale {} {} {}", scale_factor, new_inner_size.width, new_inner_size.height),
+        _ => return None,
+    })
+}
+
+/// Clicks and scrolling are read from the window's events, so the device's button and wheel events aren't recorded
+pub(crate) fn device_text(event: &DeviceEvent) -> Option<String>{
+    Some(match event{
+        DeviceEvent::Key(key) => format!("key {} {} {}", key.scancode, state_name(key.state),
+            key.virtual_keycode.map(|code| KeyCode::from_virtual(code).to_string()).unwrap_or_else(|| "-".to_string())),
+        DeviceEvent::MouseMotion{delta} => format!("motion {} {}", delta.0, delta.1),
+        _ => return None,
+    })
+}
+
+/// Blank lines don't do anything so they aren't recorded
+pub(crate) fn command_text(line: &str) -> Option<String>{
+    let line = line.trim();
+    if line.is_empty(){
+        return None;
+    }
+    Some(format!("command {}", line))
+}
+
+impl RecordedEvent{
+    /// The event as one line of text without its tick, None for events that aren't recorded
+    pub fn to_text(&self) -> Option<String>{
+        match self{
+            RecordedEvent::Window(event) => window_text(event),
+            RecordedEvent::Device(event) => device_text(event),
+            RecordedEvent::Command(line) => command_text(line),
+        }
+    }
+
+    #[allow(deprecated)]
+    pub fn parse(text: &str) -> Option<Self>{
+        //Replayed events never reach winit, so a made up device is fine
+        let device_id = unsafe {DeviceId::dummy()};
+        //Commands keep their spacing, everything after the first word is the line
+        if let Some(line) = text.strip_prefix("command "){
+            let line = line.trim();
+            return if line.is_empty() {None} else {Some(RecordedEvent::Command(line.to_string()))};
+        }
+        let parts: Vec<&str> = text.split_whitespace().collect();
+        Some(match parts.as_slice(){
+            ["key", scancode, state, virtual_keycode] => RecordedEvent::Device(DeviceEvent::Key(KeyboardInput{
+                scancode: scancode.parse().ok()?,
+                state: parse_state(state)?,
+                virtual_keycode: match *virtual_keycode{
+                    "-" => None,
+                    name => Some(KeyCode::from_name(name)?.to_virtual()?),
+                },
+                modifiers: ModifiersState::default(),
+            })),
+            //Older recordings have device button and wheel lines, they still load though nothing reads them
+            ["button", button, state] => RecordedEvent::Device(DeviceEvent::Button{button: button.parse().ok()?, state: parse_state(state)?}),
+            ["motion", x, y] => RecordedEvent::Device(DeviceEvent::MouseMotion{delta: (x.parse().ok()?, y.parse().ok()?)}),
+            ["wheel", kind, x, y] => RecordedEvent::Device(DeviceEvent::MouseWheel{delta: parse_scroll(kind, x, y)?}),
+            ["focused", focused] => RecordedEvent::Window(ApplicationEvent::Focused(focused.parse().ok()?)),
+            ["cursor", x, y] => RecordedEvent::Window(ApplicationEvent::CursorMoved{
+                device_id,
+                position: PhysicalPosition::new(x.parse().ok()?, y.parse().ok()?),
+                modifiers: ModifiersState::default(),
+            }),
+            ["entered"] => RecordedEvent::Window(ApplicationEvent::CursorEntered{device_id}),
+            ["left"] => RecordedEvent::Window(ApplicationEvent::CursorLeft{device_id}),
+            ["char", code] => RecordedEvent::Window(ApplicationEvent::ReceivedCharacter(std::char::from_u32(code.parse().ok()?)?)),
+            ["modifiers", bits] => RecordedEvent::Window(ApplicationEvent::ModifiersChanged(ModifiersState::from_bits_truncate(bits.parse().ok()?))),
+            ["window_wheel", kind, x, y, phase] => RecordedEvent::Window(ApplicationEvent::MouseWheel{
+                device_id,
+                delta: parse_scroll(kind, x, y)?,
+                phase: parse_phase(phase)?,
+                modifiers: ModifiersState::default(),
+            }),
+            ["mouse", button, state] => RecordedEvent::Window(ApplicationEvent::MouseInput{
+                device_id,
+                state: parse_state(state)?,
+                button: parse_mouse_button(button)?,
+                modifiers: ModifiersState::default(),
+            }),
+            ["resized", width, height] => RecordedEvent::Window(ApplicationEvent::Resized(PhysicalSize::new(width.parse().ok()?, height.parse().ok()?))),
+            ["scale", scale_factor, width, height] => RecordedEvent::Window(ApplicationEvent::ScaleFactorChanged{
+                scale_factor: scale_factor.parse().ok()?,
+                new_inner_size: PhysicalSize::new(width.parse().ok()?, height.parse().ok()?),
+            }),
+            _ => return None,
+        })
+    }
+}
+
+/// A parsed recording, every event tagged with the input tick it was drained on
+#[derive(Default)]
+pub struct InputRecording{
+    events: VecDeque<(u64, RecordedEvent)>,
+}
+
+impl InputRecording{
+    /// Recordings are a header line then one "<tick> <event>" line per event, in tick order
+    pub fn parse(text: &str) -> Result<Self, RecordingError>{
+        let mut lines = text.lines().enumerate();
+        match lines.next(){
+            Some((_, header)) if header.trim() == RECORDING_HEADER => {},
+            _ => return Err(RecordingError::InvalidHeader),
+        }
+
+        let mut events = VecDeque::new();
+        for (number, line) in lines{
+            if line.trim().is_empty(){
+                continue;
+            }
+            let invalid = RecordingError::InvalidLine(number + 1);
+            let (tick, event) = line.trim().split_once(' ').ok_or_else(|| invalid.clone())?;
+            let tick = tick.parse().map_err(|_| invalid.clone())?;
+            events.push_back((tick, RecordedEvent::parse(event).ok_or(invalid)?));
+        }
+        Ok(InputRecording{events})
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, RecordingError>{
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn len(&self) -> usize{
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.events.is_empty()
+    }
+
+    /// Number of ticks the recording covers, up to and including its last event
+    pub fn ticks(&self) -> u64{
+        self.events.back().map(|(tick, _)| tick + 1).unwrap_or(0)
+    }
+}
+
+/// Writes every event and command line InputSystem drains to a file as it goes, so a recording survives a crash
+pub struct InputRecorder{
+    writer: Box<dyn IoWrite>,
+}
+
+impl InputRecorder{
+    pub fn new<W: IoWrite + 'static>(writer: W) -> Result<Self, RecordingError>{
+        let mut writer: Box<dyn IoWrite> = Box::new(writer);
+        writeln!(writer, "{}", RECORDING_HEADER)?;
+        Ok(InputRecorder{writer})
+    }
+
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, RecordingError>{
+        Self::new(std::io::BufWriter::new(std::fs::File::create(path)?))
+    }
+
+    pub(crate) fn record(&mut self, tick: u64, text: Option<String>){
+        if let Some(text) = text{
+            if let Err(error) = writeln!(self.writer, "{} {}", tick, text){
+                println!("Couldn't record input: {}", error);
+            }
+        }
+    }
+
+    pub(crate) fn flush(&mut self){
+        if let Err(error) = self.writer.flush(){
+            println!("Couldn't record input: {}", error);
+        }
+    }
+}
+
+/// Feeds a recording back into the input queues on the ticks it was recorded on
+pub struct InputReplay{
+    recording: InputRecording,
+}
+
+impl InputReplay{
+    pub fn new(recording: InputRecording) -> Self{
+        InputReplay{recording}
+    }
+
+    pub fn finished(&self) -> bool{
+        self.recording.is_empty()
+    }
+
+    /// Commands are dropped without a console to hand them to
+    pub(crate) fn push(&mut self, tick: u64, window_queue: &Mutex<Vec<ApplicationEvent>>, hardware_queue: &Mutex<Vec<DeviceEvent>>, console: Option<&Mutex<Vec<String>>>){
+        while matches!(self.recording.events.front(), Some((event_tick, _)) if *event_tick <= tick){
+            match self.recording.events.pop_front().unwrap().1{
+                RecordedEvent::Window(event) => window_queue.lock().unwrap().push(event),
+                RecordedEvent::Device(event) => hardware_queue.lock().unwrap().push(event),
+                RecordedEvent::Command(line) => if let Some(console) = console{
+                    console.lock().unwrap().push(line);
+                },
+            }
+        }
+    }
+}
+
+impl InputSystem{
+    /// Write everything this system drains to a recorder
+    pub fn with_recorder(mut self, recorder: InputRecorder) -> Self{
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Push a recording into the queues before they're drained each tick
+    pub fn with_replay(mut self, replay: InputReplay) -> Self{
+        self.replay = Some(replay);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn events_round_trip(){
+        let lines = [
+            "key 17 pressed W",
+            "key 57 released -",
+            "motion 1.5 -0.25",
+            "focused false",
+            "cursor 100.5 20",
+            "entered",
+            "left",
+            "char 97",
+            "modifiers 4",
+            "window_wheel pixels 3 4.5 moved",
+            "mouse left released",
+            "mouse 7 pressed",
+            "resized 800 600",
+            "scale 1.5 1200 900",
+            "command time set  noon",
+        ];
+        for line in lines.iter(){
+            assert_eq!(RecordedEvent::parse(line).unwrap().to_text().unwrap(), *line);
+        }
+        assert!(RecordedEvent::parse("key 17 sideways W").is_none());
+        assert!(RecordedEvent::parse("command  ").is_none());
+        assert!(RecordedEvent::parse("button 1 pressed").unwrap().to_text().is_none());
+        assert!(RecordedEvent::parse("wheel lines 0 -1").unwrap().to_text().is_none());
+        assert!(RecordedEvent::Command(" ".to_string()).to_text().is_none());
+    }
+
+    #[test]
+    fn parse_recording(){
+        let recording = InputRecording::parse("VINPUT 1\n0 key 17 pressed W\n\n4 motion 1 1\n").unwrap();
+        assert_eq!(recording.len(), 2);
+        assert_eq!(recording.ticks(), 5);
+        assert_eq!(InputRecording::parse("0 key 17 pressed W").err(), Some(RecordingError::InvalidHeader));
+        assert_eq!(InputRecording::parse("VINPUT 1\nsoon key 17 pressed W").err(), Some(RecordingError::InvalidLine(2)));
+    }
+}
@@ -0,0 +1,106 @@
+use std::ops::Range;
+
+/// Hands out ranges of a buffer, keeping the gaps left by freed ranges in a sorted free list.
+/// Neighbouring gaps are merged as ranges are freed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeAllocator{
+    capacity: usize,
+    free: Vec<Range<usize>>,
+}
+
+impl RangeAllocator{
+    pub fn new(capacity: usize) -> Self{
+        let mut free = Vec::new();
+        if capacity > 0{
+            free.push(0..capacity);
+        }
+        RangeAllocator{capacity, free}
+    }
+
+    pub fn capacity(&self) -> usize{
+        self.capacity
+    }
+
+    /// Total free space, which may be split across many gaps
+    pub fn free_space(&self) -> usize{
+        self.free.iter().map(|range| range.len()).sum()
+    }
+
+    /// The first gap length fits in, None when no gap is big enough. Empty ranges always succeed.
+    pub fn allocate(&mut self, length: usize) -> Option<Range<usize>>{
+        if length == 0{
+            return Some(0..0);
+        }
+        let index = self.free.iter().position(|range| range.len() >= length)?;
+        let start = self.free[index].start;
+        self.free[index].start += length;
+        if self.free[index].is_empty(){
+            self.free.remove(index);
+        }
+        Some(start..start + length)
+    }
+
+    /// Give a range back, it has to have come from allocate
+    pub fn free(&mut self, range: Range<usize>){
+        if range.is_empty(){
+            return;
+        }
+        let index = self.free.iter().position(|free| free.start > range.start).unwrap_or(self.free.len());
+        self.free.insert(index, range);
+        //Merge with the gap after and then the one before
+        if index + 1 < self.free.len() && self.free[index].end == self.free[index + 1].start{
+            self.free[index].end = self.free.remove(index + 1).end;
+        }
+        if index > 0 && self.free[index - 1].end == self.free[index].start{
+            self.free[index - 1].end = self.free.remove(index).end;
+        }
+    }
+
+    /// Make the buffer bigger, the new space goes on the end
+    pub fn grow(&mut self, capacity: usize){
+        if capacity <= self.capacity{
+            return;
+        }
+        let old = self.capacity;
+        self.capacity = capacity;
+        self.free(old..capacity);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn freed_ranges_are_reused_and_merged(){
+        let mut allocator = RangeAllocator::new(100);
+        let a = allocator.allocate(30).unwrap();
+        let b = allocator.allocate(30).unwrap();
+        let c = allocator.allocate(30).unwrap();
+        assert_eq!((a.clone(), b.clone(), c.clone()), (0..30, 30..60, 60..90));
+        assert_eq!(allocator.allocate(20), None);
+        assert_eq!(allocator.allocate(0), Some(0..0));
+
+        allocator.free(b);
+        assert_eq!(allocator.allocate(10), Some(30..40));
+        allocator.free(30..40);
+        allocator.free(a);
+        //0..60 is one gap again
+        assert_eq!(allocator.allocate(55), Some(0..55));
+        allocator.free(0..55);
+        allocator.free(c);
+        assert_eq!(allocator.free_space(), 100);
+        assert_eq!(allocator, RangeAllocator::new(100));
+    }
+
+    #[test]
+    fn growing_extends_the_last_gap(){
+        let mut allocator = RangeAllocator::new(10);
+        allocator.allocate(5).unwrap();
+        assert_eq!(allocator.allocate(10), None);
+        allocator.grow(20);
+        assert_eq!(allocator.capacity(), 20);
+        assert_eq!(allocator.allocate(10), Some(5..15));
+        assert_eq!(allocator.free_space(), 5);
+    }
+}
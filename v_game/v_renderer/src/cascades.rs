@@ -0,0 +1,98 @@
+use crate::*;
+use nalgebra_glm as glm;
+
+/// How far behind a cascade's slice of the view shadow casters are still caught, in world units
+pub const CASTER_DISTANCE: f32 = 64.0;
+
+/// Distances from the camera where each cascade starts and ends, count + 1 of them from near to far.
+/// lambda blends evenly spaced splits at 0 with logarithmic ones at 1, which keep more detail close up.
+pub fn cascade_splits(near: f32, far: f32, count: usize, lambda: f32) -> Vec<f32>{
+    (0..=count).map(|i|{
+        let fraction = i as f32 / count as f32;
+        let uniform = near + (far - near) * fraction;
+        let logarithmic = near * (far / near).powf(fraction);
+        uniform + (logarithmic - uniform) * lambda
+    }).collect()
+}
+
+/// World space corners of the part of a camera's view between two distances.
+/// camera_matrix is the camera's transform, it looks down its -z with a vertical field of view of fov.
+pub fn frustum_slice_corners(camera_matrix: &Matrix4<f32>, fov: f32, aspect_ratio: f32, near: f32, far: f32) -> [Vector3<f32>; 8]{
+    let mut corners = [Vector3::zeros(); 8];
+    let tan = (fov / 2.0).tan();
+    for (i, corner) in corners.iter_mut().enumerate(){
+        let distance = if i < 4 {near} else {far};
+        let x = if i % 2 == 0 {-1.0} else {1.0};
+        let y = if (i / 2) % 2 == 0 {-1.0} else {1.0};
+        let view = Vector3::new(x * distance * tan * aspect_ratio, y * distance * tan, -distance);
+        *corner = camera_matrix.transform_point(&view.into()).coords;
+    }
+    corners
+}
+
+/// Light projection times view matrix for one cascade, looking along -light_direction at a box around the corners.
+/// The box is a sphere's so it doesn't change size as the camera turns, and it moves in whole shadow map texels
+/// so shadow edges don't shimmer as the camera moves.
+pub fn fit_cascade(corners: &[Vector3<f32>; 8], light_direction: &Vector3<f32>, resolution: u32) -> Matrix4<f32>{
+    let center = corners.iter().sum::<Vector3<f32>>() / 8.0;
+    //Rounded up with a unit spare so snapping can't push a corner out
+    let radius = corners.iter().map(|corner| (corner - center).norm()).fold(0.0, f32::max).ceil() + 1.0;
+
+    let light_direction = light_direction.normalize();
+    let up = if light_direction[1].abs() > 0.99 {Vector3::new(0.0, 0.0, 1.0)} else {Vector3::new(0.0, 1.0, 0.0)};
+    let light_view = glm::look_at(&Vector3::zeros(), &-light_direction, &up);
+
+    //Snap the centre to the texel grid in light space
+    let texel = radius * 2.0 / resolution.max(1) as f32;
+    let mut light_center = light_view.transform_point(&center.into()).coords;
+    light_center[0] = (light_center[0] / texel).round() * texel;
+    light_center[1] = (light_center[1] / texel).round() * texel;
+
+    //Light space looks down -z, casters up to CASTER_DISTANCE towards the light still land in the map
+    let projection = glm::ortho(
+        light_center[0] - radius, light_center[0] + radius,
+        light_center[1] - radius, light_center[1] + radius,
+        -light_center[2] - radius - CASTER_DISTANCE, -light_center[2] + radius);
+    projection * light_view
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn splits_cover_the_range(){
+        let splits = cascade_splits(1.0, 100.0, 4, 0.5);
+        assert_eq!(splits.len(), 5);
+        assert_eq!(splits[0], 1.0);
+        assert!((splits[4] - 100.0).abs() < 0.001);
+        assert!(splits.windows(2).all(|pair| pair[0] < pair[1]));
+        //Logarithmic splits are closer together near the camera
+        assert!(splits[1] < 25.75);
+        assert_eq!(cascade_splits(1.0, 100.0, 2, 0.0), vec![1.0, 50.5, 100.0]);
+    }
+
+    #[test]
+    fn cascades_hold_their_slice(){
+        let camera = glm::translation(&Vector3::new(10.0, 20.0, 30.0)) * glm::rotation(0.7, &Vector3::new(0.0, 1.0, 0.0));
+        let corners = frustum_slice_corners(&camera, 1.2, 16.0 / 9.0, 5.0, 40.0);
+        //Near corners are closer to the camera than far ones
+        let position = Vector3::new(10.0, 20.0, 30.0);
+        assert!((corners[0] - position).norm() < (corners[4] - position).norm());
+
+        let light_direction = Vector3::new(0.3, 1.0, 0.2);
+        let light = fit_cascade(&corners, &light_direction, 2048);
+        for corner in corners.iter(){
+            let clip = light.transform_point(&(*corner).into());
+            assert!(clip.coords.iter().all(|x| x.abs() <= 1.0), "{:?} is outside the cascade", clip);
+        }
+
+        //Something between the slice and the sun still casts into it
+        let caster = corners[4] + light_direction.normalize() * (CASTER_DISTANCE - 1.0);
+        assert!(light.transform_point(&caster.into()).coords.iter().all(|x| x.abs() <= 1.0));
+
+        //Straight down sun doesn't break the light's view
+        let overhead = fit_cascade(&corners, &Vector3::new(0.0, 1.0, 0.0), 1024);
+        assert!(overhead.iter().all(|x| x.is_finite()));
+    }
+}
@@ -0,0 +1,310 @@
+use crate::*;
+use std::ops::Range;
+use glium::index::{DrawCommandIndices, DrawCommandsIndicesBuffer};
+
+/// Vertices the chunk geometry pool starts with room for, it doubles when it runs out
+pub const INITIAL_POOL_VERTICES: usize = 1 << 18;
+/// Indices the chunk geometry pool starts with room for
+pub const INITIAL_POOL_INDICES: usize = 3 << 17;
+
+/// Where a mesh's vertices and indices live in a GeometryPool. Indices start from 0 for each mesh.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeometryAllocation{
+    pub vertices: Range<usize>,
+    pub indices: Range<usize>,
+}
+
+impl GeometryAllocation{
+    pub fn is_empty(&self) -> bool{
+        self.indices.is_empty()
+    }
+
+    /// Draw command for this mesh, base_instance picks its entry in the per instance buffer
+    pub fn draw_command(&self, base_instance: u32) -> DrawCommandIndices{
+        DrawCommandIndices{
+            count: self.indices.len() as u32,
+            instance_count: 1,
+            first_index: self.indices.start as u32,
+            base_vertex: self.vertices.start as u32,
+            base_instance,
+        }
+    }
+}
+
+/// Packs many meshes into one big vertex buffer and one big index buffer so they can be drawn together.
+/// When a buffer is full it's replaced with one twice the size and the old contents are copied over.
+pub struct GeometryPool<V>
+    where V: Vertex{
+    pub vertex_buffer: VertexBuffer<V>,
+    pub index_buffer: IndexBuffer<u32>,
+    vertex_space: RangeAllocator,
+    index_space: RangeAllocator,
+}
+
+impl<V> GeometryPool<V>
+    where V: Vertex{
+    pub fn new<F>(display: &F, vertices: usize, indices: usize) -> Self
+    where F: Facade{
+        GeometryPool{
+            vertex_buffer: VertexBuffer::empty_dynamic(display, vertices).unwrap(),
+            index_buffer: IndexBuffer::empty_dynamic(display, PrimitiveType::TrianglesList, indices).unwrap(),
+            vertex_space: RangeAllocator::new(vertices),
+            index_space: RangeAllocator::new(indices),
+        }
+    }
+
+    /// Copy a mesh into the pool, growing the buffers when it doesn't fit
+    pub fn insert<F>(&mut self, display: &F, verts: &[V], indices: &[u32]) -> GeometryAllocation
+    where F: Facade{
+        let vertex_range = match self.vertex_space.allocate(verts.len()){
+            Some(range) => range,
+            None => {
+                self.grow_vertices(display, verts.len());
+                self.vertex_space.allocate(verts.len()).unwrap()
+            }
+        };
+        let index_range = match self.index_space.allocate(indices.len()){
+            Some(range) => range,
+            None => {
+                self.grow_indices(display, indices.len());
+                self.index_space.allocate(indices.len()).unwrap()
+            }
+        };
+
+        if !verts.is_empty(){
+            self.vertex_buffer.slice(vertex_range.clone()).unwrap().write(verts);
+        }
+        if !indices.is_empty(){
+            self.index_buffer.slice(index_range.clone()).unwrap().write(indices);
+        }
+        GeometryAllocation{vertices: vertex_range, indices: index_range}
+    }
+
+    /// Free a mesh's space to be reused, the old data is left in the buffers until it's overwritten
+    pub fn remove(&mut self, allocation: &GeometryAllocation){
+        self.vertex_space.free(allocation.vertices.clone());
+        self.index_space.free(allocation.indices.clone());
+    }
+
+    /// Replace a mesh that's been remeshed, the new one can end up anywhere in the pool
+    pub fn replace<F>(&mut self, display: &F, old: &GeometryAllocation, verts: &[V], indices: &[u32]) -> GeometryAllocation
+    where F: Facade{
+        self.remove(old);
+        self.insert(display, verts, indices)
+    }
+
+    fn grow_vertices<F>(&mut self, display: &F, needed: usize)
+    where F: Facade{
+        let old = self.vertex_space.capacity();
+        let capacity = (old * 2).max(old + needed);
+        let buffer = VertexBuffer::empty_dynamic(display, capacity).unwrap();
+        if old > 0{
+            self.vertex_buffer.copy_to(buffer.slice(0..old).unwrap()).unwrap();
+        }
+        self.vertex_buffer = buffer;
+        self.vertex_space.grow(capacity);
+    }
+
+    fn grow_indices<F>(&mut self, display: &F, needed: usize)
+    where F: Facade{
+        let old = self.index_space.capacity();
+        let capacity = (old * 2).max(old + needed);
+        let buffer = IndexBuffer::empty_dynamic(display, PrimitiveType::TrianglesList, capacity).unwrap();
+        if old > 0{
+            self.index_buffer.copy_to(buffer.slice(0..old).unwrap()).unwrap();
+        }
+        self.index_buffer = buffer;
+        self.index_space.grow(capacity);
+    }
+}
+
+/// Where a chunk mesh drawn from the pool is in the world, one per draw command
+#[derive(Copy, Clone)]
+pub struct ChunkInstance{
+    pub chunk_offset: [f32; 3],
+}
+
+implement_vertex!(ChunkInstance, chunk_offset);
+
+/// Which vertex layout chunk meshes are uploaded in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VertexFormat{
+    /// PackedVoxelVertex, 8 bytes a vertex
+    Packed,
+    /// VoxelVertex, kept for drivers that have trouble with the packed shader
+    Full,
+}
+
+impl Default for VertexFormat{
+    fn default() -> Self{
+        VertexFormat::Packed
+    }
+}
+
+/// A geometry pool in one of the vertex formats
+pub enum ChunkPool{
+    Packed{pool: GeometryPool<PackedVoxelVertex>, block_size: f32},
+    Full(GeometryPool<VoxelVertex>),
+}
+
+impl ChunkPool{
+    pub fn new<F>(display: &F, format: VertexFormat, block_size: f32) -> Self
+    where F: Facade{
+        match format{
+            VertexFormat::Packed => ChunkPool::Packed{pool: GeometryPool::new(display, INITIAL_POOL_VERTICES, INITIAL_POOL_INDICES), block_size},
+            VertexFormat::Full => ChunkPool::Full(GeometryPool::new(display, INITIAL_POOL_VERTICES, INITIAL_POOL_INDICES)),
+        }
+    }
+
+    /// Size of a block in the world, the packed shader needs it to turn grid positions back into positions
+    pub fn block_size(&self) -> f32{
+        match self{
+            ChunkPool::Packed{block_size, ..} => *block_size,
+            ChunkPool::Full(_) => 1.0,
+        }
+    }
+
+    /// Upload a chunk mesh, packing it first when the pool is packed. A remeshed chunk's old space is freed first.
+    pub fn upload<F>(&mut self, display: &F, old: Option<&GeometryAllocation>, verts: &[VoxelVertex], indices: &[u32]) -> GeometryAllocation
+    where F: Facade{
+        match self{
+            ChunkPool::Packed{pool, block_size} => {
+                let packed: Vec<PackedVoxelVertex> = verts.iter().map(|vert| PackedVoxelVertex::pack(vert, *block_size)).collect();
+                match old{
+                    Some(old) => pool.replace(display, old, &packed, indices),
+                    None => pool.insert(display, &packed, indices),
+                }
+            }
+            ChunkPool::Full(pool) => match old{
+                Some(old) => pool.replace(display, old, verts, indices),
+                None => pool.insert(display, verts, indices),
+            },
+        }
+    }
+
+    /// Free a chunk mesh's space once nothing draws it
+    pub fn remove(&mut self, allocation: &GeometryAllocation){
+        match self{
+            ChunkPool::Packed{pool, ..} => pool.remove(allocation),
+            ChunkPool::Full(pool) => pool.remove(allocation),
+        }
+    }
+}
+
+/// The pool every chunk mesh is packed into, made by the mesher the first time it has something to upload
+#[derive(Default)]
+pub struct ChunkGeometry{
+    pub format: VertexFormat,
+    pub pool: Option<ChunkPool>,
+    /// Each entity's mesh in the pool, so it can be freed once the entity or its MeshRenderer is gone
+    allocations: HashMap<Entity, GeometryAllocation>,
+}
+
+impl ChunkGeometry{
+    pub fn new(format: VertexFormat) -> Self{
+        ChunkGeometry{
+            format,
+            pool: None,
+            allocations: HashMap::new(),
+        }
+    }
+
+    /// Upload an entity's chunk mesh, freeing the one it had before. The pool is made with block_size the first time.
+    pub fn upload<F>(&mut self, display: &F, block_size: f32, entity: Entity, verts: &[VoxelVertex], indices: &[u32]) -> GeometryAllocation
+    where F: Facade{
+        let format = self.format;
+        let pool = self.pool.get_or_insert_with(|| ChunkPool::new(display, format, block_size));
+        let allocation = pool.upload(display, self.allocations.get(&entity), verts, indices);
+        self.allocations.insert(entity, allocation.clone());
+        allocation
+    }
+
+    /// Free the meshes of entities drawn says no longer draw them, because the entity was deleted or its MeshRenderer removed
+    pub fn free_unused<D>(&mut self, drawn: D)
+    where D: Fn(Entity, &GeometryAllocation) -> bool{
+        let pool = &mut self.pool;
+        self.allocations.retain(|entity, allocation|{
+            let keep = drawn(*entity, allocation);
+            if let (false, Some(pool)) = (keep, pool.as_mut()){
+                pool.remove(allocation);
+            }
+            keep
+        });
+    }
+}
+
+unsafe impl Sync for ChunkGeometry{}
+unsafe impl Send for ChunkGeometry{}
+
+/// The per instance buffer and, when multi draw is used, the draw commands for drawing chunk meshes out of a pool.
+/// Keep one for each view drawn in a frame, its buffers are refilled every frame and only remade when they're too small.
+#[derive(Default)]
+pub struct ChunkBatch{
+    meshes: Vec<GeometryAllocation>,
+    instances: Option<VertexBuffer<ChunkInstance>>,
+    commands: Option<DrawCommandsIndicesBuffer>,
+    multidraw: bool,
+}
+
+impl ChunkBatch{
+    pub fn new() -> Self{
+        Self::default()
+    }
+
+    /// Fill the buffers with the meshes to draw next, growing them to the next power of two when they're too small.
+    /// Draw commands past the last mesh draw nothing.
+    pub fn fill(&mut self, display: &Display, meshes: &[(GeometryAllocation, ChunkInstance)], multidraw: bool){
+        self.meshes.clear();
+        self.meshes.extend(meshes.iter().map(|(allocation, _)| allocation.clone()));
+        self.multidraw = multidraw;
+        if meshes.is_empty(){
+            return;
+        }
+        let capacity = meshes.len().next_power_of_two();
+
+        let too_small = |len: Option<usize>| match len{
+            Some(len) => len < meshes.len(),
+            None => true,
+        };
+        if too_small(self.instances.as_ref().map(|buffer| buffer.len())){
+            self.instances = Some(VertexBuffer::empty_dynamic(display, capacity).unwrap());
+        }
+        let instances: Vec<ChunkInstance> = meshes.iter().map(|(_, instance)| *instance).collect();
+        self.instances.as_ref().unwrap().slice(0..meshes.len()).unwrap().write(&instances);
+
+        if multidraw{
+            if too_small(self.commands.as_ref().map(|buffer| buffer.len())){
+                self.commands = Some(DrawCommandsIndicesBuffer::empty_dynamic(display, capacity).unwrap());
+            }
+            let buffer = self.commands.as_ref().unwrap();
+            let empty = DrawCommandIndices{count: 0, instance_count: 0, first_index: 0, base_vertex: 0, base_instance: 0};
+            let mut commands: Vec<DrawCommandIndices> = meshes.iter().enumerate().map(|(i, (allocation, _))| allocation.draw_command(i as u32)).collect();
+            commands.resize(buffer.len(), empty);
+            buffer.write(&commands);
+        }
+    }
+
+    /// One multi draw indirect call when the batch was filled for multi draw, otherwise a draw per mesh out of the same buffers
+    pub fn draw<S, V, U>(&self, surface: &mut S, pool: &GeometryPool<V>, program: &Program, uniforms: &U, params: &DrawParameters) -> Result<(), DrawError>
+    where S: Surface, V: Vertex, U: uniforms::Uniforms{
+        let instances = match self.instances.as_ref(){
+            Some(instances) if !self.meshes.is_empty() => instances,
+            _ => return Ok(()),
+        };
+        if let (true, Some(commands)) = (self.multidraw, self.commands.as_ref()){
+            return surface.draw(
+                (&pool.vertex_buffer, instances.slice(0..self.meshes.len()).unwrap().per_instance().unwrap()),
+                commands.with_index_buffer(&pool.index_buffer),
+                program, uniforms, params);
+        }
+
+        //The vertex slice's start stands in for the base vertex
+        for (i, allocation) in self.meshes.iter().enumerate(){
+            surface.draw(
+                (pool.vertex_buffer.slice(allocation.vertices.clone()).unwrap(), instances.slice(i..i + 1).unwrap().per_instance().unwrap()),
+                pool.index_buffer.slice(allocation.indices.clone()).unwrap(),
+                program, uniforms, params)?;
+        }
+        Ok(())
+    }
+}
@@ -1,20 +1,54 @@
 use specs::prelude::*;
 use glium::*;
 use nalgebra as na;
-use std::sync::{Arc, Mutex};
+use std::collections::{HashMap, HashSet};
+use std::marker::PhantomData;
+use std::time::Instant;
 use glium::backend::Facade;
 use glium::index::PrimitiveType;
 use nalgebra::{Matrix4, Vector3, Vector2};
 use nalgebra_glm::perspective;
-use v_windowing::WindowDisplay;
+use v_windowing::{WindowDisplay, WindowInfo, ShadowQuality};
 use v_transform::*;
 pub use glium::*;
-use glium::texture::RawImage2d;
-use glium::texture::Texture2dArray;
+use glium::texture::{RawImage2d, MipmapsOption, UncompressedFloatFormat};
 use glium::uniforms::{MinifySamplerFilter, MagnifySamplerFilter};
+use glium::index::DrawCommandsIndicesBuffer;
 
+mod allocator;
+pub use allocator::*;
+mod geometry;
+pub use geometry::*;
+mod packed;
+pub use packed::*;
+mod sky;
+pub use sky::*;
+mod cascades;
+pub use cascades::*;
+mod shadows;
+pub use shadows::*;
+mod post;
+pub use post::*;
+mod texture_set;
+pub use texture_set::*;
+mod textures;
+pub use textures::*;
+mod zip;
+pub use zip::*;
+mod resources;
+pub use resources::*;
+mod preprocess;
+pub use preprocess::*;
+mod shaders;
+pub use shaders::*;
+mod viewport;
+pub use viewport::*;
 
-#[derive(Copy, Clone)]
+
+/// Texture files loaded into the voxel texture array, a vertex's tex_index is a position in this list
+pub const TEXTURE_FILES: &[&str] = &["dirt.png", "grass.png", "rock.png"];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct VoxelVertex{
     pub position: [f32; 3],
     pub tex_coord: [f32; 2],
@@ -34,29 +68,31 @@ impl VoxelVertex{
 }
 
 implement_vertex!(VoxelVertex, position, tex_coord, tex_index, lighting);
+implement_vertex!(PackedVoxelVertex, data);
 
-pub struct MeshBuffer<V>
-    where V: Vertex{
-    pub vertex_buffer: VertexBuffer<V>,
-    pub index_buffer: IndexBuffer<u32>
+pub struct MeshRenderer<V>
+    where V: Vertex + Send + Sync{
+    /// Where the mesh is in the ChunkGeometry pool
+    pub allocation: GeometryAllocation,
+    /// Bounds of the mesh before its transform, used to skip it when it's off screen
+    pub bounds: Aabb,
+    /// Set by occlusion culling, hidden meshes aren't drawn
+    pub visible: bool,
+    vertex: PhantomData<V>,
 }
 
-impl<V> MeshBuffer<V>
-    where V: Vertex{
-    pub fn new<F>(display: &F, verts: Vec<V>, indicies: Vec<u32>) -> Self
-    where F: Facade{
-        Self{
-            vertex_buffer: VertexBuffer::new(display, &verts).unwrap(),
-            index_buffer: IndexBuffer::new(display, PrimitiveType::TrianglesList, &indicies).unwrap(),
+impl<V> MeshRenderer<V>
+    where V: Vertex + Send + Sync{
+    pub fn new(allocation: GeometryAllocation, bounds: Aabb) -> Self{
+        MeshRenderer{
+            allocation,
+            bounds,
+            visible: true,
+            vertex: PhantomData,
         }
     }
 }
 
-pub struct MeshRenderer<V>
-    where V: Vertex + Send + Sync{
-    pub mesh: Arc<Mutex<MeshBuffer<V>>>,
-}
-
 unsafe impl<V> Sync for MeshRenderer<V>
     where V: Vertex + Send + Sync{}
 unsafe impl<V> Send for MeshRenderer<V>
@@ -71,15 +107,58 @@ pub struct Camera{
     pub fov: f32,
     pub znear: f32,
     pub zfar: f32,
+    /// Where on the screen, or the render target, the camera draws
+    pub viewport: Viewport,
+    /// Draw into a texture instead of the screen
+    pub target: Option<RenderTarget>,
+    /// Cameras are drawn lowest priority first, so higher ones end up over lower ones where their viewports overlap
+    pub priority: i32,
+    pub clear: CameraClear,
 }
 
 impl Camera{
+    /// A camera covering the whole screen
+    pub fn new(fov: f32, znear: f32, zfar: f32) -> Self{
+        Camera{
+            fov,
+            znear,
+            zfar,
+            viewport: Viewport::FULL,
+            target: None,
+            priority: 0,
+            clear: CameraClear::Sky,
+        }
+    }
+
+    pub fn with_viewport(mut self, viewport: Viewport) -> Self{
+        self.viewport = viewport;
+        self
+    }
+
+    pub fn with_target(mut self, target: RenderTarget) -> Self{
+        self.target = Some(target);
+        self
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self{
+        self.priority = priority;
+        self
+    }
+
+    pub fn with_clear(mut self, clear: CameraClear) -> Self{
+        self.clear = clear;
+        self
+    }
 
     pub fn perspective_matrix<F: Surface>(&self, target: &F) -> Matrix4<f32> {
         let (width, height) = target.get_dimensions();
-        let aspect_ratio = height as f32 / width as f32;
+        self.projection_matrix(width, height)
+    }
 
-        perspective(1.0/aspect_ratio, self.fov, self.znear, self.zfar)
+    /// Projection for a viewport of the given size in pixels
+    pub fn projection_matrix(&self, width: u32, height: u32) -> Matrix4<f32>{
+        let aspect_ratio = width.max(1) as f32 / height.max(1) as f32;
+        perspective(aspect_ratio, self.fov, self.znear, self.zfar)
     }
 }
 
@@ -87,40 +166,172 @@ impl Component for Camera{
     type Storage = HashMapStorage<Self>;
 }
 
+/// Textures cameras with a RenderTarget draw into, by name. They hold the finished, post processed picture.
+/// A texture is let go of once no camera draws into it.
+#[derive(Default)]
+pub struct RenderTextures{
+    textures: HashMap<String, Texture2d>,
+    /// Names drawn into since the last end_frame
+    used: HashSet<String>,
+}
+
+impl RenderTextures{
+    pub fn get(&self, name: &str) -> Option<&Texture2d>{
+        self.textures.get(name)
+    }
+
+    /// Names of the textures cameras have drawn into
+    pub fn names(&self) -> Vec<&str>{
+        self.textures.keys().map(|name| name.as_str()).collect()
+    }
+
+    /// The texture for a target, made or remade if it isn't there at the target's size
+    fn texture(&mut self, display: &Display, target: &RenderTarget) -> &Texture2d{
+        let resized = match self.textures.get(&target.name){
+            Some(texture) => texture.dimensions() != (target.width, target.height),
+            None => true,
+        };
+        if resized{
+            let texture = Texture2d::empty_with_format(display, UncompressedFloatFormat::U8U8U8U8, MipmapsOption::NoMipmap, target.width, target.height).unwrap();
+            texture.as_surface().clear_color(0.0, 0.0, 0.0, 1.0);
+            self.textures.insert(target.name.clone(), texture);
+        }
+        self.used.insert(target.name.clone());
+        &self.textures[&target.name]
+    }
+
+    /// Let go of the textures no camera drew into this frame
+    fn end_frame(&mut self){
+        let used = &self.used;
+        self.textures.retain(|name, _| used.contains(name));
+        self.used.clear();
+    }
+}
+
+unsafe impl Sync for RenderTextures{}
+unsafe impl Send for RenderTextures{}
+
+/// How often shader files are checked for changes while hot reloading, in seconds
+pub const SHADER_RELOAD_INTERVAL: f32 = 0.5;
+
 pub struct VoxelRenderingSystem{
-    program: Program,
-    textures: Texture2dArray
+    program: ShaderProgram,
+    /// For pools of PackedVoxelVertex
+    packed_program: ShaderProgram,
+    sky_program: ShaderProgram,
+    shadows: ShadowMaps,
+    post: PostPipeline,
+    textures: VoxelTextures,
+    /// Animated textures are timed from here
+    started: Instant,
+    /// Whether chunks are drawn with one multi draw indirect call, falls back to a draw per chunk
+    multidraw: bool,
+    /// One for each camera
+    batches: Vec<ChunkBatch>,
+    /// Whether shaders are recompiled when their files change
+    hot_reload: bool,
+    last_reload_check: Instant,
 }
 
 impl VoxelRenderingSystem{
-    pub fn new(display: &Display) -> Self{
+    /// Shaders and textures come from the resource packs
+    pub fn new(display: &Display, resources: &ResourceManager) -> Self{
+        Self::with_post_process(display, resources, PostPipeline::standard(display, resources))
+    }
+
+    /// Draw the scene through a game's own chain of post process passes
+    pub fn with_post_process(display: &Display, resources: &ResourceManager, post: PostPipeline) -> Self{
         VoxelRenderingSystem{
-            program:  glium::Program::from_source(display, include_str!("../../src/shaders/base_vertex.glsl"), include_str!("../../src/shaders/base_frag.glsl"), None).unwrap(),
-            textures: Texture2dArray::new(display, vec!["dirt.png", "grass.png", "rock.png"].iter().map(|x| Self::get_raw_image(*x)).collect()).unwrap(),
+            program: ShaderProgram::load(display, resources, "base_vertex.glsl", "base_frag.glsl"),
+            packed_program: ShaderProgram::load(display, resources, "packed_vertex.glsl", "base_frag.glsl"),
+            sky_program: ShaderProgram::load(display, resources, "sky_vertex.glsl", "sky_frag.glsl"),
+            shadows: ShadowMaps::new(display, resources, ShadowQuality::Off),
+            post,
+            textures: Self::load_textures(display, resources),
+            started: Instant::now(),
+            multidraw: DrawCommandsIndicesBuffer::is_supported(display),
+            batches: Vec::new(),
+            hot_reload: false,
+            last_reload_check: Instant::now(),
         }
     }
 
-    fn get_raw_image(file_name: &str) -> RawImage2d<u8>{
-        let tex = image::open(format!("{}{}", "./assets/", file_name)).unwrap().to_rgba();
-        let tex_dimensions = tex.dimensions();
-        glium::texture::RawImage2d::from_raw_rgba_reversed(&tex.into_raw(), tex_dimensions)
+    /// Recompile shaders while the game runs when their files in the resource packs change.
+    /// A shader that doesn't compile is logged and the one before it is kept.
+    pub fn with_hot_reload(mut self, hot_reload: bool) -> Self{
+        self.hot_reload = hot_reload;
+        self
+    }
+
+    /// The texture array chunks are drawn with, block definitions look their textures' layers up in it
+    pub fn textures(&self) -> &VoxelTextures{
+        &self.textures
+    }
+
+    /// Draw a batch of chunk meshes with the program for the pool's vertex format
+    fn draw_chunks<S, U>(&self, surface: &mut S, pool: &ChunkPool, batch: &ChunkBatch, uniforms: &U, params: &DrawParameters) -> Result<(), DrawError>
+    where S: Surface, U: uniforms::Uniforms{
+        match pool{
+            ChunkPool::Packed{pool, ..} => batch.draw(surface, pool, &self.packed_program, uniforms, params),
+            ChunkPool::Full(pool) => batch.draw(surface, pool, &self.program, uniforms, params),
+        }
+    }
+
+    /// Recompile every shader whose files changed, at most once every SHADER_RELOAD_INTERVAL
+    fn reload_shaders(&mut self, display: &Display, resources: &ResourceManager){
+        if !self.hot_reload || self.last_reload_check.elapsed().as_secs_f32() < SHADER_RELOAD_INTERVAL{
+            return;
+        }
+        self.last_reload_check = Instant::now();
+        self.program.reload_if_changed(display, resources);
+        self.packed_program.reload_if_changed(display, resources);
+        self.sky_program.reload_if_changed(display, resources);
+        self.shadows.reload_shaders(display, resources);
+        self.post.reload_shaders(display, resources);
+    }
+
+    /// Textures from the resource packs, checks stand in for them if they can't be loaded
+    fn load_textures(display: &Display, resources: &ResourceManager) -> VoxelTextures{
+        match TextureArrayBuilder::new().build(display, resources){
+            Ok(textures) => textures,
+            Err(error) => {
+                println!("Couldn't load textures: {}", error);
+                VoxelTextures::placeholder(display, TEXTURE_FILES)
+            },
+        }
     }
 }
 
 impl<'a> System<'a> for VoxelRenderingSystem{
     type SystemData = (
     Write<'a, WindowDisplay>,
+    Read<'a, ChunkGeometry>,
+    Read<'a, TimeOfDay>,
+    Read<'a, Fog>,
+    Read<'a, WindowInfo>,
+    Read<'a, PostSettings>,
+    Read<'a, ResourceManager>,
+    Write<'a, RenderTextures>,
     ReadStorage<'a, MeshRenderer<VoxelVertex>>,
     ReadStorage<'a, TransformMatrix>,
     ReadStorage<'a, Camera>);
 
-    fn run(&mut self, (mut window, voxel_meshes, transforms, cameras): Self::SystemData){
-        let display = window.as_ref().unwrap().lock().unwrap();
+    fn run(&mut self, (mut window, geometry, time_of_day, fog, window_info, post_settings, resources, mut render_textures, voxel_meshes, transforms, cameras): Self::SystemData){
+        let display = match window.as_ref(){
+            Some(display) => display.lock().unwrap(),
+            None => return,
+        };
+        self.reload_shaders(&display, &resources);
+        if window_info.shadows() != self.shadows.quality(){
+            self.shadows.set_quality(&display, window_info.shadows());
+        }
         let mut frame = display.draw();
+        let lighting = time_of_day.lighting();
+        let array = |vector: Vector3<f32>| [vector[0], vector[1], vector[2]];
 
-
-        //Clear the frame color and depth
-        frame.clear_color_and_depth((0.0, 0.0, 0.0, 1.0), 1.0);
+        //Parts of the screen no camera covers are left black
+        frame.clear_color(0.0, 0.0, 0.0, 1.0);
+        let screen_size = frame.get_dimensions();
 
         //Create the draw params
         let params = glium::DrawParameters {
@@ -133,17 +344,131 @@ impl<'a> System<'a> for VoxelRenderingSystem{
             .. Default::default()
         };
 
-        //Draw the meshes
-        for(camera, cam_transform) in (&cameras, &transforms).join() {
-            let vp = camera.perspective_matrix(&frame) * cam_transform.view_matrix();
-            let sampler = self.textures.sampled().minify_filter(MinifySamplerFilter::Nearest).magnify_filter(MagnifySamplerFilter::Nearest);
-            for (voxel_mesh, transform) in (&voxel_meshes, &transforms).join() {
-                let mvp = (vp * transform.matrix());
-                let mesh_buffer = voxel_mesh.mesh.lock().unwrap();
-                frame.draw(&mesh_buffer.vertex_buffer, &mesh_buffer.index_buffer, &self.program, &uniform!(mvp: mvp.as_ref().clone(), tex: sampler), &params);
+        //Lowest priority first so higher cameras end up on top
+        let mut views: Vec<(&Camera, &TransformMatrix)> = (&cameras, &transforms).join().collect();
+        views.sort_by_key(|(camera, _)| camera.priority);
+        let viewport_of = |camera: &Camera| {
+            let (target_width, target_height) = camera.target.as_ref().map_or(screen_size, |target| (target.width, target.height));
+            camera.viewport.pixels(target_width, target_height)
+        };
+
+        //Every chunk with a mesh, whether each camera sees it is worked out below. Chunks hidden from a camera can still cast shadows onto ones it sees.
+        let mut casters: Vec<ShadowCaster> = Vec::new();
+        let mut visible = Vec::new();
+        for (voxel_mesh, transform) in (&voxel_meshes, &transforms).join(){
+            if voxel_mesh.allocation.is_empty(){
+                continue;
+            }
+            let offset = transform.matrix().column(3);
+            let instance = ChunkInstance{chunk_offset: [offset[0], offset[1], offset[2]]};
+            casters.push((voxel_mesh.allocation.clone(), instance, voxel_mesh.bounds.transformed(transform.matrix())));
+            visible.push(voxel_mesh.visible);
+        }
+
+        //The cascades are fitted to the highest priority camera on the screen and shared by every camera, so extra views don't pay for shadows again
+        let shadow_camera = views.iter().rev().find(|(camera, _)| camera.target.is_none()).or_else(|| views.last());
+        if let (Some(pool), Some((camera, cam_transform))) = (geometry.pool.as_ref(), shadow_camera){
+            let viewport = viewport_of(camera);
+            let shadow_view = ShadowView{
+                camera_matrix: *cam_transform.matrix(),
+                fov: camera.fov,
+                aspect_ratio: viewport.width.max(1) as f32 / viewport.height.max(1) as f32,
+                near: camera.znear,
+                light_direction: lighting.light_direction,
+            };
+            self.shadows.render(&display, pool, &casters, &shadow_view, self.multidraw);
+        }
+
+        //Each camera's view goes into an HDR target the size of its viewport, post processing takes it to the screen or its texture
+        while self.batches.len() < views.len(){
+            self.batches.push(ChunkBatch::new());
+        }
+        for (view, (camera, cam_transform)) in views.into_iter().enumerate(){
+            let viewport = viewport_of(camera);
+            let (width, height) = (viewport.width, viewport.height);
+            self.post.prepare(&display, width, height);
+
+            let projection = camera.projection_matrix(width, height);
+            let vp = projection * cam_transform.view_matrix();
+            let frustum = Frustum::from_matrix(&vp);
+            let meshes: Vec<_> = casters.iter().zip(visible.iter())
+                .filter(|((_, _, bounds), visible)| **visible && frustum.intersects(bounds))
+                .map(|((allocation, instance, _), _)| (allocation.clone(), *instance))
+                .collect();
+
+            let mut scene = self.post.scene_target(&display, width, height);
+            match camera.clear{
+                CameraClear::Sky => {
+                    scene.clear_color_and_depth((lighting.horizon_color[0], lighting.horizon_color[1], lighting.horizon_color[2], 1.0), 1.0);
+
+                    //Sky first, behind everything and without touching the depth buffer
+                    let inverse_view_projection = vp.try_inverse().unwrap_or_else(Matrix4::identity);
+                    let sky_uniforms = uniform!(
+                        inverse_view_projection: *inverse_view_projection.as_ref(),
+                        zenith_color: array(lighting.zenith_color),
+                        horizon_color: array(lighting.horizon_color),
+                        sun_direction: array(lighting.sun_direction),
+                        moon_direction: array(lighting.moon_direction));
+                    if let Err(error) = scene.draw(vertex::EmptyVertexAttributes{len: 3}, index::NoIndices(PrimitiveType::TrianglesList), &self.sky_program, &sky_uniforms, &Default::default()){
+                        println!("Couldn't draw the sky: {:?}", error);
+                    }
+                },
+                CameraClear::Color(color) => scene.clear_color_and_depth((color[0], color[1], color[2], color[3]), 1.0),
+            }
+
+            if let Some(pool) = geometry.pool.as_ref(){
+                let splits = self.shadows.splits();
+                let camera_forward = -cam_transform.matrix().column(2).xyz().normalize();
+                let camera_position = cam_transform.matrix().column(3);
+                let uniforms = uniform!(
+                    view_projection: *vp.as_ref(),
+                    block_size: pool.block_size(),
+                    tex: self.textures.sampler(),
+                    animations: self.textures.animations(),
+                    animation_time: self.started.elapsed().as_secs_f32() % 3600.0,
+                    light_direction: array(lighting.light_direction),
+                    light_color: array(lighting.light_color),
+                    ambient_color: array(lighting.ambient_color),
+                    camera_position: [camera_position[0], camera_position[1], camera_position[2]],
+                    fog_color: array(lighting.horizon_color),
+                    fog_start: fog.start,
+                    fog_end: fog.end,
+                    cascade_count: self.shadows.cascades() as i32,
+                    cascade_splits: splits,
+                    camera_forward: array(camera_forward),
+                    light_view_projection0: self.shadows.view_projection(0),
+                    light_view_projection1: self.shadows.view_projection(1),
+                    light_view_projection2: self.shadows.view_projection(2),
+                    light_view_projection3: self.shadows.view_projection(3),
+                    shadow_map0: self.shadows.sampler(0),
+                    shadow_map1: self.shadows.sampler(1),
+                    shadow_map2: self.shadows.sampler(2),
+                    shadow_map3: self.shadows.sampler(3));
+                let mut batch = std::mem::take(&mut self.batches[view]);
+                batch.fill(&display, &meshes, self.multidraw);
+                if let Err(error) = self.draw_chunks(&mut scene, pool, &batch, &uniforms, &params){
+                    if self.multidraw{
+                        println!("Multi draw failed, drawing chunks one at a time: {:?}", error);
+                        self.multidraw = false;
+                        batch.fill(&display, &meshes, false);
+                        self.draw_chunks(&mut scene, pool, &batch, &uniforms, &params).ok();
+                    }
+                }
+                self.batches[view] = batch;
+            }
+
+            drop(scene);
+            match camera.target.as_ref(){
+                Some(target) => {
+                    let texture = render_textures.texture(&display, target);
+                    self.post.draw(&display, &texture.as_surface(), &viewport, &post_settings, projection);
+                },
+                None => self.post.draw(&display, &frame, &viewport, &post_settings, projection),
             }
         }
 
+        self.post.end_frame();
+        render_textures.end_frame();
         frame.finish();
     }
 }
\ No newline at end of file
@@ -0,0 +1,136 @@
+use crate::*;
+
+/// Texture coordinates of a face's four corners, in the order ChunkMesherSystem winds them
+pub const CORNER_TEX_COORDS: [[f32; 2]; 4] = [[1.0, 1.0], [1.0, 0.0], [0.0, 0.0], [0.0, 1.0]];
+
+const POSITION_BITS: u32 = 6;
+const POSITION_MASK: u32 = (1 << POSITION_BITS) - 1;
+
+/// A chunk mesh vertex in 8 bytes instead of VoxelVertex's 32, unpacked by packed_vertex.glsl.
+///
+/// The first word holds the corner's position on the chunk's block grid in bits 0-17, 6 bits for each of x, y and z,
+/// then the face in bits 18-20 and which of the face's corners it is in bits 21-22.
+/// The second word holds the texture layer in bits 0-15, the rest of it is free.
+/// A grid position of 0 is half a block before the chunk's origin since blocks are centred on their coordinates.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PackedVoxelVertex{
+    pub data: [u32; 2],
+}
+
+impl PackedVoxelVertex{
+    pub fn new(grid_position: [u32; 3], face: u32, corner: u32, texture: u32) -> Self{
+        let position = (grid_position[0] & POSITION_MASK)
+            | (grid_position[1] & POSITION_MASK) << POSITION_BITS
+            | (grid_position[2] & POSITION_MASK) << (POSITION_BITS * 2);
+        PackedVoxelVertex{
+            data: [
+                position | (face & 7) << 18 | (corner & 3) << 21,
+                texture & 0xffff,
+            ],
+        }
+    }
+
+    pub fn grid_position(&self) -> [u32; 3]{
+        [
+            self.data[0] & POSITION_MASK,
+            (self.data[0] >> POSITION_BITS) & POSITION_MASK,
+            (self.data[0] >> (POSITION_BITS * 2)) & POSITION_MASK,
+        ]
+    }
+
+    pub fn face(&self) -> u32{
+        (self.data[0] >> 18) & 7
+    }
+
+    pub fn corner(&self) -> u32{
+        (self.data[0] >> 21) & 3
+    }
+
+    pub fn texture(&self) -> u32{
+        self.data[1] & 0xffff
+    }
+
+    /// Pack a vertex with a chunk local position, lighting is taken as the face.
+    /// The position has to be on the chunk's block grid and the tex_coord one of CORNER_TEX_COORDS.
+    pub fn pack(vertex: &VoxelVertex, block_size: f32) -> Self{
+        let mut grid_position = [0; 3];
+        for (grid, position) in grid_position.iter_mut().zip(vertex.position.iter()){
+            let cell = (position / block_size + 0.5).round();
+            debug_assert!(cell >= 0.0 && cell <= POSITION_MASK as f32, "{:?} is outside the chunk's grid", vertex.position);
+            *grid = cell.max(0.0) as u32;
+        }
+        let corner = CORNER_TEX_COORDS.iter().position(|tex_coord| *tex_coord == vertex.tex_coord);
+        debug_assert!(corner.is_some(), "{:?} isn't a face corner", vertex.tex_coord);
+        Self::new(grid_position, vertex.lighting, corner.unwrap_or(0) as u32, vertex.tex_index)
+    }
+
+    /// The vertex this was packed from, the same maths as packed_vertex.glsl
+    pub fn unpack(&self, block_size: f32) -> VoxelVertex{
+        let grid_position = self.grid_position();
+        let mut position = [0.0; 3];
+        for (position, grid) in position.iter_mut().zip(grid_position.iter()){
+            *position = (*grid as f32 - 0.5) * block_size;
+        }
+        VoxelVertex{
+            position,
+            tex_coord: CORNER_TEX_COORDS[self.corner() as usize],
+            tex_index: self.texture(),
+            lighting: self.face(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn fields_round_trip(){
+        let vertex = PackedVoxelVertex::new([32, 0, 17], 5, 3, 1234);
+        assert_eq!(vertex.grid_position(), [32, 0, 17]);
+        assert_eq!((vertex.face(), vertex.corner(), vertex.texture()), (5, 3, 1234));
+
+        //Out of range values don't spill into their neighbours
+        let vertex = PackedVoxelVertex::new([64, 1, 0], 8, 4, 0x10000);
+        assert_eq!(vertex.grid_position(), [0, 1, 0]);
+        assert_eq!((vertex.face(), vertex.corner(), vertex.texture()), (0, 0, 0));
+    }
+
+    #[test]
+    fn vertices_round_trip(){
+        let block_size = 0.5;
+        for face in 0..6{
+            for (corner, tex_coord) in CORNER_TEX_COORDS.iter().enumerate(){
+                //Corners of the first and last blocks in a chunk
+                for block in [0.0, 31.0].iter(){
+                    let offset = if corner % 2 == 0 {0.5} else {-0.5};
+                    let vertex = VoxelVertex{
+                        position: [(block + offset) * block_size, (block - offset) * block_size, (block + offset) * block_size],
+                        tex_coord: *tex_coord,
+                        tex_index: corner as u32 + 1,
+                        lighting: face,
+                    };
+                    let packed = PackedVoxelVertex::pack(&vertex, block_size);
+                    assert_eq!(packed.unpack(block_size), vertex);
+                    assert_eq!(packed.corner(), corner as u32);
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    #[cfg(debug_assertions)]
+    fn positions_outside_the_grid_are_caught(){
+        let vertex = VoxelVertex{position: [64.0, 0.0, 0.0], tex_coord: CORNER_TEX_COORDS[0], tex_index: 0, lighting: 0};
+        PackedVoxelVertex::pack(&vertex, 1.0);
+    }
+
+    #[test]
+    #[should_panic]
+    #[cfg(debug_assertions)]
+    fn unknown_corners_are_caught(){
+        let vertex = VoxelVertex{position: [0.0; 3], tex_coord: [0.5, 0.5], tex_index: 0, lighting: 0};
+        PackedVoxelVertex::pack(&vertex, 1.0);
+    }
+}
@@ -0,0 +1,320 @@
+use crate::*;
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::{DepthTexture2d, DepthFormat, MipmapsOption, UncompressedFloatFormat};
+
+/// Knobs for the standard post process passes, read by the renderer every frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostSettings{
+    /// Scene brightness is scaled by this before tonemapping
+    pub exposure: f32,
+    pub gamma: f32,
+    pub fxaa: bool,
+    pub ssao: bool,
+    /// How far around a point ambient occlusion looks for things blocking it, in world units
+    pub ssao_radius: f32,
+    /// 0 turns ambient occlusion off, 1 darkens fully occluded points to black
+    pub ssao_strength: f32,
+    /// Set by the game while the camera is in water to tint the view
+    pub underwater: bool,
+    /// Colour distant things fade into underwater, in linear colour
+    pub underwater_tint: [f32; 3],
+    /// How quickly things fade into the tint with distance
+    pub underwater_density: f32,
+}
+
+impl Default for PostSettings{
+    fn default() -> Self{
+        PostSettings{
+            exposure: 1.0,
+            gamma: 2.2,
+            fxaa: true,
+            ssao: true,
+            ssao_radius: 1.0,
+            ssao_strength: 1.0,
+            underwater: false,
+            underwater_tint: [0.05, 0.25, 0.35],
+            underwater_density: 0.08,
+        }
+    }
+}
+
+/// What a post process pass draws from
+pub struct PostInput<'a>{
+    /// The previous pass's output, the scene for the first pass. HDR until the tonemap pass.
+    pub color: &'a Texture2d,
+    /// The scene's depth buffer
+    pub depth: &'a DepthTexture2d,
+    pub settings: &'a PostSettings,
+    /// The camera's projection, for getting view space positions back from depth
+    pub projection: Matrix4<f32>,
+}
+
+/// One step of the post process chain. Passes draw the whole screen into target from the previous pass's output.
+pub trait PostPass{
+    /// Used to find passes in a PostPipeline
+    fn name(&self) -> &str;
+
+    /// Disabled passes are skipped this frame
+    fn enabled(&self, _settings: &PostSettings) -> bool{
+        true
+    }
+
+    fn draw(&self, input: &PostInput, target: &mut SimpleFrameBuffer) -> Result<(), DrawError>;
+
+    /// Recompile any shaders whose files changed in the resource packs
+    fn reload_shaders(&mut self, _display: &Display, _resources: &ResourceManager){}
+}
+
+/// A pass that draws a full screen triangle with a fragment shader from the resource packs.
+/// The shader gets o_uv and can use any of the uniforms color, depth, texel_size, projection, inverse_projection,
+/// exposure, gamma, ssao_radius, ssao_strength, tint_color and tint_density.
+pub struct ShaderPass{
+    name: String,
+    program: ShaderProgram,
+    enabled: fn(&PostSettings) -> bool,
+}
+
+impl ShaderPass{
+    /// fragment_shader is a file in the packs' shader directory, like "post_fxaa.glsl"
+    pub fn new(display: &Display, resources: &ResourceManager, name: &str, fragment_shader: &str) -> Self{
+        ShaderPass{
+            name: name.to_string(),
+            program: ShaderProgram::load(display, resources, "post_vertex.glsl", fragment_shader),
+            enabled: |_| true,
+        }
+    }
+
+    /// Only run the pass while enabled returns true
+    pub fn with_toggle(mut self, enabled: fn(&PostSettings) -> bool) -> Self{
+        self.enabled = enabled;
+        self
+    }
+}
+
+impl PostPass for ShaderPass{
+    fn name(&self) -> &str{
+        &self.name
+    }
+
+    fn enabled(&self, settings: &PostSettings) -> bool{
+        (self.enabled)(settings)
+    }
+
+    fn draw(&self, input: &PostInput, target: &mut SimpleFrameBuffer) -> Result<(), DrawError>{
+        let settings = input.settings;
+        let inverse_projection = input.projection.try_inverse().unwrap_or_else(Matrix4::identity);
+        let uniforms = uniform!(
+            color: input.color.sampled()
+                .minify_filter(MinifySamplerFilter::Linear)
+                .magnify_filter(MagnifySamplerFilter::Linear)
+                .wrap_function(uniforms::SamplerWrapFunction::Clamp),
+            depth: input.depth.sampled()
+                .minify_filter(MinifySamplerFilter::Nearest)
+                .magnify_filter(MagnifySamplerFilter::Nearest)
+                .wrap_function(uniforms::SamplerWrapFunction::Clamp),
+            texel_size: [1.0 / input.color.width() as f32, 1.0 / input.color.height() as f32],
+            projection: *input.projection.as_ref(),
+            inverse_projection: *inverse_projection.as_ref(),
+            exposure: settings.exposure,
+            gamma: settings.gamma,
+            ssao_radius: settings.ssao_radius,
+            ssao_strength: settings.ssao_strength,
+            tint_color: settings.underwater_tint,
+            tint_density: settings.underwater_density);
+        target.draw(vertex::EmptyVertexAttributes{len: 3}, index::NoIndices(PrimitiveType::TrianglesList), &self.program, &uniforms, &Default::default())
+    }
+
+    fn reload_shaders(&mut self, display: &Display, resources: &ResourceManager){
+        self.program.reload_if_changed(display, resources);
+    }
+}
+
+/// Off screen targets the scene is drawn into, the two colour textures take turns being read and drawn to by passes
+struct PostTargets{
+    color: [Texture2d; 2],
+    depth: DepthTexture2d,
+    /// Whether a camera has been drawn at this size since the last end_frame
+    used: bool,
+}
+
+impl PostTargets{
+    fn new(display: &Display, width: u32, height: u32) -> Self{
+        let color = || Texture2d::empty_with_format(display, UncompressedFloatFormat::F16F16F16F16, MipmapsOption::NoMipmap, width, height).unwrap();
+        PostTargets{
+            color: [color(), color()],
+            depth: DepthTexture2d::empty_with_format(display, DepthFormat::F32, MipmapsOption::NoMipmap, width, height).unwrap(),
+            used: true,
+        }
+    }
+
+    fn size(&self) -> (u32, u32){
+        (self.depth.width(), self.depth.height())
+    }
+}
+
+/// The scene is drawn into an HDR target then through each pass in order before landing on the screen.
+/// Games can add their own passes anywhere in the chain, the standard ones are called
+/// ssao, underwater, tonemap, gamma and fxaa.
+/// Every camera is drawn and post processed on its own, in targets the size of its viewport.
+pub struct PostPipeline{
+    passes: Vec<Box<dyn PostPass>>,
+    /// One set for each viewport size drawn this frame
+    targets: Vec<PostTargets>,
+}
+
+impl PostPipeline{
+    /// A pipeline without any passes, the scene is copied straight to the screen
+    pub fn new() -> Self{
+        PostPipeline{
+            passes: Vec::new(),
+            targets: Vec::new(),
+        }
+    }
+
+    /// Ambient occlusion and the underwater tint on the HDR scene, then tonemapping, gamma and FXAA
+    pub fn standard(display: &Display, resources: &ResourceManager) -> Self{
+        let pass = |name, shader| ShaderPass::new(display, resources, name, shader);
+        let mut pipeline = Self::new();
+        pipeline.push(pass("ssao", "post_ssao.glsl").with_toggle(|settings| settings.ssao && settings.ssao_strength > 0.0));
+        pipeline.push(pass("underwater", "post_underwater.glsl").with_toggle(|settings| settings.underwater));
+        pipeline.push(pass("tonemap", "post_tonemap.glsl"));
+        pipeline.push(pass("gamma", "post_gamma.glsl"));
+        pipeline.push(pass("fxaa", "post_fxaa.glsl").with_toggle(|settings| settings.fxaa));
+        pipeline
+    }
+
+    /// Add a pass to the end of the chain
+    pub fn push<P: PostPass + 'static>(&mut self, pass: P){
+        self.passes.push(Box::new(pass));
+    }
+
+    /// Add a pass just before the one called name, or at the end if there isn't one
+    pub fn insert_before<P: PostPass + 'static>(&mut self, name: &str, pass: P){
+        let index = self.position(name).unwrap_or(self.passes.len());
+        self.passes.insert(index, Box::new(pass));
+    }
+
+    /// Add a pass just after the one called name, or at the end if there isn't one
+    pub fn insert_after<P: PostPass + 'static>(&mut self, name: &str, pass: P){
+        let index = self.position(name).map(|index| index + 1).unwrap_or(self.passes.len());
+        self.passes.insert(index, Box::new(pass));
+    }
+
+    /// Take a pass out of the chain
+    pub fn remove(&mut self, name: &str) -> Option<Box<dyn PostPass>>{
+        self.position(name).map(|index| self.passes.remove(index))
+    }
+
+    /// Names of the passes in the order they run
+    pub fn names(&self) -> Vec<&str>{
+        self.passes.iter().map(|pass| pass.name()).collect()
+    }
+
+    /// Recompile the passes' shaders that changed in the resource packs
+    pub fn reload_shaders(&mut self, display: &Display, resources: &ResourceManager){
+        for pass in self.passes.iter_mut(){
+            pass.reload_shaders(display, resources);
+        }
+    }
+
+    fn position(&self, name: &str) -> Option<usize>{
+        self.passes.iter().position(|pass| pass.name() == name)
+    }
+
+    fn targets(&self, width: u32, height: u32) -> Option<&PostTargets>{
+        self.targets.iter().find(|targets| targets.size() == (width.max(1), height.max(1)))
+    }
+
+    /// Make sure there are targets for a camera's viewport size, call it before scene_target each frame
+    pub fn prepare(&mut self, display: &Display, width: u32, height: u32){
+        let size = (width.max(1), height.max(1));
+        match self.targets.iter_mut().find(|targets| targets.size() == size){
+            Some(targets) => targets.used = true,
+            None => self.targets.push(PostTargets::new(display, size.0, size.1)),
+        }
+    }
+
+    /// Let go of the targets for sizes no camera drew at this frame, like the old size after the window is resized
+    pub fn end_frame(&mut self){
+        self.targets.retain(|targets| targets.used);
+        for targets in self.targets.iter_mut(){
+            targets.used = false;
+        }
+    }
+
+    /// Framebuffer a camera's view of the scene is drawn into, call prepare with its size first
+    pub fn scene_target<'a>(&'a self, display: &Display, width: u32, height: u32) -> SimpleFrameBuffer<'a>{
+        let targets = self.targets(width, height).expect("Post process targets are made by prepare");
+        SimpleFrameBuffer::with_depth_buffer(display, &targets.color[0], &targets.depth).unwrap()
+    }
+
+    /// Run the enabled passes over the scene drawn into the target the size of viewport, then copy the result into viewport on output
+    pub fn draw<S: Surface>(&self, display: &Display, output: &S, viewport: &Rect, settings: &PostSettings, projection: Matrix4<f32>){
+        let targets = match self.targets(viewport.width, viewport.height){
+            Some(targets) => targets,
+            None => return,
+        };
+        let mut source = 0;
+        for pass in self.passes.iter().filter(|pass| pass.enabled(settings)){
+            let destination = 1 - source;
+            let mut target = SimpleFrameBuffer::new(display, &targets.color[destination]).unwrap();
+            let input = PostInput{
+                color: &targets.color[source],
+                depth: &targets.depth,
+                settings,
+                projection,
+            };
+            match pass.draw(&input, &mut target){
+                Ok(()) => source = destination,
+                Err(error) => println!("Couldn't draw post process pass {}: {:?}", pass.name(), error),
+            }
+        }
+        let (width, height) = targets.size();
+        let destination = BlitTarget{
+            left: viewport.left,
+            bottom: viewport.bottom,
+            width: width as i32,
+            height: height as i32,
+        };
+        SimpleFrameBuffer::new(display, &targets.color[source]).unwrap()
+            .blit_color(&Rect{left: 0, bottom: 0, width, height}, output, &destination, MagnifySamplerFilter::Nearest);
+    }
+}
+
+impl Default for PostPipeline{
+    fn default() -> Self{
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    struct Named(&'static str);
+
+    impl PostPass for Named{
+        fn name(&self) -> &str{
+            self.0
+        }
+
+        fn draw(&self, _input: &PostInput, _target: &mut SimpleFrameBuffer) -> Result<(), DrawError>{
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn passes_keep_their_order(){
+        let mut pipeline = PostPipeline::new();
+        pipeline.push(Named("tonemap"));
+        pipeline.push(Named("fxaa"));
+        pipeline.insert_before("tonemap", Named("bloom"));
+        pipeline.insert_after("tonemap", Named("vignette"));
+        pipeline.insert_after("missing", Named("grain"));
+        assert_eq!(pipeline.names(), vec!["bloom", "tonemap", "vignette", "fxaa", "grain"]);
+
+        assert!(pipeline.remove("vignette").is_some());
+        assert!(pipeline.remove("vignette").is_none());
+        assert_eq!(pipeline.names(), vec!["bloom", "tonemap", "fxaa", "grain"]);
+    }
+}
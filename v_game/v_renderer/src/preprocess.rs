@@ -0,0 +1,120 @@
+use crate::*;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShaderError{
+    /// A shader or one of its includes couldn't be read
+    Resource(String, ResourceError),
+    /// A file ends up including itself
+    IncludeCycle(String),
+    /// An #include line in a file that isn't #include "file"
+    InvalidInclude(String, usize),
+}
+
+impl fmt::Display for ShaderError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            ShaderError::Resource(file, error) => write!(f, "couldn't read {}: {}", file, error),
+            ShaderError::IncludeCycle(file) => write!(f, "{} includes itself", file),
+            ShaderError::InvalidInclude(file, line) => write!(f, "invalid #include on line {} of {}", line, file),
+        }
+    }
+}
+
+/// A shader's source with its includes pasted in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderSource{
+    pub source: String,
+    /// The shader then every file it included, a file's position here is its source string number in GLSL errors
+    pub files: Vec<String>,
+}
+
+/// Paste the file named by each #include "file" line in its place, reading files through read.
+/// Files are only included once, later includes of the same file are left out.
+/// #line directives are added around includes so GLSL errors point at the right line of the right file.
+pub fn preprocess<F>(name: &str, read: F) -> Result<ShaderSource, ShaderError>
+where F: Fn(&str) -> Result<String, ResourceError>{
+    let mut shader = ShaderSource{
+        source: String::new(),
+        files: Vec::new(),
+    };
+    expand(name, &read, &mut shader, &mut Vec::new())?;
+    Ok(shader)
+}
+
+fn expand<F>(name: &str, read: &F, shader: &mut ShaderSource, including: &mut Vec<String>) -> Result<(), ShaderError>
+where F: Fn(&str) -> Result<String, ResourceError>{
+    if including.iter().any(|file| file == name){
+        return Err(ShaderError::IncludeCycle(name.to_string()));
+    }
+    let text = read(name).map_err(|error| ShaderError::Resource(name.to_string(), error))?;
+    let index = shader.files.len();
+    shader.files.push(name.to_string());
+    including.push(name.to_string());
+    if index > 0{
+        shader.source.push_str(&format!("#line 1 {}\n", index));
+    }
+
+    for (number, line) in text.lines().enumerate(){
+        let include = match line.trim().strip_prefix("#include"){
+            Some(include) => include.trim(),
+            None => {
+                shader.source.push_str(line);
+                shader.source.push('\n');
+                continue;
+            },
+        };
+        let include = include.strip_prefix('"').and_then(|include| include.strip_suffix('"'))
+            .ok_or_else(|| ShaderError::InvalidInclude(name.to_string(), number + 1))?;
+        if including.iter().any(|file| file == include) || !shader.files.iter().any(|file| file == include){
+            expand(include, read, shader, including)?;
+            shader.source.push_str(&format!("#line {} {}\n", number + 2, index));
+        } else {
+            //Already pasted in, a blank line keeps the line numbers right
+            shader.source.push('\n');
+        }
+    }
+    including.pop();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn files(files: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Result<String, ResourceError>{
+        move |name| files.iter().find(|(file, _)| *file == name)
+            .map(|(_, text)| text.to_string())
+            .ok_or_else(|| ResourceError::NotFound(name.to_string()))
+    }
+
+    #[test]
+    fn includes_are_pasted_in_once(){
+        let read = files(&[
+            ("main.glsl", "#version 450\n#include \"light.glsl\"\n  #include \"fog.glsl\"\nvoid main() {}\n"),
+            ("light.glsl", "#include \"common.glsl\"\nvec3 light() {}\n"),
+            ("fog.glsl", "#include \"common.glsl\"\nfloat fog() {}\n"),
+            ("common.glsl", "const float PI = 3.14;\n"),
+        ]);
+        let shader = preprocess("main.glsl", read).unwrap();
+        assert_eq!(shader.files, vec!["main.glsl", "light.glsl", "common.glsl", "fog.glsl"]);
+        assert_eq!(shader.source, "#version 450\n\
+            #line 1 1\n\
+            #line 1 2\nconst float PI = 3.14;\n#line 2 1\n\
+            vec3 light() {}\n#line 3 0\n\
+            #line 1 3\n\nfloat fog() {}\n#line 4 0\n\
+            void main() {}\n");
+    }
+
+    #[test]
+    fn bad_includes_are_reported(){
+        let cycle = files(&[("a.glsl", "#include \"b.glsl\"\n"), ("b.glsl", "\n#include \"a.glsl\"\n")]);
+        assert_eq!(preprocess("a.glsl", cycle), Err(ShaderError::IncludeCycle("a.glsl".to_string())));
+
+        let missing = files(&[("a.glsl", "#include \"b.glsl\"\n")]);
+        assert_eq!(preprocess("a.glsl", missing), Err(ShaderError::Resource("b.glsl".to_string(), ResourceError::NotFound("b.glsl".to_string()))));
+
+        let unquoted = files(&[("a.glsl", "#version 450\n#include b.glsl\n")]);
+        assert_eq!(preprocess("a.glsl", unquoted), Err(ShaderError::InvalidInclude("a.glsl".to_string(), 2)));
+    }
+}
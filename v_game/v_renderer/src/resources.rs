@@ -0,0 +1,250 @@
+use crate::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The game's own assets, resource packs go on top of it
+pub const ASSET_DIRECTORY: &str = "./assets/";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResourceError{
+    Io(std::io::ErrorKind),
+    /// None of the packs have the file
+    NotFound(String),
+    /// A zip archive that couldn't be read, and why
+    InvalidZip(String),
+}
+
+impl From<std::io::Error> for ResourceError{
+    fn from(error: std::io::Error) -> Self{
+        ResourceError::Io(error.kind())
+    }
+}
+
+impl fmt::Display for ResourceError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            ResourceError::Io(kind) => write!(f, "{:?}", kind),
+            ResourceError::NotFound(path) => write!(f, "{} isn't in any resource pack", path),
+            ResourceError::InvalidZip(reason) => write!(f, "invalid zip archive: {}", reason),
+        }
+    }
+}
+
+/// Somewhere assets are read from. Paths inside a pack use / and are relative to its root,
+/// like "textures/dirt.png", "shaders/base_frag.glsl" and "blocks.cfg".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResourcePack{
+    Directory(PathBuf),
+    Zip(ZipArchive),
+    /// Files held in memory, like the shaders built into the renderer
+    Memory(HashMap<String, Vec<u8>>),
+    /// A directory on disk standing in for one directory of a pack, nothing outside that directory is in the pack.
+    /// Mapped("shaders", "src/shaders") serves a repository's shader sources as shaders/.
+    Mapped(String, PathBuf),
+}
+
+impl ResourcePack{
+    /// A directory, or a zip archive if the path is a file
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ResourceError>{
+        let path = path.as_ref();
+        if path.is_dir(){
+            Ok(ResourcePack::Directory(path.to_path_buf()))
+        } else {
+            Ok(ResourcePack::Zip(ZipArchive::open(path)?))
+        }
+    }
+
+    /// Where a path in a directory or mapped pack is on disk, None for paths a mapped pack doesn't cover and other packs
+    fn disk_path(&self, path: &str) -> Option<PathBuf>{
+        match self{
+            ResourcePack::Directory(directory) => Some(directory.join(path)),
+            ResourcePack::Mapped(mapped, directory) => {
+                let rest = path.strip_prefix(mapped.trim_end_matches('/'))?;
+                match rest.strip_prefix('/'){
+                    Some(rest) => Some(directory.join(rest)),
+                    None if rest.is_empty() => Some(directory.clone()),
+                    None => None,
+                }
+            },
+            _ => None,
+        }
+    }
+
+    /// A file's contents, None if this pack doesn't have it
+    pub fn read(&self, path: &str) -> Option<Result<Vec<u8>, ResourceError>>{
+        match self{
+            ResourcePack::Directory(_) | ResourcePack::Mapped(..) => {
+                let file = self.disk_path(path).filter(|file| file.is_file())?;
+                Some(std::fs::read(file).map_err(ResourceError::from))
+            },
+            ResourcePack::Zip(archive) => archive.read(path),
+            ResourcePack::Memory(files) => files.get(path).map(|bytes| Ok(bytes.clone())),
+        }
+    }
+
+    pub fn contains(&self, path: &str) -> bool{
+        match self{
+            ResourcePack::Directory(_) | ResourcePack::Mapped(..) => matches!(self.disk_path(path), Some(file) if file.is_file()),
+            ResourcePack::Zip(archive) => archive.contains(path),
+            ResourcePack::Memory(files) => files.contains_key(path),
+        }
+    }
+
+    /// When a file in a directory or mapped pack was last changed, None for other packs
+    pub fn modified(&self, path: &str) -> Option<SystemTime>{
+        self.disk_path(path).and_then(|file| std::fs::metadata(file).and_then(|metadata| metadata.modified()).ok())
+    }
+
+    /// Names of the files directly inside a directory of the pack
+    pub fn list(&self, directory: &str) -> Vec<String>{
+        let prefix = if directory.is_empty() || directory.ends_with('/') {directory.to_string()} else {format!("{}/", directory)};
+        let in_directory = |path: &str| path.strip_prefix(prefix.as_str()).filter(|name| !name.contains('/')).map(|name| name.to_string());
+        match self{
+            ResourcePack::Directory(_) | ResourcePack::Mapped(..) => self.disk_path(directory).and_then(|path| std::fs::read_dir(path).ok()).map(|entries|{
+                entries.filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.path().is_file())
+                    .map(|entry| entry.file_name().to_string_lossy().to_string())
+                    .collect()
+            }).unwrap_or_default(),
+            ResourcePack::Zip(archive) => archive.names().filter_map(in_directory).collect(),
+            ResourcePack::Memory(files) => files.keys().filter_map(|path| in_directory(path)).collect(),
+        }
+    }
+}
+
+/// Where the renderer and block definitions find their assets.
+/// Packs are stacked, a file is read from the highest pack that has it so packs can override
+/// any texture, shader or block definition below them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceManager{
+    /// Lowest priority first
+    packs: Vec<ResourcePack>,
+    /// How many packs at the bottom are the game's own, resource packs go above them
+    base: usize,
+}
+
+impl ResourceManager{
+    pub fn new() -> Self{
+        Self::default()
+    }
+
+    /// The built in shaders with ASSET_DIRECTORY on top of them, then each pack in order, later packs win.
+    /// Packs that can't be opened are reported and left out.
+    pub fn with_packs<P: AsRef<Path>>(packs: &[P]) -> Self{
+        let mut resources = Self::new();
+        resources.push_base(builtin_shaders());
+        resources.push_base(ResourcePack::Directory(PathBuf::from(ASSET_DIRECTORY)));
+        for path in packs.iter(){
+            match ResourcePack::open(path){
+                Ok(pack) => resources.push(pack),
+                Err(error) => println!("Couldn't open resource pack {}: {}", path.as_ref().display(), error),
+            }
+        }
+        resources
+    }
+
+    /// Put a pack on top of the others
+    pub fn push(&mut self, pack: ResourcePack){
+        self.packs.push(pack);
+    }
+
+    /// Put a pack on top of the game's own packs but under every resource pack, for things like development shaders
+    pub fn push_base(&mut self, pack: ResourcePack){
+        self.packs.insert(self.base, pack);
+        self.base += 1;
+    }
+
+    pub fn packs(&self) -> &[ResourcePack]{
+        &self.packs
+    }
+
+    /// The file from the highest pack that has it
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, ResourceError>{
+        self.packs.iter().rev()
+            .find_map(|pack| pack.read(path))
+            .unwrap_or_else(|| Err(ResourceError::NotFound(path.to_string())))
+    }
+
+    pub fn read_to_string(&self, path: &str) -> Result<String, ResourceError>{
+        String::from_utf8(self.read(path)?).map_err(|_| ResourceError::Io(std::io::ErrorKind::InvalidData))
+    }
+
+    pub fn contains(&self, path: &str) -> bool{
+        self.packs.iter().any(|pack| pack.contains(path))
+    }
+
+    /// When the file that would be read was last changed, if its pack can tell
+    pub fn modified(&self, path: &str) -> Option<SystemTime>{
+        self.packs.iter().rev().find(|pack| pack.contains(path)).and_then(|pack| pack.modified(path))
+    }
+
+    /// Names of the files directly inside a directory in any pack, sorted
+    pub fn list(&self, directory: &str) -> Vec<String>{
+        let mut names: Vec<String> = self.packs.iter().flat_map(|pack| pack.list(directory)).collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn memory(files: &[(&str, &str)]) -> ResourcePack{
+        ResourcePack::Memory(files.iter().map(|(path, text)| (path.to_string(), text.as_bytes().to_vec())).collect())
+    }
+
+    #[test]
+    fn higher_packs_override_lower_ones(){
+        let mut resources = ResourceManager::new();
+        resources.push(memory(&[("blocks.cfg", "base"), ("textures/dirt.png", "dirt"), ("textures/rock.png", "rock")]));
+        resources.push(memory(&[("blocks.cfg", "pack"), ("textures/water.png", "water"), ("textures/water/frames.txt", "")]));
+
+        assert_eq!(resources.read_to_string("blocks.cfg"), Ok("pack".to_string()));
+        assert_eq!(resources.read_to_string("textures/dirt.png"), Ok("dirt".to_string()));
+        assert_eq!(resources.read("shaders/missing.glsl"), Err(ResourceError::NotFound("shaders/missing.glsl".to_string())));
+        assert!(resources.contains("textures/water.png"));
+        assert_eq!(resources.list("textures"), vec!["dirt.png", "rock.png", "water.png"]);
+        assert_eq!(resources.list(""), vec!["blocks.cfg"]);
+    }
+
+    #[test]
+    fn directory_packs_read_files(){
+        let directory = std::env::temp_dir().join(format!("v_renderer_pack_{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("textures")).unwrap();
+        std::fs::write(directory.join("textures/dirt.png"), "dirt").unwrap();
+
+        let pack = ResourcePack::open(&directory).unwrap();
+        assert_eq!(pack, ResourcePack::Directory(directory.clone()));
+        assert_eq!(pack.read("textures/dirt.png"), Some(Ok(b"dirt".to_vec())));
+        assert_eq!(pack.read("textures/rock.png"), None);
+        assert_eq!(pack.list("textures"), vec!["dirt.png"]);
+        assert_eq!(pack.list("shaders"), Vec::<String>::new());
+        assert!(pack.modified("textures/dirt.png").is_some());
+        assert!(builtin_shaders().modified("shaders/base_frag.glsl").is_none());
+
+        //The textures directory served as shaders, and only that
+        let mapped = ResourcePack::Mapped("shaders".to_string(), directory.join("textures"));
+        assert_eq!(mapped.read("shaders/dirt.png"), Some(Ok(b"dirt".to_vec())));
+        assert_eq!(mapped.read("textures/dirt.png"), None);
+        assert!(!mapped.contains("shadersdirt.png"));
+        assert!(mapped.modified("shaders/dirt.png").is_some());
+        assert_eq!(mapped.list("shaders"), vec!["dirt.png"]);
+        assert_eq!(mapped.list(""), Vec::<String>::new());
+
+        //Base packs stay under resource packs pushed before them
+        let mut resources = ResourceManager::new();
+        resources.push_base(memory(&[("blocks.cfg", "base")]));
+        resources.push(memory(&[("blocks.cfg", "pack")]));
+        resources.push_base(mapped);
+        assert_eq!(resources.read_to_string("blocks.cfg"), Ok("pack".to_string()));
+        assert_eq!(resources.read_to_string("shaders/dirt.png"), Ok("dirt".to_string()));
+        assert_eq!(resources.packs()[1], ResourcePack::Mapped("shaders".to_string(), directory.join("textures")));
+
+        assert_eq!(ResourcePack::open(directory.join("missing.zip")), Err(ResourceError::Io(std::io::ErrorKind::NotFound)));
+        std::fs::remove_dir_all(directory).ok();
+    }
+}
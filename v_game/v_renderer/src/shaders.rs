@@ -0,0 +1,163 @@
+use crate::*;
+use std::ops::Deref;
+use std::time::SystemTime;
+use std::cell::RefCell;
+
+/// Where shaders are in a resource pack
+pub const SHADER_DIRECTORY: &str = "shaders";
+
+/// Shaders built into the renderer, resource packs can replace any of them
+pub const BUILTIN_SHADERS: &[(&str, &str)] = &[
+    ("fullscreen.glsl", include_str!("../../src/shaders/fullscreen.glsl")),
+    ("depth.glsl", include_str!("../../src/shaders/depth.glsl")),
+    ("base_vertex.glsl", include_str!("../../src/shaders/base_vertex.glsl")),
+    ("packed_vertex.glsl", include_str!("../../src/shaders/packed_vertex.glsl")),
+    ("base_frag.glsl", include_str!("../../src/shaders/base_frag.glsl")),
+    ("shadow_frag.glsl", include_str!("../../src/shaders/shadow_frag.glsl")),
+    ("sky_vertex.glsl", include_str!("../../src/shaders/sky_vertex.glsl")),
+    ("sky_frag.glsl", include_str!("../../src/shaders/sky_frag.glsl")),
+    ("post_vertex.glsl", include_str!("../../src/shaders/post_vertex.glsl")),
+    ("post_ssao.glsl", include_str!("../../src/shaders/post_ssao.glsl")),
+    ("post_underwater.glsl", include_str!("../../src/shaders/post_underwater.glsl")),
+    ("post_tonemap.glsl", include_str!("../../src/shaders/post_tonemap.glsl")),
+    ("post_gamma.glsl", include_str!("../../src/shaders/post_gamma.glsl")),
+    ("post_fxaa.glsl", include_str!("../../src/shaders/post_fxaa.glsl")),
+];
+
+/// The built in shaders as the bottom resource pack
+pub fn builtin_shaders() -> ResourcePack{
+    ResourcePack::Memory(BUILTIN_SHADERS.iter()
+        .map(|(name, source)| (format!("{}/{}", SHADER_DIRECTORY, name), source.as_bytes().to_vec()))
+        .collect())
+}
+
+fn builtin_shader(name: &str) -> Result<String, ResourceError>{
+    BUILTIN_SHADERS.iter().find(|(builtin, _)| *builtin == name).map(|(_, source)| source.to_string())
+        .ok_or_else(|| ResourceError::NotFound(format!("{}/{}", SHADER_DIRECTORY, name)))
+}
+
+/// A shader with its includes from the resource packs, along with every file the preprocessor tried to read.
+/// The files are there even when it fails, the one that couldn't be read or was broken included.
+fn shader_source(resources: &ResourceManager, name: &str) -> (Vec<String>, Result<ShaderSource, ShaderError>){
+    let files = RefCell::new(Vec::new());
+    let source = preprocess(name, |file|{
+        files.borrow_mut().push(file.to_string());
+        resources.read_to_string(&format!("{}/{}", SHADER_DIRECTORY, file))
+    });
+    (files.into_inner(), source)
+}
+
+/// Compile a vertex and fragment shader, a failure is described with the files GLSL source string numbers refer to
+fn compile(display: &Display, vertex: &ShaderSource, fragment: &ShaderSource) -> Result<Program, String>{
+    Program::from_source(display, &vertex.source, &fragment.source, None).map_err(|error|{
+        format!("{}\nvertex source strings: {:?}\nfragment source strings: {:?}", error, vertex.files, fragment.files)
+    })
+}
+
+type FileStamps = Vec<(String, Option<SystemTime>)>;
+
+/// Preprocess and compile a program from the resource packs, along with when each file it needed last changed.
+/// The files are stamped even when it fails so a broken shader is only tried again once it's edited.
+fn compile_from_packs(display: &Display, resources: &ResourceManager, vertex: &str, fragment: &str) -> (FileStamps, Result<Program, String>){
+    let (mut files, vertex_source) = shader_source(resources, vertex);
+    let (fragment_files, fragment_source) = shader_source(resources, fragment);
+    files.extend(fragment_files);
+    let mut stamps: FileStamps = files.into_iter().map(|file|{
+        let modified = resources.modified(&format!("{}/{}", SHADER_DIRECTORY, file));
+        (file, modified)
+    }).collect();
+    stamps.sort();
+    stamps.dedup_by(|a, b| a.0 == b.0);
+    match (vertex_source, fragment_source){
+        (Ok(vertex_source), Ok(fragment_source)) => (stamps, compile(display, &vertex_source, &fragment_source)),
+        (Err(error), _) | (_, Err(error)) => (stamps, Err(error.to_string())),
+    }
+}
+
+/// A program compiled from shaders in the resource packs, named by file like "sky_vertex.glsl".
+/// Shaders can #include "file.glsl" other files in SHADER_DIRECTORY.
+/// Keeps when each file it was built from last changed so it can be recompiled when they're edited.
+pub struct ShaderProgram{
+    vertex: String,
+    fragment: String,
+    program: Program,
+    /// Every file read, includes too, with when it last changed
+    files: FileStamps,
+}
+
+impl ShaderProgram{
+    /// If a pack's shaders can't be loaded or don't compile the built in ones are used instead
+    pub fn load(display: &Display, resources: &ResourceManager, vertex: &str, fragment: &str) -> Self{
+        let (files, program) = compile_from_packs(display, resources, vertex, fragment);
+        let program = program.unwrap_or_else(|error|{
+            println!("Couldn't build {} and {}, using the built in shaders: {}", vertex, fragment, error);
+            Self::builtin(display, vertex, fragment)
+        });
+        ShaderProgram{
+            vertex: vertex.to_string(),
+            fragment: fragment.to_string(),
+            program,
+            files,
+        }
+    }
+
+    fn builtin(display: &Display, vertex: &str, fragment: &str) -> Program{
+        let source = |name: &str| preprocess(name, builtin_shader).unwrap_or_else(|error| panic!("Built in shader {}: {}", name, error));
+        compile(display, &source(vertex), &source(fragment))
+            .unwrap_or_else(|error| panic!("Built in shaders {} and {}: {}", vertex, fragment, error))
+    }
+
+    /// Whether any file the program was built from has changed since
+    pub fn changed(&self, resources: &ResourceManager) -> bool{
+        self.files.iter().any(|(file, modified)| resources.modified(&format!("{}/{}", SHADER_DIRECTORY, file)) != *modified)
+    }
+
+    /// Build the program again from the resource packs. If the shaders can't be read or don't compile
+    /// the error is logged, the program in use is kept and false is returned.
+    pub fn reload(&mut self, display: &Display, resources: &ResourceManager) -> bool{
+        let (files, program) = compile_from_packs(display, resources, &self.vertex, &self.fragment);
+        self.files = files;
+        match program{
+            Ok(program) => {
+                self.program = program;
+                println!("Reloaded {} and {}", self.vertex, self.fragment);
+                true
+            },
+            Err(error) => {
+                println!("Couldn't build {} and {}, keeping the old program: {}", self.vertex, self.fragment, error);
+                false
+            },
+        }
+    }
+
+    /// Reload if any of the files changed, true if a new program is in use
+    pub fn reload_if_changed(&mut self, display: &Display, resources: &ResourceManager) -> bool{
+        self.changed(resources) && self.reload(display, resources)
+    }
+}
+
+impl Deref for ShaderProgram{
+    type Target = Program;
+
+    fn deref(&self) -> &Program{
+        &self.program
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn failed_shaders_list_every_file_tried(){
+        let mut resources = ResourceManager::new();
+        resources.push(ResourcePack::Memory(vec![
+            ("shaders/main.glsl", "#include \"light.glsl\"\n#include \"fog.glsl\"\n"),
+            ("shaders/light.glsl", "vec3 light() {}\n"),
+        ].into_iter().map(|(path, text)| (path.to_string(), text.as_bytes().to_vec())).collect()));
+
+        let (files, source) = shader_source(&resources, "main.glsl");
+        assert_eq!(source, Err(ShaderError::Resource("fog.glsl".to_string(), ResourceError::NotFound("shaders/fog.glsl".to_string()))));
+        assert_eq!(files, vec!["main.glsl", "light.glsl", "fog.glsl"]);
+    }
+}
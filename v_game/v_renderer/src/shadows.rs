@@ -0,0 +1,142 @@
+use crate::*;
+use glium::texture::{DepthTexture2d, DepthFormat, MipmapsOption};
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::uniforms::{DepthTextureComparison, Sampler};
+use v_windowing::ShadowQuality;
+
+/// Most cascades any quality uses, the fragment shader has a shadow map uniform for each
+pub const MAX_CASCADES: usize = 4;
+
+/// How far cascade splits lean towards logarithmic spacing, see cascade_splits
+const SPLIT_LAMBDA: f32 = 0.6;
+
+/// A chunk mesh that can cast a shadow, with its bounds in world space
+pub type ShadowCaster = (GeometryAllocation, ChunkInstance, Aabb);
+
+/// What the cascades are fitted around
+pub struct ShadowView{
+    /// The camera's transform
+    pub camera_matrix: Matrix4<f32>,
+    pub fov: f32,
+    pub aspect_ratio: f32,
+    pub near: f32,
+    /// Towards the sun or moon
+    pub light_direction: Vector3<f32>,
+}
+
+/// Cascaded shadow maps for the sun, drawn from the sun's point of view before the main pass.
+/// Each cascade covers a slice of the camera's view, close slices get the most detail.
+pub struct ShadowMaps{
+    quality: ShadowQuality,
+    maps: Vec<DepthTexture2d>,
+    view_projections: [Matrix4<f32>; MAX_CASCADES],
+    /// Distance from the camera each cascade ends at
+    splits: [f32; MAX_CASCADES],
+    /// Bound in place of cascades the quality doesn't use
+    unused: DepthTexture2d,
+    program: ShaderProgram,
+    packed_program: ShaderProgram,
+    /// One for each cascade
+    batches: Vec<ChunkBatch>,
+}
+
+impl ShadowMaps{
+    pub fn new(display: &Display, resources: &ResourceManager, quality: ShadowQuality) -> Self{
+        let mut shadows = ShadowMaps{
+            quality: ShadowQuality::Off,
+            maps: Vec::new(),
+            view_projections: [Matrix4::identity(); MAX_CASCADES],
+            splits: [0.0; MAX_CASCADES],
+            unused: Self::depth_texture(display, 1),
+            program: ShaderProgram::load(display, resources, "base_vertex.glsl", "shadow_frag.glsl"),
+            packed_program: ShaderProgram::load(display, resources, "packed_vertex.glsl", "shadow_frag.glsl"),
+            batches: (0..MAX_CASCADES).map(|_| ChunkBatch::new()).collect(),
+        };
+        shadows.set_quality(display, quality);
+        shadows
+    }
+
+    fn depth_texture(display: &Display, size: u32) -> DepthTexture2d{
+        DepthTexture2d::empty_with_format(display, DepthFormat::F32, MipmapsOption::NoMipmap, size, size).unwrap()
+    }
+
+    pub fn quality(&self) -> ShadowQuality{
+        self.quality
+    }
+
+    /// Remake the shadow maps for a new quality
+    pub fn set_quality(&mut self, display: &Display, quality: ShadowQuality){
+        self.quality = quality;
+        self.maps = (0..quality.cascades()).map(|_| Self::depth_texture(display, quality.resolution())).collect();
+    }
+
+    /// Recompile the shadow shaders if they changed in the resource packs
+    pub fn reload_shaders(&mut self, display: &Display, resources: &ResourceManager){
+        self.program.reload_if_changed(display, resources);
+        self.packed_program.reload_if_changed(display, resources);
+    }
+
+    pub fn cascades(&self) -> usize{
+        self.maps.len()
+    }
+
+    /// Shadow map for a cascade set up for comparing depths, the unused map past the last cascade
+    pub fn sampler(&self, cascade: usize) -> Sampler<DepthTexture2d>{
+        self.maps.get(cascade).unwrap_or(&self.unused).sampled()
+            .depth_texture_comparison(Some(DepthTextureComparison::LessOrEqual))
+            .minify_filter(uniforms::MinifySamplerFilter::Linear)
+            .magnify_filter(uniforms::MagnifySamplerFilter::Linear)
+            .wrap_function(uniforms::SamplerWrapFunction::Clamp)
+    }
+
+    pub fn view_projection(&self, cascade: usize) -> [[f32; 4]; 4]{
+        *self.view_projections[cascade].as_ref()
+    }
+
+    pub fn splits(&self) -> [f32; MAX_CASCADES]{
+        self.splits
+    }
+
+    /// Fit each cascade to its slice of the view and draw the casters inside it
+    pub fn render(&mut self, display: &Display, pool: &ChunkPool, casters: &[ShadowCaster], view: &ShadowView, multidraw: bool){
+        let count = self.cascades();
+        if count == 0{
+            return;
+        }
+        let resolution = self.quality.resolution();
+        let splits = cascade_splits(view.near.max(0.1), self.quality.distance(), count, SPLIT_LAMBDA);
+        let params = DrawParameters{
+            depth: Depth{
+                test: DepthTest::IfLess,
+                write: true,
+                .. Default::default()
+            },
+            .. Default::default()
+        };
+
+        for cascade in 0..count{
+            let corners = frustum_slice_corners(&view.camera_matrix, view.fov, view.aspect_ratio, splits[cascade], splits[cascade + 1]);
+            let light = fit_cascade(&corners, &view.light_direction, resolution);
+            self.view_projections[cascade] = light;
+            self.splits[cascade] = splits[cascade + 1];
+
+            let frustum = Frustum::from_matrix(&light);
+            let meshes: Vec<_> = casters.iter()
+                .filter(|(_, _, bounds)| frustum.intersects(bounds))
+                .map(|(allocation, instance, _)| (allocation.clone(), *instance))
+                .collect();
+            let batch = &mut self.batches[cascade];
+            batch.fill(display, &meshes, multidraw);
+            let mut target = SimpleFrameBuffer::depth_only(display, &self.maps[cascade]).unwrap();
+            target.clear_depth(1.0);
+            let uniforms = uniform!(view_projection: *light.as_ref(), block_size: pool.block_size());
+            let result = match pool{
+                ChunkPool::Packed{pool, ..} => batch.draw(&mut target, pool, &self.packed_program, &uniforms, &params),
+                ChunkPool::Full(pool) => batch.draw(&mut target, pool, &self.program, &uniforms, &params),
+            };
+            if let Err(error) = result{
+                println!("Couldn't draw shadow cascade {}: {:?}", cascade, error);
+            }
+        }
+    }
+}
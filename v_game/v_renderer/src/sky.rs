@@ -0,0 +1,39 @@
+/// The time of day and its lighting live with the other world state so crates without GL can use them
+pub use v_transform::{TimeOfDay, SkyLighting};
+
+/// Distance fog, faded in between start and end so the edge of the loaded chunks can't be seen.
+/// end should be a little short of how far away chunks get loaded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fog{
+    pub start: f32,
+    pub end: f32,
+}
+
+impl Default for Fog{
+    fn default() -> Self{
+        Fog{
+            start: 160.0,
+            end: 240.0,
+        }
+    }
+}
+
+impl Fog{
+    /// How much of a point this far from the camera is hidden by fog, the same as the fragment shader
+    pub fn amount(&self, distance: f32) -> f32{
+        ((distance - self.start) / (self.end - self.start).max(0.0001)).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn fog_fades_between_distances(){
+        let fog = Fog{start: 10.0, end: 20.0};
+        assert_eq!(fog.amount(5.0), 0.0);
+        assert_eq!(fog.amount(15.0), 0.5);
+        assert_eq!(fog.amount(30.0), 1.0);
+    }
+}
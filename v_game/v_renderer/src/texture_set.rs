@@ -0,0 +1,217 @@
+use crate::*;
+use std::fmt;
+
+/// Where the voxel textures are in a resource pack
+pub const TEXTURE_DIRECTORY: &str = "textures";
+
+/// Seconds each frame of an animated texture shows for unless its .anim file says otherwise
+pub const DEFAULT_FRAME_TIME: f32 = 0.125;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextureError{
+    /// A texture the block types need isn't in any resource pack
+    Missing(String),
+    /// A file that couldn't be read or decoded as an image, and why
+    Unreadable(String, String),
+    /// Every texture has to be as wide as the first one
+    SizeMismatch{file: String, expected: u32, found: u32},
+    /// Animated strips are square frames stacked top to bottom, so their height is a whole number of widths
+    BadStrip{file: String, width: u32, height: u32},
+    /// A line in a .anim file that isn't "frame_time <seconds>"
+    InvalidAnimation(String, usize),
+    /// There aren't any textures to build an array from
+    Empty,
+    /// The GL texture couldn't be made from them, and why
+    Creation(String),
+}
+
+impl fmt::Display for TextureError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            TextureError::Missing(file) => write!(f, "missing texture {}", file),
+            TextureError::Unreadable(file, reason) => write!(f, "couldn't load {}: {}", file, reason),
+            TextureError::SizeMismatch{file, expected, found} => write!(f, "{} is {} pixels wide but textures are {} wide", file, found, expected),
+            TextureError::BadStrip{file, width, height} => write!(f, "{} is {}x{}, animation strips are square frames stacked vertically", file, width, height),
+            TextureError::InvalidAnimation(file, line) => write!(f, "invalid line {} in {}", line, file),
+            TextureError::Empty => write!(f, "no textures to load"),
+            TextureError::Creation(reason) => write!(f, "couldn't create the texture array: {}", reason),
+        }
+    }
+}
+
+/// A texture's place in the array, animated textures take a layer for each frame starting at layer
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureInfo{
+    /// File name, like "grass.png"
+    pub name: String,
+    pub layer: u32,
+    pub frames: u32,
+    /// Seconds each frame shows for
+    pub frame_time: f32,
+}
+
+/// Textures read from TEXTURE_DIRECTORY in the resource packs, checked and cut up into layers ready for a Texture2dArray.
+/// Required textures come first in the order given so block types can rely on their layers, any other PNGs follow by name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureSet{
+    /// Width and height of every layer
+    pub size: u32,
+    pub textures: Vec<TextureInfo>,
+    /// RGBA pixels of each layer, top row first
+    pub layers: Vec<Vec<u8>>,
+}
+
+impl TextureSet{
+    pub fn load(resources: &ResourceManager, required: &[&str]) -> Result<Self, TextureError>{
+        let extra = resources.list(TEXTURE_DIRECTORY).into_iter()
+            .filter(|name| name.ends_with(".png") && !required.contains(&name.as_str()));
+
+        let mut set = TextureSet{
+            size: 0,
+            textures: Vec::new(),
+            layers: Vec::new(),
+        };
+        for name in required.iter().map(|name| name.to_string()).chain(extra){
+            let bytes = match resources.read(&format!("{}/{}", TEXTURE_DIRECTORY, name)){
+                Ok(bytes) => bytes,
+                Err(ResourceError::NotFound(_)) => return Err(TextureError::Missing(name)),
+                Err(error) => return Err(TextureError::Unreadable(name, error.to_string())),
+            };
+            let image = image::load_from_memory(&bytes).map_err(|error| TextureError::Unreadable(name.clone(), error.to_string()))?.to_rgba();
+            let (width, height) = image.dimensions();
+            if set.textures.is_empty(){
+                set.size = width;
+            }
+            if width != set.size{
+                return Err(TextureError::SizeMismatch{file: name, expected: set.size, found: width});
+            }
+            if width == 0 || height == 0 || height % width != 0{
+                return Err(TextureError::BadStrip{file: name, width, height});
+            }
+            let frame_time = Self::frame_time(resources, &name)?;
+
+            let frames = height / width;
+            let frame_bytes = (width * width * 4) as usize;
+            set.textures.push(TextureInfo{
+                name,
+                layer: set.layers.len() as u32,
+                frames,
+                frame_time,
+            });
+            set.layers.extend(image.into_raw().chunks(frame_bytes).map(|frame| frame.to_vec()));
+        }
+        if set.textures.is_empty(){
+            return Err(TextureError::Empty);
+        }
+        Ok(set)
+    }
+
+    /// Frame time from the texture's .anim file next to it, "water.anim" for "water.png"
+    fn frame_time(resources: &ResourceManager, name: &str) -> Result<f32, TextureError>{
+        let file = format!("{}.anim", name.trim_end_matches(".png"));
+        let text = match resources.read_to_string(&format!("{}/{}", TEXTURE_DIRECTORY, file)){
+            Ok(text) => text,
+            Err(_) => return Ok(DEFAULT_FRAME_TIME),
+        };
+        let mut frame_time = DEFAULT_FRAME_TIME;
+        for (number, line) in text.lines().enumerate(){
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#'){
+                continue;
+            }
+            let invalid = || TextureError::InvalidAnimation(file.clone(), number + 1);
+            match line.split_once(' '){
+                Some(("frame_time", value)) => frame_time = value.trim().parse().ok().filter(|time: &f32| *time > 0.0).ok_or_else(invalid)?,
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(frame_time)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TextureInfo>{
+        self.textures.iter().find(|texture| texture.name == name)
+    }
+
+    pub fn layer_count(&self) -> usize{
+        self.layers.len()
+    }
+
+    /// Frame count and frame time in milliseconds for every layer, the shader looks a vertex's texture up in this.
+    /// Layers after an animation's first are never a vertex's texture and get a single frame.
+    pub fn animations(&self) -> Vec<(u32, u32)>{
+        let mut animations = vec![(1, 0); self.layer_count()];
+        for texture in self.textures.iter(){
+            animations[texture.layer as usize] = (texture.frames, (texture.frame_time * 1000.0).round().max(1.0) as u32);
+        }
+        animations
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Files in the textures directory of a resource pack
+    type Files = HashMap<String, Vec<u8>>;
+
+    fn load(files: &Files, required: &[&str]) -> Result<TextureSet, TextureError>{
+        let mut resources = ResourceManager::new();
+        resources.push(ResourcePack::Memory(files.iter().map(|(name, bytes)| (format!("{}/{}", TEXTURE_DIRECTORY, name), bytes.clone())).collect()));
+        TextureSet::load(&resources, required)
+    }
+
+    fn write_png(files: &mut Files, name: &str, width: u32, height: u32){
+        let pixels: Vec<u8> = (0..width * height).flat_map(|i| vec![(i % 256) as u8, 0, (i / 256) as u8, 255]).collect();
+        let mut bytes = Vec::new();
+        image::png::PNGEncoder::new(&mut bytes).encode(&pixels, width, height, image::ColorType::RGBA(8)).unwrap();
+        files.insert(name.to_string(), bytes);
+    }
+
+    #[test]
+    fn animated_strips_take_a_layer_per_frame(){
+        let mut files = Files::new();
+        write_png(&mut files, "dirt.png", 4, 4);
+        write_png(&mut files, "water.png", 4, 12);
+        write_png(&mut files, "lava.png", 4, 8);
+        files.insert("water.anim".to_string(), b"# slow\nframe_time 0.5\n".to_vec());
+
+        let set = load(&files, &["dirt.png"]).unwrap();
+        let names: Vec<_> = set.textures.iter().map(|texture| texture.name.as_str()).collect();
+        assert_eq!(names, vec!["dirt.png", "lava.png", "water.png"]);
+        assert_eq!(set.size, 4);
+        assert_eq!(set.layer_count(), 6);
+        assert!(set.layers.iter().all(|layer| layer.len() == 4 * 4 * 4));
+        //Frames are cut from the top of the strip down
+        assert_eq!(set.layers[3][0..4], [0, 0, 0, 255]);
+        assert_eq!(set.layers[4][0..4], [16, 0, 0, 255]);
+
+        let water = set.get("water.png").unwrap();
+        assert_eq!((water.layer, water.frames, water.frame_time), (3, 3, 0.5));
+        assert_eq!(set.get("lava.png").unwrap().frame_time, DEFAULT_FRAME_TIME);
+        assert_eq!(set.animations(), vec![(1, 125), (2, 125), (1, 0), (3, 500), (1, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn bad_textures_are_reported(){
+        let mut files = Files::new();
+        write_png(&mut files, "dirt.png", 4, 4);
+        assert_eq!(load(&files, &["dirt.png", "rock.png"]), Err(TextureError::Missing("rock.png".to_string())));
+
+        write_png(&mut files, "rock.png", 8, 8);
+        assert_eq!(load(&files, &["dirt.png", "rock.png"]), Err(TextureError::SizeMismatch{file: "rock.png".to_string(), expected: 4, found: 8}));
+
+        files.remove("rock.png");
+        write_png(&mut files, "water.png", 4, 6);
+        assert_eq!(load(&files, &["dirt.png"]), Err(TextureError::BadStrip{file: "water.png".to_string(), width: 4, height: 6}));
+
+        write_png(&mut files, "water.png", 4, 8);
+        files.insert("water.anim".to_string(), b"frame_time fast\n".to_vec());
+        assert_eq!(load(&files, &["dirt.png"]), Err(TextureError::InvalidAnimation("water.anim".to_string(), 1)));
+
+        files.insert("broken.png".to_string(), b"not a png".to_vec());
+        assert!(matches!(load(&files, &["dirt.png"]), Err(TextureError::Unreadable(file, _)) if file == "broken.png"));
+
+        assert_eq!(load(&Files::new(), &[]), Err(TextureError::Empty));
+    }
+}
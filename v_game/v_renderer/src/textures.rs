@@ -0,0 +1,118 @@
+use crate::*;
+use glium::texture::{MipmapsOption, SrgbTexture2dArray, UnsignedTexture1d};
+use glium::uniforms::Sampler;
+
+/// Reads the PNGs in the resource packs into the voxel texture array, see TextureSet for how they're laid out
+pub struct TextureArrayBuilder{
+    required: Vec<String>,
+    mipmaps: bool,
+    anisotropy: u16,
+}
+
+impl TextureArrayBuilder{
+    /// Requires TEXTURE_FILES with mipmaps and 16x anisotropic filtering
+    pub fn new() -> Self{
+        TextureArrayBuilder{
+            required: TEXTURE_FILES.iter().map(|file| file.to_string()).collect(),
+            mipmaps: true,
+            anisotropy: 16,
+        }
+    }
+
+    /// Textures that have to be there, in the layers block types expect them in
+    pub fn with_required(mut self, files: &[&str]) -> Self{
+        self.required = files.iter().map(|file| file.to_string()).collect();
+        self
+    }
+
+    pub fn with_mipmaps(mut self, mipmaps: bool) -> Self{
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    /// Most samples taken for surfaces seen at an angle, 1 turns anisotropic filtering off
+    pub fn with_anisotropy(mut self, anisotropy: u16) -> Self{
+        self.anisotropy = anisotropy.max(1);
+        self
+    }
+
+    pub fn build(&self, display: &Display, resources: &ResourceManager) -> Result<VoxelTextures, TextureError>{
+        let required: Vec<&str> = self.required.iter().map(|file| file.as_str()).collect();
+        let set = TextureSet::load(resources, &required)?;
+        VoxelTextures::new(display, set, self.mipmaps, self.anisotropy)
+    }
+}
+
+impl Default for TextureArrayBuilder{
+    fn default() -> Self{
+        Self::new()
+    }
+}
+
+/// The texture array chunk faces are drawn with and what the shader needs to animate it
+pub struct VoxelTextures{
+    array: SrgbTexture2dArray,
+    /// See TextureSet::animations
+    animations: UnsignedTexture1d,
+    textures: Vec<TextureInfo>,
+    mipmaps: bool,
+    anisotropy: u16,
+}
+
+impl VoxelTextures{
+    pub fn new(display: &Display, set: TextureSet, mipmaps: bool, anisotropy: u16) -> Result<Self, TextureError>{
+        let size = set.size;
+        let images = set.layers.iter().map(|layer| RawImage2d::from_raw_rgba_reversed(layer, (size, size))).collect();
+        let option = if mipmaps {MipmapsOption::AutoGeneratedMipmaps} else {MipmapsOption::NoMipmap};
+        let array = SrgbTexture2dArray::with_mipmaps(display, images, option).map_err(|error| TextureError::Creation(format!("{:?}", error)))?;
+        let animations = UnsignedTexture1d::new(display, set.animations()).map_err(|error| TextureError::Creation(format!("{:?}", error)))?;
+        Ok(VoxelTextures{
+            array,
+            animations,
+            textures: set.textures,
+            mipmaps,
+            anisotropy,
+        })
+    }
+
+    /// Magenta and black checks in place of each of the named textures, so the world can still be seen when they couldn't be loaded
+    pub fn placeholder(display: &Display, names: &[&str]) -> Self{
+        let checks: Vec<u8> = [[255, 0, 255, 255], [0, 0, 0, 255], [0, 0, 0, 255], [255, 0, 255, 255]].concat();
+        let set = TextureSet{
+            size: 2,
+            textures: names.iter().enumerate().map(|(layer, name)| TextureInfo{
+                name: name.to_string(),
+                layer: layer as u32,
+                frames: 1,
+                frame_time: DEFAULT_FRAME_TIME,
+            }).collect(),
+            layers: vec![checks; names.len().max(1)],
+        };
+        Self::new(display, set, false, 1).unwrap()
+    }
+
+    /// Pixels stay sharp up close, mipmaps smooth them out in the distance
+    pub fn sampler(&self) -> Sampler<SrgbTexture2dArray>{
+        let minify = if self.mipmaps {MinifySamplerFilter::NearestMipmapLinear} else {MinifySamplerFilter::Nearest};
+        self.array.sampled()
+            .minify_filter(minify)
+            .magnify_filter(MagnifySamplerFilter::Nearest)
+            .anisotropy(self.anisotropy)
+    }
+
+    pub fn animations(&self) -> Sampler<UnsignedTexture1d>{
+        self.animations.sampled()
+            .minify_filter(MinifySamplerFilter::Nearest)
+            .magnify_filter(MagnifySamplerFilter::Nearest)
+    }
+
+    /// Where each texture is in the array
+    pub fn textures(&self) -> &[TextureInfo]{
+        &self.textures
+    }
+
+    /// First layer of a texture, by file name
+    pub fn layer(&self, name: &str) -> Option<u32>{
+        self.textures.iter().find(|texture| texture.name == name).map(|texture| texture.layer)
+    }
+}
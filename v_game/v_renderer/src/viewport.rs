@@ -0,0 +1,99 @@
+use crate::*;
+
+/// The part of the screen or render texture a camera draws to, as fractions of its size measured from the bottom left
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport{
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport{
+    /// The whole screen
+    pub const FULL: Viewport = Viewport{x: 0.0, y: 0.0, width: 1.0, height: 1.0};
+
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self{
+        Viewport{x, y, width, height}
+    }
+
+    /// One of count equal columns side by side, for split screen
+    pub fn column(index: u32, count: u32) -> Self{
+        let width = 1.0 / count.max(1) as f32;
+        Viewport::new(width * index as f32, 0.0, width, 1.0)
+    }
+
+    /// One of count equal rows, the first at the top
+    pub fn row(index: u32, count: u32) -> Self{
+        let height = 1.0 / count.max(1) as f32;
+        Viewport::new(0.0, 1.0 - height * (index + 1) as f32, 1.0, height)
+    }
+
+    /// Pixels covered on a surface of the given size, clamped to the surface and at least a pixel across
+    pub fn pixels(&self, width: u32, height: u32) -> Rect{
+        let (width, height) = (width.max(1), height.max(1));
+        let edge = |fraction: f32, size: u32| (fraction.clamp(0.0, 1.0) * size as f32).round() as u32;
+        let left = edge(self.x, width).min(width - 1);
+        let bottom = edge(self.y, height).min(height - 1);
+        let right = edge(self.x + self.width, width).max(left + 1);
+        let top = edge(self.y + self.height, height).max(bottom + 1);
+        Rect{left, bottom, width: right - left, height: top - bottom}
+    }
+}
+
+impl Default for Viewport{
+    fn default() -> Self{
+        Viewport::FULL
+    }
+}
+
+/// What a camera's view starts out as before the world is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CameraClear{
+    /// The horizon colour with the sky drawn over it
+    #[default]
+    Sky,
+    /// A flat colour and no sky, for views like a top down minimap
+    Color([f32; 4]),
+}
+
+/// A texture a camera draws into instead of the screen, found in RenderTextures by name.
+/// Cameras sharing a name share the texture, each drawing into its own viewport of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderTarget{
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl RenderTarget{
+    pub fn new(name: &str, width: u32, height: u32) -> Self{
+        RenderTarget{
+            name: name.to_string(),
+            width: width.max(1),
+            height: height.max(1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn rect(rect: Rect) -> (u32, u32, u32, u32){
+        (rect.left, rect.bottom, rect.width, rect.height)
+    }
+
+    #[test]
+    fn viewports_cover_their_part_of_the_surface(){
+        assert_eq!(rect(Viewport::FULL.pixels(1280, 720)), (0, 0, 1280, 720));
+        assert_eq!(rect(Viewport::column(0, 2).pixels(1280, 720)), (0, 0, 640, 720));
+        assert_eq!(rect(Viewport::column(1, 2).pixels(1281, 720)), (641, 0, 640, 720));
+        assert_eq!(rect(Viewport::row(0, 2).pixels(1280, 720)), (0, 360, 1280, 360));
+        assert_eq!(rect(Viewport::new(0.75, 0.75, 0.25, 0.25).pixels(1280, 720)), (960, 540, 320, 180));
+
+        //Off the edge or empty viewports still get a pixel on the surface
+        assert_eq!(rect(Viewport::new(0.9, -0.5, 0.5, 1.0).pixels(100, 100)), (90, 0, 10, 50));
+        assert_eq!(rect(Viewport::new(1.0, 0.5, 0.0, 0.0).pixels(100, 100)), (99, 50, 1, 1));
+    }
+}
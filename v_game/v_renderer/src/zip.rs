@@ -0,0 +1,254 @@
+use crate::*;
+use std::collections::HashMap;
+use std::path::Path;
+
+const END_OF_DIRECTORY: u32 = 0x0605_4b50;
+const DIRECTORY_HEADER: u32 = 0x0201_4b50;
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+/// Where a file's bytes are in the archive and how they're compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ZipEntry{
+    method: u16,
+    local_header: usize,
+    compressed_size: usize,
+    /// Size and CRC-32 of the file once it's decompressed
+    size: usize,
+    crc: u32,
+}
+
+/// A zip archive read into memory, files are decompressed as they're read.
+/// Only stored and deflated files are supported, which is what zip tools write by default.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZipArchive{
+    data: Vec<u8>,
+    entries: HashMap<String, ZipEntry>,
+}
+
+fn read_u16(data: &[u8], at: usize) -> Result<u16, ResourceError>{
+    data.get(at..at + 2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]])).ok_or_else(|| ResourceError::InvalidZip("unexpected end of archive".to_string()))
+}
+
+fn read_u32(data: &[u8], at: usize) -> Result<u32, ResourceError>{
+    data.get(at..at + 4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])).ok_or_else(|| ResourceError::InvalidZip("unexpected end of archive".to_string()))
+}
+
+/// The CRC-32 zip files store for each file
+fn crc32(bytes: &[u8]) -> u32{
+    let mut crc = !0u32;
+    for byte in bytes.iter(){
+        crc ^= *byte as u32;
+        for _ in 0..8{
+            crc = if crc & 1 == 1 {(crc >> 1) ^ 0xedb8_8320} else {crc >> 1};
+        }
+    }
+    !crc
+}
+
+/// Inflate at most size bytes, so an archive can't claim a small file and unpack a huge one
+fn inflate_limited(bytes: &[u8], size: usize) -> Result<Vec<u8>, ResourceError>{
+    let mut stream = inflate::InflateStream::new();
+    let mut output = Vec::new();
+    let mut at = 0;
+    while at < bytes.len(){
+        let (read, inflated) = stream.update(&bytes[at..]).map_err(ResourceError::InvalidZip)?;
+        if output.len() + inflated.len() > size{
+            return Err(ResourceError::InvalidZip("file is larger than the archive says".to_string()));
+        }
+        output.extend_from_slice(inflated);
+        if read == 0 && inflated.is_empty(){
+            break;
+        }
+        at += read;
+    }
+    Ok(output)
+}
+
+impl ZipArchive{
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ResourceError>{
+        Self::from_bytes(std::fs::read(path)?)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, ResourceError>{
+        //The end of directory record is last, before a comment of up to 64k
+        let search_start = data.len().saturating_sub(22 + 0xffff);
+        let end = (search_start..data.len().saturating_sub(21)).rev()
+            .find(|at| read_u32(&data, *at) == Ok(END_OF_DIRECTORY))
+            .ok_or_else(|| ResourceError::InvalidZip("no central directory".to_string()))?;
+        let count = read_u16(&data, end + 10)? as usize;
+        let mut at = read_u32(&data, end + 16)? as usize;
+
+        let mut entries = HashMap::new();
+        for _ in 0..count{
+            if read_u32(&data, at)? != DIRECTORY_HEADER{
+                return Err(ResourceError::InvalidZip("bad central directory entry".to_string()));
+            }
+            let method = read_u16(&data, at + 10)?;
+            let crc = read_u32(&data, at + 16)?;
+            let compressed_size = read_u32(&data, at + 20)?;
+            let size = read_u32(&data, at + 24)?;
+            let name_length = read_u16(&data, at + 28)? as usize;
+            let extra_length = read_u16(&data, at + 30)? as usize;
+            let comment_length = read_u16(&data, at + 32)? as usize;
+            let local_header = read_u32(&data, at + 42)?;
+            let name = data.get(at + 46..at + 46 + name_length)
+                .map(|name| String::from_utf8_lossy(name).replace('\\', "/"))
+                .ok_or_else(|| ResourceError::InvalidZip("unexpected end of archive".to_string()))?;
+            if compressed_size == u32::MAX || size == u32::MAX || local_header == u32::MAX{
+                return Err(ResourceError::InvalidZip("zip64 archives aren't supported".to_string()));
+            }
+            //Folders are only there as part of file names
+            if !name.ends_with('/'){
+                entries.insert(name, ZipEntry{method, local_header: local_header as usize, compressed_size: compressed_size as usize, size: size as usize, crc});
+            }
+            at += 46 + name_length + extra_length + comment_length;
+        }
+        Ok(ZipArchive{data, entries})
+    }
+
+    /// Paths of every file in the archive
+    pub fn names(&self) -> impl Iterator<Item = &str>{
+        self.entries.keys().map(|name| name.as_str())
+    }
+
+    pub fn contains(&self, name: &str) -> bool{
+        self.entries.contains_key(name)
+    }
+
+    /// A file's contents, None if the archive doesn't have it
+    pub fn read(&self, name: &str) -> Option<Result<Vec<u8>, ResourceError>>{
+        self.entries.get(name).map(|entry| self.read_entry(entry))
+    }
+
+    fn read_entry(&self, entry: &ZipEntry) -> Result<Vec<u8>, ResourceError>{
+        let at = entry.local_header;
+        if read_u32(&self.data, at)? != LOCAL_HEADER{
+            return Err(ResourceError::InvalidZip("bad local file header".to_string()));
+        }
+        //The local header's extra field can differ from the central directory's
+        let start = at + 30 + read_u16(&self.data, at + 26)? as usize + read_u16(&self.data, at + 28)? as usize;
+        let bytes = self.data.get(start..start + entry.compressed_size)
+            .ok_or_else(|| ResourceError::InvalidZip("unexpected end of archive".to_string()))?;
+        let contents = match entry.method{
+            STORED => bytes.to_vec(),
+            DEFLATED => inflate_limited(bytes, entry.size)?,
+            method => return Err(ResourceError::InvalidZip(format!("compression method {} isn't supported", method))),
+        };
+        if contents.len() != entry.size{
+            return Err(ResourceError::InvalidZip("file is smaller than the archive says".to_string()));
+        }
+        if crc32(&contents) != entry.crc{
+            return Err(ResourceError::InvalidZip("CRC doesn't match".to_string()));
+        }
+        Ok(contents)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    /// "frame_time 0.5\n" three times, deflated
+    const DEFLATED_ANIM: [u8; 20] = [75, 43, 74, 204, 77, 141, 47, 201, 204, 77, 85, 48, 208, 51, 229, 74, 195, 203, 5, 0];
+
+    /// An archive of (name, method, contents, bytes as stored), the CRC and size are worked out from contents
+    fn zip(files: &[(&str, u16, &[u8], &[u8])]) -> Vec<u8>{
+        let mut data = Vec::new();
+        let mut directory = Vec::new();
+        for (name, method, contents, bytes) in files.iter(){
+            let offset = data.len() as u32;
+            data.extend_from_slice(&LOCAL_HEADER.to_le_bytes());
+            data.extend_from_slice(&[20, 0, 0, 0]);
+            data.extend_from_slice(&method.to_le_bytes());
+            data.extend_from_slice(&[0; 4]);
+            data.extend_from_slice(&crc32(contents).to_le_bytes());
+            data.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            data.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+            data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            data.extend_from_slice(&[0, 0]);
+            data.extend_from_slice(name.as_bytes());
+            data.extend_from_slice(bytes);
+
+            directory.extend_from_slice(&DIRECTORY_HEADER.to_le_bytes());
+            directory.extend_from_slice(&[20, 0, 20, 0, 0, 0]);
+            directory.extend_from_slice(&method.to_le_bytes());
+            directory.extend_from_slice(&[0; 4]);
+            directory.extend_from_slice(&crc32(contents).to_le_bytes());
+            directory.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            directory.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+            directory.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            directory.extend_from_slice(&[0; 12]);
+            directory.extend_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+        }
+        let directory_offset = data.len() as u32;
+        data.extend_from_slice(&directory);
+        data.extend_from_slice(&END_OF_DIRECTORY.to_le_bytes());
+        data.extend_from_slice(&[0, 0, 0, 0]);
+        data.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        data.extend_from_slice(&directory_offset.to_le_bytes());
+        data.extend_from_slice(&[0, 0]);
+        data
+    }
+
+    #[test]
+    fn stored_and_deflated_files_are_read(){
+        let anim = b"frame_time 0.5\n".repeat(3);
+        let archive = ZipArchive::from_bytes(zip(&[
+            ("textures/", STORED, b"", b""),
+            ("blocks.cfg", STORED, b"dirt all rock\n", b"dirt all rock\n"),
+            ("textures/water.anim", DEFLATED, &anim, &DEFLATED_ANIM),
+        ])).unwrap();
+        let mut names: Vec<_> = archive.names().collect();
+        names.sort();
+        assert_eq!(names, vec!["blocks.cfg", "textures/water.anim"]);
+        assert_eq!(archive.read("blocks.cfg"), Some(Ok(b"dirt all rock\n".to_vec())));
+        assert_eq!(archive.read("textures/water.anim"), Some(Ok(anim)));
+        assert_eq!(archive.read("textures/lava.anim"), None);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn broken_archives_are_rejected(){
+        assert!(ZipArchive::from_bytes(b"not a zip".to_vec()).is_err());
+        let mut data = zip(&[("blocks.cfg", STORED, b"dirt", b"dirt")]);
+        //Cut the file data short
+        data.drain(30..40);
+        assert!(ZipArchive::from_bytes(data).is_err());
+
+        let archive = ZipArchive::from_bytes(zip(&[("blocks.cfg", 12, b"dirt", b"dirt")])).unwrap();
+        assert!(matches!(archive.read("blocks.cfg"), Some(Err(ResourceError::InvalidZip(_)))));
+    }
+
+    #[test]
+    fn truncated_archives_are_rejected(){
+        let anim = b"frame_time 0.5\n".repeat(3);
+        let data = zip(&[("textures/water.anim", DEFLATED, &anim, &DEFLATED_ANIM)]);
+        assert!(ZipArchive::from_bytes(data[..data.len() - 10].to_vec()).is_err());
+
+        //Half of the deflated stream, and an archive claiming less than the stream unpacks to
+        let archive = ZipArchive::from_bytes(zip(&[
+            ("half.anim", DEFLATED, &anim, &DEFLATED_ANIM[..10]),
+            ("short.anim", DEFLATED, b"frame_time 0.5\n", &DEFLATED_ANIM),
+        ])).unwrap();
+        assert!(matches!(archive.read("half.anim"), Some(Err(ResourceError::InvalidZip(_)))));
+        assert_eq!(archive.read("short.anim"), Some(Err(ResourceError::InvalidZip("file is larger than the archive says".to_string()))));
+    }
+
+    #[test]
+    fn wrong_crcs_are_rejected(){
+        let anim = b"frame_time 0.5\n".repeat(3);
+        let mut wrong = anim.clone();
+        wrong[0] = b'g';
+        let archive = ZipArchive::from_bytes(zip(&[
+            ("blocks.cfg", STORED, b"rock", b"dirt"),
+            ("textures/water.anim", DEFLATED, &wrong, &DEFLATED_ANIM),
+        ])).unwrap();
+        assert_eq!(archive.read("blocks.cfg"), Some(Err(ResourceError::InvalidZip("CRC doesn't match".to_string()))));
+        assert_eq!(archive.read("textures/water.anim"), Some(Err(ResourceError::InvalidZip("CRC doesn't match".to_string()))));
+    }
+}
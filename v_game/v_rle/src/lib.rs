@@ -110,89 +110,106 @@ impl<T> RLE<T>
     /// Get a range over a group of values, returns as a vector and not an iterator
     /// meaning that this get's an uncompressed range
     pub fn get_range(&self, index: Range<usize>) -> Result<Vec<T>, RLEError>{
-        if index.start >= self.raw_length || index.end > self.raw_length{
-            return Err(RLEError::OutOfRange);
-        }
-        let mut vec = Vec::new();
-        for item in self.iter().skip(index.start).take(index.end - index.start){
-            vec.push(item.clone());
+        let mut vec = Vec::with_capacity(index.end.saturating_sub(index.start));
+        for (num, data) in self.get_runs(index)?{
+            vec.extend(std::iter::repeat(data).take(num));
         }
         Ok(vec)
     }
 
-    /// Set a range to a singular value
-    pub fn set_range_singular(&mut self, item: T, index: Range<usize>) -> Result<(), RLEError>{
+    /// Get the runs covering a range, clipped so the counts add up to the length of the range
+    pub fn get_runs(&self, index: Range<usize>) -> Result<Vec<(usize, T)>, RLEError>{
         if index.start >= self.raw_length || index.end > self.raw_length{
             return Err(RLEError::OutOfRange);
         }
-        let mut current_start = 0;
-        let mut start_index = 0;
-        // Find the beginning placement of the range
-        for (i, (num, data)) in self.raw.iter().enumerate(){
-            let next = current_start + *num;
-            //It's somewhere *within* the current item
-            if next > index.start{
-                start_index = i;
-                break;
+        let mut runs = Vec::new();
+        let mut current = 0;
+        for (num, data) in self.raw.iter(){
+            let next = current + *num;
+            if next > index.start && current < index.end{
+                let from = current.max(index.start);
+                let to = next.min(index.end);
+                runs.push((to - from, data.clone()));
             }
-            current_start = next;
-        }
-        // Find the end placement of the range
-        let mut end_index = 0;
-        let mut current_end = 0;
-        for (i, (num, data)) in self.raw.iter().enumerate(){
-            let next = current_end + *num;
-            //It's somewhere *within* the current item
-            if next > index.end{
-                end_index = i;
+            if next >= index.end{
                 break;
             }
-            current_end = next;
+            current = next;
         }
+        Ok(runs)
+    }
 
-        let target_start_place = index.start - current_start;
-        let target_end_place = index.end - current_end;
-
-        let second_half_end = if target_end_place > 0 {self.raw[end_index].0 - target_end_place} else {0} ;
-
-        let start_item = self.raw[start_index].1.clone();
-        let end_item = self.raw[end_index].1.clone();
-
-        for i in (start_index..end_index + 1).rev(){
-            self.raw.remove(i);
+    /// Set a range to a singular value
+    pub fn set_range_singular(&mut self, item: T, index: Range<usize>) -> Result<(), RLEError>{
+        if index.start >= self.raw_length || index.end > self.raw_length{
+            return Err(RLEError::OutOfRange);
+        }
+        if index.start >= index.end{
+            return Ok(());
         }
 
-        self.raw.insert(start_index, (second_half_end, end_item));
-        self.raw.insert(start_index, (index.end - index.start, item));
-        self.raw.insert(start_index, (target_start_place, start_item));
-
+        // Cut the runs so the range starts and ends on run boundaries, then swap everything in between
+        let start_index = self.split_at(index.start);
+        let end_index = self.split_at(index.end);
+        self.raw.splice(start_index..end_index, std::iter::once((index.end - index.start, item)));
+        self.merge_neighbours(start_index);
+        Ok(())
+    }
 
-        if start_index + 2 < self.raw.len() && self.raw[start_index + 2].0 == 0{
-            self.raw.remove(start_index + 2);
-        }
-        if start_index + 1 < self.raw.len() && self.raw[start_index + 1].0 == 0{
-            self.raw.remove(start_index + 1);
-        }
-        if self.raw[start_index].0 == 0{
-            self.raw.remove(start_index);
+    /// Split the run containing index so that a run begins exactly at index,
+    /// returns the position of that run in the raw vector
+    fn split_at(&mut self, index: usize) -> usize{
+        let mut current = 0;
+        for i in 0..self.raw.len(){
+            let num = self.raw[i].0;
+            if current == index{
+                return i;
+            }
+            if current + num > index{
+                let first_half = index - current;
+                let data = self.raw[i].1.clone();
+                self.raw[i].0 = first_half;
+                self.raw.insert(i + 1, (num - first_half, data));
+                return i + 1;
+            }
+            current += num;
         }
+        self.raw.len()
+    }
 
-        if start_index + 2 < self.raw.len() && self.raw[start_index + 2].1 == self.raw[start_index + 1].1{
-            self.raw[start_index + 1].0 += self.raw[start_index + 2].0;
-            self.raw.remove(start_index + 2);
+    /// Merge the run at index with the runs on either side if they hold the same value
+    fn merge_neighbours(&mut self, index: usize){
+        if index + 1 < self.raw.len() && self.raw[index + 1].1 == self.raw[index].1{
+            self.raw[index].0 += self.raw[index + 1].0;
+            self.raw.remove(index + 1);
         }
-
-        if start_index + 1 < self.raw.len() && self.raw[start_index + 1].1 == self.raw[start_index].1{
-            self.raw[start_index].0 += self.raw[start_index + 1].0;
-            self.raw.remove(start_index + 1);
+        if index > 0 && index < self.raw.len() && self.raw[index - 1].1 == self.raw[index].1{
+            self.raw[index - 1].0 += self.raw[index].0;
+            self.raw.remove(index);
         }
+    }
 
-        if start_index < self.raw.len() && start_index > 0 && self.raw[start_index - 1].1 == self.raw[start_index].1{
-            self.raw[start_index - 1].0 += self.raw[start_index].0;
-            self.raw.remove(start_index);
+    /// Build from already compressed (count, value) pairs, empty runs are dropped and neighbours merged
+    pub fn from_runs<U>(runs: U) -> Self
+        where U: IntoIterator<Item = (usize, T)>{
+        let mut raw: Vec<(usize, T)> = Vec::new();
+        let mut raw_length = 0;
+        for (num, data) in runs{
+            if num == 0{
+                continue;
+            }
+            raw_length += num;
+            match raw.last_mut(){
+                Some(last) if last.1 == data => last.0 += num,
+                _ => raw.push((num, data)),
+            }
         }
+        Self{raw, raw_length}
+    }
 
-        Ok(())
+    /// Iterate over the compressed runs as (count, value) pairs
+    pub fn runs(&self) -> std::slice::Iter<(usize, T)>{
+        self.raw.iter()
     }
 
     pub fn compressed_len(&self) -> usize{
@@ -232,14 +249,13 @@ impl<'a, T> Iterator for RLEIterator<'a, T>
     type Item = T;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index >= self.rle.raw.len(){
-            return None;
-        }
-        if self.current >= self.rle.raw[self.index].0{
-            let item = self.rle.raw[self.index].1.clone();
+        while self.index < self.rle.raw.len(){
+            if self.current < self.rle.raw[self.index].0{
+                self.current += 1;
+                return Some(self.rle.raw[self.index].1.clone());
+            }
             self.current = 0;
             self.index += 1;
-            return Some(item);
         }
         None
     }
@@ -279,4 +295,33 @@ mod tests {
             println!("number: {}\t item: {}", number, item);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn set_range_matches_uncompressed() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut r: RLE<usize> = RLE::from(repeat(0).take(1000));
+        let mut plain = vec![0; 1000];
+        for _ in 0..2000{
+            let start = rng.gen::<usize>() % 1000;
+            let end = start + 1 + rng.gen::<usize>() % (1000 - start);
+            let item = rng.gen::<usize>() % 3;
+            r.set_range_singular(item, start..end).unwrap();
+            for i in start..end{
+                plain[i] = item;
+            }
+            assert_eq!(r.get_range(0..1000).unwrap(), plain);
+            assert_eq!(r.iter().collect::<Vec<usize>>(), plain);
+            assert!(r.runs().all(|(num, _)| *num > 0));
+            assert!(r.raw.windows(2).all(|w| w[0].1 != w[1].1));
+        }
+    }
+
+    #[test]
+    fn get_runs_clips_to_range() {
+        let mut r: RLE<usize> = RLE::from(repeat(0).take(30));
+        r.set_range_singular(1, 10..20).unwrap();
+        assert_eq!(r.get_runs(5..25).unwrap(), vec![(5, 0), (10, 1), (5, 0)]);
+        assert_eq!(r.get_runs(12..14).unwrap(), vec![(2, 1)]);
+        assert_eq!(r.get_runs(0..30).unwrap().iter().map(|(n, _)| n).sum::<usize>(), 30);
+    }
+}
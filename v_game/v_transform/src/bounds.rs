@@ -0,0 +1,126 @@
+use crate::*;
+
+/// An axis aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb{
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb{
+    pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Self{
+        Aabb{min, max}
+    }
+
+    /// The smallest box holding every point, None when there aren't any
+    pub fn from_points<I: IntoIterator<Item = Vector3<f32>>>(points: I) -> Option<Self>{
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(Aabb::new(first, first), |bounds, point|{
+            Aabb::new(bounds.min.inf(&point), bounds.max.sup(&point))
+        }))
+    }
+
+    pub fn center(&self) -> Vector3<f32>{
+        (self.min + self.max) / 2.0
+    }
+
+    /// Half the size of the box along each axis
+    pub fn extents(&self) -> Vector3<f32>{
+        (self.max - self.min) / 2.0
+    }
+
+    /// The box around this one after it's been moved by matrix, which is bigger than the box when there's rotation
+    pub fn transformed(&self, matrix: &Matrix4<f32>) -> Self{
+        let center = matrix.transform_point(&self.center().into()).coords;
+        let extents = self.extents();
+        let mut new_extents = Vector3::zeros();
+        for row in 0..3{
+            for column in 0..3{
+                new_extents[row] += matrix[(row, column)].abs() * extents[column];
+            }
+        }
+        Aabb::new(center - new_extents, center + new_extents)
+    }
+}
+
+/// The six planes around what a camera can see, normals point inwards
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum{
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum{
+    /// Pull the planes out of a projection times view matrix, expects OpenGL's -1 to 1 clip depth
+    pub fn from_matrix(view_projection: &Matrix4<f32>) -> Self{
+        let row = |index: usize| Vector4::new(
+            view_projection[(index, 0)],
+            view_projection[(index, 1)],
+            view_projection[(index, 2)],
+            view_projection[(index, 3)]);
+        let w = row(3);
+        let mut planes = [w + row(0), w - row(0), w + row(1), w - row(1), w + row(2), w - row(2)];
+        for plane in planes.iter_mut(){
+            *plane /= plane.xyz().norm();
+        }
+        Frustum{planes}
+    }
+
+    /// False only when the box is entirely outside one of the planes, boxes near corners can pass without being seen
+    pub fn intersects(&self, bounds: &Aabb) -> bool{
+        self.planes.iter().all(|plane|{
+            //The corner furthest along the plane's normal
+            let corner = Vector3::new(
+                if plane[0] >= 0.0 {bounds.max[0]} else {bounds.min[0]},
+                if plane[1] >= 0.0 {bounds.max[1]} else {bounds.min[1]},
+                if plane[2] >= 0.0 {bounds.max[2]} else {bounds.min[2]});
+            plane.xyz().dot(&corner) + plane[3] >= 0.0
+        })
+    }
+}
+
+/// Whether any of bounds, in world space, could be on screen for a camera with this projection times view matrix
+pub fn in_frustum(view_projection: &Matrix4<f32>, bounds: &Aabb) -> bool{
+    Frustum::from_matrix(view_projection).intersects(bounds)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn unit_box(x: f32, y: f32, z: f32) -> Aabb{
+        Aabb::new(Vector3::new(x - 0.5, y - 0.5, z - 0.5), Vector3::new(x + 0.5, y + 0.5, z + 0.5))
+    }
+
+    #[test]
+    fn culls_boxes_outside_the_frustum(){
+        //Camera at the origin looking down -z with a 90 degree field of view
+        let projection = glm::perspective(1.0, std::f32::consts::FRAC_PI_2, 0.1, 100.0);
+        let view_projection = projection * Matrix4::identity();
+
+        assert!(in_frustum(&view_projection, &unit_box(0.0, 0.0, -10.0)));
+        assert!(!in_frustum(&view_projection, &unit_box(0.0, 0.0, 10.0)));
+        assert!(!in_frustum(&view_projection, &unit_box(0.0, 0.0, -200.0)));
+        assert!(!in_frustum(&view_projection, &unit_box(20.0, 0.0, -10.0)));
+        assert!(!in_frustum(&view_projection, &unit_box(0.0, -20.0, -10.0)));
+        //Straddling the edge of the view still counts
+        assert!(in_frustum(&view_projection, &unit_box(10.4, 0.0, -10.0)));
+
+        //Turning the camera around brings the box behind it into view
+        let turned = glm::look_at(&Vector3::zeros(), &Vector3::new(0.0, 0.0, 1.0), &Vector3::new(0.0, 1.0, 0.0));
+        assert!(in_frustum(&(projection * turned), &unit_box(0.0, 0.0, 10.0)));
+    }
+
+    #[test]
+    fn boxes_follow_transforms(){
+        let bounds = Aabb::from_points(vec![Vector3::new(0.0, 0.0, 0.0), Vector3::new(2.0, 1.0, -1.0)]).unwrap();
+        assert_eq!(bounds, Aabb::new(Vector3::new(0.0, 0.0, -1.0), Vector3::new(2.0, 1.0, 0.0)));
+        assert_eq!(Aabb::from_points(Vec::new()), None);
+
+        let moved = bounds.transformed(&glm::translation(&Vector3::new(10.0, 0.0, 0.0)));
+        assert_eq!(moved, Aabb::new(Vector3::new(10.0, 0.0, -1.0), Vector3::new(12.0, 1.0, 0.0)));
+
+        let turned = bounds.transformed(&glm::rotation(std::f32::consts::FRAC_PI_2, &Vector3::new(0.0, 1.0, 0.0)));
+        assert!((turned.extents() - Vector3::new(0.5, 0.5, 1.0)).norm() < 0.0001);
+    }
+}
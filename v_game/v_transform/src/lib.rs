@@ -6,6 +6,12 @@ use nalgebra_glm as glm;
 use glm::{translation, scaling, rotation};
 use nalgebra_glm::{TVec3, RealField, look_at};
 use nalgebra::{UnitQuaternion, Unit};
+use std::time::Duration;
+
+mod bounds;
+pub use bounds::*;
+mod sky;
+pub use sky::*;
 
 pub struct Position(Vector3<f32>, bool);
 
@@ -211,4 +217,133 @@ impl<'a> System<'a> for TransformSystem{
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Frame and tick timing. Systems in the fixed update should use fixed_delta,
+/// systems that run once per rendered frame use delta.
+pub struct Time{
+    delta: f32,
+    elapsed: f64,
+    frame_count: u64,
+    fixed_delta: f32,
+    tick_count: u64,
+    alpha: f32,
+}
+
+impl Time{
+    pub fn new(ticks_per_second: f64) -> Self{
+        Time{
+            delta: 0.0,
+            elapsed: 0.0,
+            frame_count: 0,
+            fixed_delta: (1.0 / ticks_per_second) as f32,
+            tick_count: 0,
+            alpha: 0.0,
+        }
+    }
+
+    /// Real seconds the last frame took
+    pub fn delta(&self) -> f32{
+        self.delta
+    }
+
+    /// Real seconds since the game started
+    pub fn elapsed(&self) -> f64{
+        self.elapsed
+    }
+
+    pub fn frame_count(&self) -> u64{
+        self.frame_count
+    }
+
+    /// Seconds simulated by every tick
+    pub fn fixed_delta(&self) -> f32{
+        self.fixed_delta
+    }
+
+    pub fn tick_length(&self) -> Duration{
+        Duration::from_secs_f32(self.fixed_delta)
+    }
+
+    pub fn tick_count(&self) -> u64{
+        self.tick_count
+    }
+
+    /// How far the current frame is between the last tick and the next one, from 0 to 1
+    pub fn alpha(&self) -> f32{
+        self.alpha
+    }
+
+    pub fn begin_tick(&mut self){
+        self.tick_count += 1;
+    }
+
+    pub fn begin_frame(&mut self, frame_time: Duration, alpha: f32){
+        self.delta = frame_time.as_secs_f32();
+        self.elapsed += frame_time.as_secs_f64();
+        self.frame_count += 1;
+        self.alpha = alpha;
+    }
+}
+
+impl Default for Time{
+    fn default() -> Self{
+        Self::new(60.0)
+    }
+}
+
+/// Where an entity was at the start of the last tick, so frames between ticks
+/// can draw it part way between there and where it is now
+#[derive(Default)]
+pub struct Interpolation(Option<(Vector3<f32>, UnitQuaternion<f32>)>);
+
+impl Component for Interpolation{
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Runs first thing every tick to remember where interpolated entities start from
+pub struct InterpolationSnapshotSystem;
+
+impl<'a> System<'a> for InterpolationSnapshotSystem{
+    type SystemData = (ReadStorage<'a, Position>,
+                       ReadStorage<'a, Rotation>,
+                       WriteStorage<'a, Interpolation>
+    );
+
+    fn run(&mut self, (positions, rotations, mut interpolations): Self::SystemData){
+        for (position, rotation, interpolation) in (&positions, &rotations, &mut interpolations).join(){
+            interpolation.0 = Some((position.0, rotation.0));
+        }
+    }
+}
+
+/// Runs every frame to blend interpolated transforms between the last two ticks
+pub struct InterpolationSystem;
+
+impl<'a> System<'a> for InterpolationSystem{
+    type SystemData = (Read<'a, Time>,
+                       ReadStorage<'a, Position>,
+                       ReadStorage<'a, Rotation>,
+                       ReadStorage<'a, Scale>,
+                       ReadStorage<'a, Interpolation>,
+                       WriteStorage<'a, TransformMatrix>
+    );
+
+    fn run(&mut self, (time, positions, rotations, scales, interpolations, mut transforms): Self::SystemData){
+        let alpha = time.alpha();
+        for (position, rotation, scale, interpolation, transform) in (&positions, &rotations, scales.maybe(), &interpolations, &mut transforms).join(){
+            let (previous_position, previous_rotation) = match interpolation.0{
+                Some(previous) => previous,
+                None => continue,
+            };
+            let blended_position = previous_position.lerp(&position.0, alpha);
+            let blended_rotation = previous_rotation.slerp(&rotation.0, alpha);
+            let mut rotation_matrix = Matrix4::from(blended_rotation.to_rotation_matrix());
+            rotation_matrix[15] = 1.0;
+            transform.0 = translation(&blended_position) * rotation_matrix;
+            if let Some(scale) = scale{
+                transform.0 *= model_matrix_s(scale);
+            }
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use nalgebra::Vector3;
+
+/// How far through the day it is, 0 and 1 are midnight and 0.5 is noon.
+/// The renderer lights the world and draws the sky from it, without needing a GL context to work it out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeOfDay{
+    fraction: f32,
+}
+
+impl TimeOfDay{
+    pub fn new(fraction: f32) -> Self{
+        TimeOfDay{
+            fraction: fraction.rem_euclid(1.0),
+        }
+    }
+
+    pub fn fraction(&self) -> f32{
+        self.fraction
+    }
+
+    /// Points towards the sun, which rises in +x at 0.25, is overhead at noon and sets in -x at 0.75.
+    /// Its path leans towards +z so it's never exactly overhead and faces always differ in brightness.
+    pub fn sun_direction(&self) -> Vector3<f32>{
+        let angle = (self.fraction - 0.25) * std::f32::consts::PI * 2.0;
+        Vector3::new(angle.cos(), angle.sin(), 0.3).normalize()
+    }
+
+    /// The moon is always opposite the sun
+    pub fn moon_direction(&self) -> Vector3<f32>{
+        -self.sun_direction()
+    }
+
+    /// 1 with the sun well up, 0 once it's well below the horizon, easing between around dawn and dusk
+    pub fn daylight(&self) -> f32{
+        let t = ((self.sun_direction()[1] + 0.1) / 0.3).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+
+    /// Everything the shaders need to light the world and draw the sky at this time
+    pub fn lighting(&self) -> SkyLighting{
+        let daylight = self.daylight();
+        //Strongest while the sun is near the horizon and up
+        let sunset = (1.0 - self.sun_direction()[1].abs() * 4.0).max(0.0) * daylight.max(0.25);
+        let horizon = lerp(NIGHT_HORIZON, DAY_HORIZON, daylight).lerp(&Vector3::from(SUNSET_HORIZON), sunset * 0.6);
+        //The moon lights the world once the sun is down
+        let (light_direction, light_color) = if daylight > 0.0{
+            (self.sun_direction(), lerp(SUNSET_LIGHT, SUN_LIGHT, (daylight - sunset * 0.5).max(0.0)) * daylight)
+        } else {
+            (self.moon_direction(), Vector3::from(MOON_LIGHT))
+        };
+        SkyLighting{
+            sun_direction: self.sun_direction(),
+            moon_direction: self.moon_direction(),
+            light_direction,
+            light_color,
+            ambient_color: lerp(NIGHT_AMBIENT, DAY_AMBIENT, daylight),
+            zenith_color: lerp(NIGHT_ZENITH, DAY_ZENITH, daylight),
+            horizon_color: horizon,
+        }
+    }
+}
+
+impl Default for TimeOfDay{
+    /// Mid morning
+    fn default() -> Self{
+        TimeOfDay::new(0.35)
+    }
+}
+
+const DAY_ZENITH: [f32; 3] = [0.25, 0.45, 0.85];
+const DAY_HORIZON: [f32; 3] = [0.7, 0.8, 0.95];
+const NIGHT_ZENITH: [f32; 3] = [0.0, 0.0, 0.02];
+const NIGHT_HORIZON: [f32; 3] = [0.02, 0.03, 0.07];
+const SUNSET_HORIZON: [f32; 3] = [0.95, 0.5, 0.25];
+const SUN_LIGHT: [f32; 3] = [1.0, 0.95, 0.85];
+const SUNSET_LIGHT: [f32; 3] = [1.0, 0.6, 0.35];
+const MOON_LIGHT: [f32; 3] = [0.1, 0.12, 0.2];
+const DAY_AMBIENT: [f32; 3] = [0.35, 0.38, 0.45];
+const NIGHT_AMBIENT: [f32; 3] = [0.05, 0.05, 0.09];
+
+fn lerp(from: [f32; 3], to: [f32; 3], amount: f32) -> Vector3<f32>{
+    Vector3::from(from).lerp(&Vector3::from(to), amount)
+}
+
+/// Light and sky colours for a time of day, in linear colour
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SkyLighting{
+    pub sun_direction: Vector3<f32>,
+    pub moon_direction: Vector3<f32>,
+    /// Towards whichever of the sun or moon is lighting the world
+    pub light_direction: Vector3<f32>,
+    pub light_color: Vector3<f32>,
+    /// Light every face gets whatever way it faces
+    pub ambient_color: Vector3<f32>,
+    /// Sky colour straight up
+    pub zenith_color: Vector3<f32>,
+    /// Sky colour at the horizon, which is also the fog colour
+    pub horizon_color: Vector3<f32>,
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn sun_follows_the_day(){
+        let noon = TimeOfDay::new(0.5);
+        let midnight = TimeOfDay::new(1.0);
+        assert_eq!(midnight.fraction(), 0.0);
+        assert!(noon.sun_direction()[1] > 0.9);
+        assert!(midnight.sun_direction()[1] < -0.9);
+        assert!(midnight.moon_direction()[1] > 0.9);
+        assert!(TimeOfDay::new(0.3).sun_direction()[0] > 0.0);
+        assert!(TimeOfDay::new(0.7).sun_direction()[0] < 0.0);
+
+        assert_eq!(noon.daylight(), 1.0);
+        assert_eq!(midnight.daylight(), 0.0);
+        let dawn = TimeOfDay::new(0.25).daylight();
+        assert!(dawn > 0.0 && dawn < 1.0);
+    }
+
+    #[test]
+    fn lighting_dims_at_night(){
+        let noon = TimeOfDay::new(0.5).lighting();
+        let midnight = TimeOfDay::new(0.0).lighting();
+        assert!(noon.light_color.sum() > midnight.light_color.sum());
+        assert!(noon.ambient_color.sum() > midnight.ambient_color.sum());
+        assert!(noon.zenith_color.sum() > midnight.zenith_color.sum());
+        assert_eq!(noon.light_direction, noon.sun_direction);
+        assert_eq!(midnight.light_direction, midnight.moon_direction);
+
+        //Sunset reddens the horizon
+        let dusk = TimeOfDay::new(0.74).lighting();
+        assert!(dusk.horizon_color[0] > dusk.horizon_color[2]);
+        assert!(noon.horizon_color[0] < noon.horizon_color[2]);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::*;
+use std::fmt;
+
+/// Where block definitions are in a resource pack
+pub const BLOCKS_FILE: &str = "blocks.cfg";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockDefinitionError{
+    Resource(ResourceError),
+    InvalidLine(usize),
+    /// A line names a texture that isn't in the texture array
+    UnknownTexture(usize, String),
+}
+
+impl fmt::Display for BlockDefinitionError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            BlockDefinitionError::Resource(error) => write!(f, "{}", error),
+            BlockDefinitionError::InvalidLine(line) => write!(f, "invalid line {} in {}", line, BLOCKS_FILE),
+            BlockDefinitionError::UnknownTexture(line, texture) => write!(f, "unknown texture {} on line {} of {}", texture, line, BLOCKS_FILE),
+        }
+    }
+}
+
+/// Faces a blocks.cfg line can set
+fn faces_from_name(name: &str) -> Option<&'static [Direction]>{
+    match name{
+        "top" => Some(&[Direction::Top]),
+        "bottom" => Some(&[Direction::Bottom]),
+        "right" => Some(&[Direction::Right]),
+        "left" => Some(&[Direction::Left]),
+        "front" => Some(&[Direction::Front]),
+        "back" => Some(&[Direction::Back]),
+        "sides" => Some(&[Direction::Right, Direction::Left, Direction::Front, Direction::Back]),
+        "all" => Some(&Direction::ALL),
+        _ => None,
+    }
+}
+
+/// The texture array layer each face of each block type is drawn with, ChunkMesherSystem meshes chunks with it.
+/// Starts out as BlockType::texture_file, resource packs can change it with "<block> <faces> <texture>" lines in
+/// blocks.cfg where faces is top, bottom, right, left, front, back, sides or all and texture is a file like "rock.png".
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockTextures{
+    /// Indexed by block id then Direction::index
+    faces: Vec<[Option<u32>; 6]>,
+}
+
+impl Default for BlockTextures{
+    /// The layers TEXTURE_FILES get when none of them are animated
+    fn default() -> Self{
+        Self::from_layers(|name| TEXTURE_FILES.iter().position(|file| *file == name).map(|layer| layer as u32))
+    }
+}
+
+impl BlockTextures{
+    /// Each block's default textures looked up by name, animated textures take more than one layer so layers can't be assumed
+    pub fn from_layers<F>(layer: F) -> Self
+    where F: Fn(&str) -> Option<u32>{
+        let mut faces = Vec::new();
+        while let Some(block) = BlockType::from_id(faces.len() as u8){
+            let mut layers = [None; 6];
+            for direction in Direction::ALL.iter(){
+                layers[direction.index()] = block.texture_file(*direction).and_then(&layer);
+            }
+            faces.push(layers);
+        }
+        BlockTextures{faces}
+    }
+
+    pub fn get(&self, block: BlockType, direction: Direction) -> Option<u32>{
+        self.faces.get(block as usize).and_then(|layers| layers[direction.index()])
+    }
+
+    pub fn set(&mut self, block: BlockType, direction: Direction, layer: Option<u32>){
+        if let Some(layers) = self.faces.get_mut(block as usize){
+            layers[direction.index()] = layer;
+        }
+    }
+
+    /// The defaults changed by blocks.cfg lines, layer finds a texture's first layer from its file name
+    pub fn parse<F>(text: &str, layer: F) -> Result<Self, BlockDefinitionError>
+    where F: Fn(&str) -> Option<u32>{
+        let mut textures = Self::from_layers(&layer);
+        for (number, line) in text.lines().enumerate(){
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#'){
+                continue;
+            }
+            let invalid = BlockDefinitionError::InvalidLine(number + 1);
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() != 3{
+                return Err(invalid);
+            }
+            let block = BlockType::from_name(parts[0]).filter(|block| !block.is_transparent()).ok_or_else(|| invalid.clone())?;
+            let faces = faces_from_name(parts[1]).ok_or(invalid)?;
+            let texture = layer(parts[2]).ok_or_else(|| BlockDefinitionError::UnknownTexture(number + 1, parts[2].to_string()))?;
+            for direction in faces.iter(){
+                textures.set(block, *direction, Some(texture));
+            }
+        }
+        Ok(textures)
+    }
+
+    /// BLOCKS_FILE from the highest resource pack that has one, the defaults if none do
+    pub fn load<F>(resources: &ResourceManager, layer: F) -> Result<Self, BlockDefinitionError>
+    where F: Fn(&str) -> Option<u32>{
+        match resources.read_to_string(BLOCKS_FILE){
+            Ok(text) => Self::parse(&text, layer),
+            Err(ResourceError::NotFound(_)) => Ok(Self::from_layers(layer)),
+            Err(error) => Err(BlockDefinitionError::Resource(error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn layer(name: &str) -> Option<u32>{
+        TEXTURE_FILES.iter().chain(["water.png"].iter()).position(|file| *file == name).map(|layer| layer as u32)
+    }
+
+    /// dirt.png animated over two layers, so everything after it is a layer further on
+    fn animated_layer(name: &str) -> Option<u32>{
+        layer(name).map(|layer| if layer > 0 {layer + 1} else {layer})
+    }
+
+    #[test]
+    fn definitions_override_the_defaults(){
+        let defaults = BlockTextures::default();
+        assert_eq!(defaults.get(BlockType::Grass, Direction::Top), Some(1));
+        assert_eq!(defaults.get(BlockType::Grass, Direction::Left), Some(0));
+        assert_eq!(defaults.get(BlockType::Air, Direction::Top), None);
+        assert_eq!(BlockTextures::from_layers(layer), defaults);
+
+        let textures = BlockTextures::parse("# stone everywhere\nrock all water.png\ngrass sides rock.png\n\ngrass top dirt.png\n", animated_layer).unwrap();
+        assert!(Direction::ALL.iter().all(|direction| textures.get(BlockType::Rock, *direction) == Some(4)));
+        assert_eq!(textures.get(BlockType::Grass, Direction::Front), Some(3));
+        assert_eq!(textures.get(BlockType::Grass, Direction::Top), Some(0));
+        assert_eq!(textures.get(BlockType::Grass, Direction::Bottom), Some(0));
+        assert_eq!(textures.get(BlockType::Dirt, Direction::Top), Some(0));
+
+        //Blocks left alone still find their textures by name
+        let textures = BlockTextures::parse("dirt all water.png\n", animated_layer).unwrap();
+        assert_eq!(textures.get(BlockType::Grass, Direction::Top), Some(2));
+        assert!(Direction::ALL.iter().all(|direction| textures.get(BlockType::Rock, *direction) == Some(3)));
+        assert_eq!(BlockTextures::load(&ResourceManager::new(), animated_layer).unwrap().get(BlockType::Rock, Direction::Top), Some(3));
+    }
+
+    #[test]
+    fn bad_definitions_are_reported(){
+        assert_eq!(BlockTextures::parse("rock all", layer), Err(BlockDefinitionError::InvalidLine(1)));
+        assert_eq!(BlockTextures::parse("\nmarble all rock.png", layer), Err(BlockDefinitionError::InvalidLine(2)));
+        assert_eq!(BlockTextures::parse("air all rock.png", layer), Err(BlockDefinitionError::InvalidLine(1)));
+        assert_eq!(BlockTextures::parse("rock middle rock.png", layer), Err(BlockDefinitionError::InvalidLine(1)));
+        assert_eq!(BlockTextures::parse("rock all lava.png", layer), Err(BlockDefinitionError::UnknownTexture(1, "lava.png".to_string())));
+
+        //No blocks.cfg in any pack keeps the defaults
+        assert_eq!(BlockTextures::load(&ResourceManager::new(), layer), Ok(BlockTextures::default()));
+        let mut resources = ResourceManager::new();
+        resources.push(ResourcePack::Memory(vec![(BLOCKS_FILE.to_string(), b"dirt top grass.png".to_vec())].into_iter().collect()));
+        assert_eq!(BlockTextures::load(&resources, layer).unwrap().get(BlockType::Dirt, Direction::Top), Some(1));
+    }
+}
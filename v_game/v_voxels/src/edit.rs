@@ -0,0 +1,455 @@
+use crate::*;
+
+/// Largest radius sphere and cylinder brushes reach to, bigger ones are cut down to it
+pub const MAX_BRUSH_RADIUS: f32 = 64.0;
+/// Tallest a cylinder brush goes
+pub const MAX_BRUSH_HEIGHT: usize = 256;
+
+/// An axis aligned box of blocks in global block coordinates, both corners are inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region{
+    pub min: Vector3<i32>,
+    pub max: Vector3<i32>,
+}
+
+impl Region{
+    pub fn from_corners(a: Vector3<i32>, b: Vector3<i32>) -> Self{
+        Region{
+            min: a.zip_map(&b, |a, b| a.min(b)),
+            max: a.zip_map(&b, |a, b| a.max(b)),
+        }
+    }
+
+    pub fn size(&self) -> Vector3<usize>{
+        (self.max - self.min).map(|x| x as usize + 1)
+    }
+
+    pub fn volume(&self) -> usize{
+        let size = self.size();
+        size[0] * size[1] * size[2]
+    }
+
+    pub fn contains(&self, block: &Vector3<i32>) -> bool{
+        (0..3).all(|axis| block[axis] >= self.min[axis] && block[axis] <= self.max[axis])
+    }
+
+    pub fn translate(&self, offset: Vector3<i32>) -> Self{
+        Region{
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+}
+
+/// The two corners picked by the player, a region exists once both are set
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Selection{
+    pub first: Option<Vector3<i32>>,
+    pub second: Option<Vector3<i32>>,
+}
+
+impl Selection{
+    pub fn region(&self) -> Option<Region>{
+        Some(Region::from_corners(self.first?, self.second?))
+    }
+}
+
+/// A dense box of blocks lifted out of the world.
+/// The offset is where the box starts relative to the point it was copied from,
+/// so pasting somewhere else keeps the same placement relative to the paste point.
+#[derive(Debug, Clone)]
+pub struct BlockVolume{
+    size: Vector3<usize>,
+    offset: Vector3<i32>,
+    blocks: Vec<BlockType>,
+}
+
+impl BlockVolume{
+    pub fn new(size: Vector3<usize>, offset: Vector3<i32>, blocks: Vec<BlockType>) -> Self{
+        assert_eq!(blocks.len(), size[0] * size[1] * size[2]);
+        BlockVolume{size, offset, blocks}
+    }
+
+    pub fn filled(size: Vector3<usize>, block: BlockType) -> Self{
+        Self::new(size, Vector3::new(0, 0, 0), vec![block; size[0] * size[1] * size[2]])
+    }
+
+    pub fn size(&self) -> Vector3<usize>{
+        self.size
+    }
+
+    pub fn offset(&self) -> Vector3<i32>{
+        self.offset
+    }
+
+    pub fn set_offset(&mut self, offset: Vector3<i32>){
+        self.offset = offset;
+    }
+
+    fn index(&self, position: Vector3<usize>) -> usize{
+        position[0] + position[1] * self.size[0] + position[2] * self.size[0] * self.size[1]
+    }
+
+    pub fn get(&self, position: Vector3<usize>) -> BlockType{
+        self.blocks[self.index(position)]
+    }
+
+    pub fn set(&mut self, position: Vector3<usize>, block: BlockType){
+        let index = self.index(position);
+        self.blocks[index] = block;
+    }
+
+    /// Rotate around the y axis through the copy point, one quarter turn is 90 degrees counter clockwise from above
+    pub fn rotate_y(&self, quarter_turns: i32) -> Self{
+        let mut rotated = self.clone();
+        for _ in 0..quarter_turns.rem_euclid(4){
+            rotated = rotated.transformed(|p| Vector3::new(p[2], p[1], -p[0]));
+        }
+        rotated
+    }
+
+    /// Flip along one axis through the copy point
+    pub fn mirror(&self, axis: usize) -> Self{
+        self.transformed(|mut p| {
+            p[axis] = -p[axis];
+            p
+        })
+    }
+
+    /// Apply a signed axis permutation to every block position relative to the copy point
+    fn transformed<F>(&self, f: F) -> Self
+        where F: Fn(Vector3<i32>) -> Vector3<i32>{
+        let first = f(self.offset);
+        let last = f(self.offset + self.size.map(|x| x as i32 - 1));
+        let min = first.zip_map(&last, |a, b| a.min(b));
+        let size = first.zip_map(&last, |a, b| (a.max(b) - a.min(b)) as usize + 1);
+
+        let mut transformed = BlockVolume::new(size, min, vec![BlockType::Air; self.blocks.len()]);
+        for z in 0..self.size[2]{
+            for y in 0..self.size[1]{
+                for x in 0..self.size[0]{
+                    let position = Vector3::new(x, y, z);
+                    let moved = f(self.offset + position.map(|x| x as i32)) - min;
+                    transformed.set(moved.map(|x| x as usize), self.get(position));
+                }
+            }
+        }
+        transformed
+    }
+
+    /// The blocks along the x axis at a given y and z, compressed into runs
+    fn row_runs(&self, y: usize, z: usize, skip_air: bool) -> Vec<(usize, Option<BlockType>)>{
+        let start = self.index(Vector3::new(0, y, z));
+        let mut runs: Vec<(usize, Option<BlockType>)> = Vec::new();
+        for block in self.blocks[start..start + self.size[0]].iter(){
+            let block = if skip_air && *block == BlockType::Air {None} else {Some(*block)};
+            match runs.last_mut(){
+                Some((count, last)) if *last == block => *count += 1,
+                _ => runs.push((1, block)),
+            }
+        }
+        runs
+    }
+}
+
+impl ChunkStorage{
+    /// Get a block by its global block coordinate, missing chunks read as air
+    pub fn get_block_at(&self, block: Vector3<i32>) -> BlockType{
+        self.read_row(block, 1)[0].1
+    }
+
+    /// Set a block by its global block coordinate
+    pub fn set_block_at(&mut self, block: Vector3<i32>, block_type: &BlockType){
+        self.fill_run(block, 1, block_type);
+    }
+
+    /// Write runs of blocks along the x axis starting at a global block coordinate.
+    /// A run of None leaves the blocks it covers untouched.
+    pub fn write_row(&mut self, start: Vector3<i32>, runs: &[(usize, Option<BlockType>)]){
+        let mut x = start[0];
+        for (length, block) in runs.iter(){
+            if let Some(block) = block{
+                self.fill_run(Vector3::new(x, start[1], start[2]), *length, block);
+            }
+            x += *length as i32;
+        }
+    }
+
+    /// Read length blocks along the x axis starting at a global block coordinate as runs.
+    /// Chunks that don't exist yet read as air and aren't created.
+    pub fn read_row(&self, start: Vector3<i32>, length: usize) -> Vec<(usize, BlockType)>{
+        let mut runs: Vec<(usize, BlockType)> = Vec::new();
+        let mut read = 0;
+        while read < length{
+            let (chunk_coord, block_coord) = Self::block_to_chunk_block(&(start + Vector3::new(read as i32, 0, 0)));
+            let run = (CHUNK_SIZE - block_coord[0]).min(length - read);
+            let chunk_runs = match self.map.get(&chunk_coord){
+                Some(chunk) => chunk.get_runs(block_coord, run),
+                None => vec![(run, BlockType::Air)],
+            };
+            for (count, block) in chunk_runs{
+                match runs.last_mut(){
+                    Some((last_count, last)) if *last == block => *last_count += count,
+                    _ => runs.push((count, block)),
+                }
+            }
+            read += run;
+        }
+        runs
+    }
+
+    /// Fill a run along the x axis, split up where it crosses chunk boundaries
+    fn fill_run(&mut self, start: Vector3<i32>, length: usize, block: &BlockType){
+        let mut written = 0;
+        while written < length{
+            let (chunk_coord, block_coord) = Self::block_to_chunk_block(&(start + Vector3::new(written as i32, 0, 0)));
+            let run = (CHUNK_SIZE - block_coord[0]).min(length - written);
+            if !self.map.contains_key(&chunk_coord){
+                self.map.insert(chunk_coord, Chunk::new());
+                self.needed_chunks.lock().unwrap().push(chunk_coord);
+            }
+            self.map.get_mut(&chunk_coord).unwrap().set_run(block_coord, run, block);
+            self.mark_run_changed(chunk_coord, block_coord, run);
+            written += run;
+        }
+    }
+
+    /// Flag a chunk for remeshing along with any neighbours the run touches
+    fn mark_run_changed(&mut self, chunk_coord: Vector3<i32>, block_coord: Vector3<usize>, length: usize){
+        self.changed_chunks.insert(chunk_coord);
+        let end = block_coord + Vector3::new(length - 1, 0, 0);
+        for axis in 0..3{
+            let mut offset = Vector3::new(0, 0, 0);
+            if block_coord[axis] == 0{
+                offset[axis] = -1;
+                self.changed_chunks.insert(chunk_coord + offset);
+            }
+            if end[axis] == CHUNK_SIZE - 1{
+                offset[axis] = 1;
+                self.changed_chunks.insert(chunk_coord + offset);
+            }
+        }
+    }
+
+    fn for_each_row<F>(region: &Region, mut f: F)
+        where F: FnMut(i32, i32){
+        for z in region.min[2]..=region.max[2]{
+            for y in region.min[1]..=region.max[1]{
+                f(y, z);
+            }
+        }
+    }
+
+    /// Set every block in the region, returns the number of blocks set
+    pub fn fill(&mut self, region: &Region, block: &BlockType) -> usize{
+        let length = region.size()[0];
+        Self::for_each_row(region, |y, z|{
+            self.write_row(Vector3::new(region.min[0], y, z), &[(length, Some(*block))]);
+        });
+        region.volume()
+    }
+
+    /// Swap every block of one type in the region for another, returns the number of blocks replaced
+    pub fn replace(&mut self, region: &Region, from: &BlockType, to: &BlockType) -> usize{
+        let length = region.size()[0];
+        let mut replaced = 0;
+        Self::for_each_row(region, |y, z|{
+            let start = Vector3::new(region.min[0], y, z);
+            let runs: Vec<(usize, Option<BlockType>)> = self.read_row(start, length).into_iter()
+                .map(|(count, block)| if block == *from {
+                    replaced += count;
+                    (count, Some(*to))
+                } else {
+                    (count, None)
+                })
+                .collect();
+            self.write_row(start, &runs);
+        });
+        replaced
+    }
+
+    /// Turn the region into a closed box of block with air inside
+    pub fn hollow(&mut self, region: &Region, block: &BlockType){
+        let length = region.size()[0];
+        Self::for_each_row(region, |y, z|{
+            let runs = if y == region.min[1] || y == region.max[1] || z == region.min[2] || z == region.max[2]{
+                vec![(length, Some(*block))]
+            } else {
+                Self::edge_runs(length, Some(*block), Some(BlockType::Air))
+            };
+            self.write_row(Vector3::new(region.min[0], y, z), &runs);
+        });
+    }
+
+    /// Build the four vertical sides of the region, leaving the inside alone
+    pub fn walls(&mut self, region: &Region, block: &BlockType){
+        let length = region.size()[0];
+        Self::for_each_row(region, |y, z|{
+            let runs = if z == region.min[2] || z == region.max[2]{
+                vec![(length, Some(*block))]
+            } else {
+                Self::edge_runs(length, Some(*block), None)
+            };
+            self.write_row(Vector3::new(region.min[0], y, z), &runs);
+        });
+    }
+
+    fn edge_runs(length: usize, edge: Option<BlockType>, middle: Option<BlockType>) -> Vec<(usize, Option<BlockType>)>{
+        if length <= 2{
+            vec![(length, edge)]
+        } else {
+            vec![(1, edge), (length - 2, middle), (1, edge)]
+        }
+    }
+
+    /// Half the width of the row at (a, b) off the centre of a circle or sphere, if the row is inside it
+    fn half_width(radius: f32, a: i32, b: i32) -> Option<i32>{
+        let remaining = radius * radius - (a * a + b * b) as f32;
+        if radius < 0.0 || remaining < 0.0{
+            return None;
+        }
+        Some(remaining.sqrt().floor() as i32)
+    }
+
+    /// A row through a round shape, with the middle left untouched when there is an inner wall
+    fn span_runs(outer: i32, inner: Option<i32>, block: &BlockType) -> Vec<(usize, Option<BlockType>)>{
+        match inner{
+            Some(inner) => vec![
+                ((outer - inner) as usize, Some(*block)),
+                ((2 * inner + 1) as usize, None),
+                ((outer - inner) as usize, Some(*block)),
+            ],
+            None => vec![((2 * outer + 1) as usize, Some(*block))],
+        }
+    }
+
+    /// Sphere brush centred on a block, a hollow sphere is one block thick
+    pub fn sphere(&mut self, center: Vector3<i32>, radius: f32, block: &BlockType, hollow: bool){
+        let radius = radius.min(MAX_BRUSH_RADIUS);
+        let reach = radius.floor() as i32;
+        for dz in -reach..=reach{
+            for dy in -reach..=reach{
+                let outer = match Self::half_width(radius, dy, dz){
+                    Some(outer) => outer,
+                    None => continue,
+                };
+                let inner = if hollow {Self::half_width(radius - 1.0, dy, dz)} else {None};
+                self.write_row(center + Vector3::new(-outer, dy, dz), &Self::span_runs(outer, inner, block));
+            }
+        }
+    }
+
+    /// Upright cylinder brush standing on a block, a hollow cylinder has no caps
+    pub fn cylinder(&mut self, base: Vector3<i32>, radius: f32, height: usize, block: &BlockType, hollow: bool){
+        let radius = radius.min(MAX_BRUSH_RADIUS);
+        let reach = radius.floor() as i32;
+        for dy in 0..height.min(MAX_BRUSH_HEIGHT) as i32{
+            for dz in -reach..=reach{
+                let outer = match Self::half_width(radius, 0, dz){
+                    Some(outer) => outer,
+                    None => continue,
+                };
+                let inner = if hollow {Self::half_width(radius - 1.0, 0, dz)} else {None};
+                self.write_row(base + Vector3::new(-outer, dy, dz), &Self::span_runs(outer, inner, block));
+            }
+        }
+    }
+
+    /// Copy a region out of the world, origin is the point it will later be pasted relative to
+    pub fn copy(&self, region: &Region, origin: Vector3<i32>) -> BlockVolume{
+        let size = region.size();
+        let mut blocks = Vec::with_capacity(region.volume());
+        Self::for_each_row(region, |y, z|{
+            for (count, block) in self.read_row(Vector3::new(region.min[0], y, z), size[0]){
+                blocks.extend(std::iter::repeat(block).take(count));
+            }
+        });
+        BlockVolume::new(size, region.min - origin, blocks)
+    }
+
+    /// Paste a volume relative to origin, returns the region written to
+    pub fn paste(&mut self, volume: &BlockVolume, origin: Vector3<i32>, skip_air: bool) -> Region{
+        let start = origin + volume.offset;
+        for z in 0..volume.size[2]{
+            for y in 0..volume.size[1]{
+                let runs = volume.row_runs(y, z, skip_air);
+                self.write_row(start + Vector3::new(0, y as i32, z as i32), &runs);
+            }
+        }
+        Region{
+            min: start,
+            max: start + volume.size.map(|x| x as i32 - 1),
+        }
+    }
+
+    /// Repeat a region count times in a direction, each copy placed right against the last.
+    /// Returns the region covering the original and every copy.
+    pub fn stack(&mut self, region: &Region, direction: Vector3<i32>, count: usize) -> Region{
+        let volume = self.copy(region, region.min);
+        let step = direction.component_mul(&region.size().map(|x| x as i32));
+        for i in 1..=count as i32{
+            self.paste(&volume, region.min + step * i, false);
+        }
+        let last = region.translate(step * count as i32);
+        Region{
+            min: region.min.zip_map(&last.min, |a, b| a.min(b)),
+            max: region.max.zip_map(&last.max, |a, b| a.max(b)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn fill_across_chunks(){
+        let mut chunks = ChunkStorage::new();
+        let region = Region::from_corners(Vector3::new(40, 2, 5), Vector3::new(-10, 0, 3));
+        assert_eq!(chunks.fill(&region, &BlockType::Rock), 51 * 3 * 3);
+        assert_eq!(chunks.read_row(Vector3::new(-12, 1, 4), 55), vec![(2, BlockType::Air), (51, BlockType::Rock), (2, BlockType::Air)]);
+        assert_eq!(chunks.replace(&region, &BlockType::Rock, &BlockType::Dirt), 51 * 3 * 3);
+        assert_eq!(chunks.get_block_at(Vector3::new(-10, 0, 3)), BlockType::Dirt);
+        assert_eq!(chunks.get_block_at(Vector3::new(0, 3, 3)), BlockType::Air);
+    }
+
+    #[test]
+    fn hollow_sphere_keeps_centre(){
+        let mut chunks = ChunkStorage::new();
+        chunks.set_block_at(Vector3::new(0, 0, 0), &BlockType::Grass);
+        chunks.sphere(Vector3::new(0, 0, 0), 4.0, &BlockType::Rock, true);
+        assert_eq!(chunks.get_block_at(Vector3::new(0, 0, 0)), BlockType::Grass);
+        assert_eq!(chunks.get_block_at(Vector3::new(4, 0, 0)), BlockType::Rock);
+        assert_eq!(chunks.get_block_at(Vector3::new(0, -4, 0)), BlockType::Rock);
+        assert_eq!(chunks.get_block_at(Vector3::new(3, 3, 3)), BlockType::Air);
+    }
+
+    #[test]
+    fn brushes_are_cut_down_to_the_largest_size(){
+        let mut chunks = ChunkStorage::new();
+        let reach = MAX_BRUSH_RADIUS as i32;
+        chunks.sphere(Vector3::new(0, 0, 0), f32::INFINITY, &BlockType::Rock, true);
+        assert_eq!(chunks.get_block_at(Vector3::new(reach, 0, 0)), BlockType::Rock);
+        assert_eq!(chunks.get_block_at(Vector3::new(reach + 1, 0, 0)), BlockType::Air);
+
+        chunks.cylinder(Vector3::new(0, 0, 0), 0.0, usize::MAX, &BlockType::Dirt, false);
+        assert_eq!(chunks.get_block_at(Vector3::new(0, MAX_BRUSH_HEIGHT as i32 - 1, 0)), BlockType::Dirt);
+        assert_eq!(chunks.get_block_at(Vector3::new(0, MAX_BRUSH_HEIGHT as i32, 0)), BlockType::Air);
+    }
+
+    #[test]
+    fn rotate_and_paste(){
+        let mut chunks = ChunkStorage::new();
+        chunks.fill(&Region::from_corners(Vector3::new(1, 0, 0), Vector3::new(3, 0, 0)), &BlockType::Rock);
+        let volume = chunks.copy(&Region::from_corners(Vector3::new(1, 0, 0), Vector3::new(3, 0, 0)), Vector3::new(0, 0, 0));
+        let rotated = volume.rotate_y(1);
+        assert_eq!(rotated.size(), Vector3::new(1, 1, 3));
+        assert_eq!(rotated.offset(), Vector3::new(0, 0, -3));
+        assert_eq!(volume.rotate_y(4).offset(), volume.offset());
+        assert_eq!(volume.mirror(0).offset(), Vector3::new(-3, 0, 0));
+
+        let pasted = chunks.paste(&rotated, Vector3::new(10, 0, 10), true);
+        assert_eq!(pasted, Region::from_corners(Vector3::new(10, 0, 7), Vector3::new(10, 0, 9)));
+        assert_eq!(chunks.get_block_at(Vector3::new(10, 0, 8)), BlockType::Rock);
+    }
+}
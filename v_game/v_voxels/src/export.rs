@@ -0,0 +1,254 @@
+use crate::*;
+use std::path::Path;
+use std::fmt::Write;
+
+/// Chunk meshes gathered up for writing out to other tools, positions are in world space.
+/// Every texture in the voxel texture array becomes its own material.
+#[derive(Default)]
+pub struct MeshExport{
+    vertices: Vec<VoxelVertex>,
+    indices: Vec<u32>,
+}
+
+impl MeshExport{
+    pub fn new() -> Self{
+        Self::default()
+    }
+
+    /// Add a mesh as made by ChunkMesherSystem::mesh_chunk, moved by offset
+    pub fn add_mesh(&mut self, vertices: &[VoxelVertex], indices: &[u32], offset: Vector3<f32>){
+        let start = self.vertices.len() as u32;
+        self.vertices.extend(vertices.iter().map(|vertex|{
+            let mut vertex = *vertex;
+            for axis in 0..3{
+                vertex.position[axis] += offset[axis];
+            }
+            vertex
+        }));
+        self.indices.extend(indices.iter().map(|index| index + start));
+    }
+
+    pub fn add_chunk(&mut self, chunks: &ChunkStorage, coords: Vector3<i32>){
+        let mut marker = ChunkMarker{coords, renderable: true, changed: false, visibility: ChunkVisibility::ALL, lod: 1, skirts: [false; 6]};
+        let (vertices, indices) = ChunkMesherSystem::mesh_chunk(chunks, &mut marker, &BlockTextures::default());
+        self.add_mesh(&vertices, &indices, coords.map(|x| x as f32 * CHUNK_SIZE as f32 * BLOCK_SIZE));
+    }
+
+    pub fn from_chunks(chunks: &ChunkStorage, coords: &[Vector3<i32>]) -> Self{
+        let mut export = Self::new();
+        for coord in coords.iter(){
+            export.add_chunk(chunks, *coord);
+        }
+        export
+    }
+
+    /// Mesh every loaded chunk the region touches, whole chunks are exported
+    pub fn from_region(chunks: &ChunkStorage, region: &Region) -> Self{
+        let (min, _) = ChunkStorage::block_to_chunk_block(&region.min);
+        let (max, _) = ChunkStorage::block_to_chunk_block(&region.max);
+        let mut coords = Vec::new();
+        for z in min[2]..=max[2]{
+            for y in min[1]..=max[1]{
+                for x in min[0]..=max[0]{
+                    let coord = Vector3::new(x, y, z);
+                    if chunks.map.contains_key(&coord){
+                        coords.push(coord);
+                    }
+                }
+            }
+        }
+        Self::from_chunks(chunks, &coords)
+    }
+
+    pub fn vertex_count(&self) -> usize{
+        self.vertices.len()
+    }
+
+    pub fn triangle_count(&self) -> usize{
+        self.indices.len() / 3
+    }
+
+    /// Triangle indices grouped by the texture of their first vertex, in texture order
+    fn materials(&self) -> Vec<(u32, Vec<u32>)>{
+        let mut materials: Vec<(u32, Vec<u32>)> = Vec::new();
+        for triangle in self.indices.chunks(3){
+            let tex_index = self.vertices[triangle[0] as usize].tex_index;
+            match materials.iter_mut().find(|(index, _)| *index == tex_index){
+                Some((_, indices)) => indices.extend_from_slice(triangle),
+                None => materials.push((tex_index, triangle.to_vec())),
+            }
+        }
+        materials.sort_by_key(|(index, _)| *index);
+        materials
+    }
+
+    fn material_name(tex_index: u32) -> String{
+        match TEXTURE_FILES.get(tex_index as usize){
+            Some(file) => file.trim_end_matches(".png").to_string(),
+            None => format!("texture_{}", tex_index),
+        }
+    }
+
+    fn texture_file(tex_index: u32) -> String{
+        match TEXTURE_FILES.get(tex_index as usize){
+            Some(file) => file.to_string(),
+            None => format!("texture_{}.png", tex_index),
+        }
+    }
+
+    /// Wavefront OBJ text, material_library is the .mtl file name written into it
+    pub fn to_obj(&self, material_library: &str) -> String{
+        let mut obj = String::new();
+        writeln!(obj, "mtllib {}", material_library).unwrap();
+        for vertex in self.vertices.iter(){
+            writeln!(obj, "v {} {} {}", vertex.position[0], vertex.position[1], vertex.position[2]).unwrap();
+        }
+        for vertex in self.vertices.iter(){
+            writeln!(obj, "vt {} {}", vertex.tex_coord[0], vertex.tex_coord[1]).unwrap();
+        }
+        for (tex_index, indices) in self.materials(){
+            writeln!(obj, "usemtl {}", Self::material_name(tex_index)).unwrap();
+            for triangle in indices.chunks(3){
+                let (a, b, c) = (triangle[0] + 1, triangle[1] + 1, triangle[2] + 1);
+                writeln!(obj, "f {}/{} {}/{} {}/{}", a, a, b, b, c, c).unwrap();
+            }
+        }
+        obj
+    }
+
+    /// The material library to go with to_obj, textures are referenced by file name
+    pub fn to_mtl(&self) -> String{
+        let mut mtl = String::new();
+        for (tex_index, _) in self.materials(){
+            writeln!(mtl, "newmtl {}", Self::material_name(tex_index)).unwrap();
+            writeln!(mtl, "Kd 1 1 1").unwrap();
+            writeln!(mtl, "map_Kd {}", Self::texture_file(tex_index)).unwrap();
+        }
+        mtl
+    }
+
+    /// Binary glTF 2.0 with one primitive per material, images are referenced by file name
+    pub fn to_glb(&self) -> Vec<u8>{
+        let materials = self.materials();
+        let mut bin: Vec<u8> = Vec::new();
+        let mut views = Vec::new();
+
+        let mut min = [std::f32::MAX; 3];
+        let mut max = [std::f32::MIN; 3];
+        for vertex in self.vertices.iter(){
+            for axis in 0..3{
+                min[axis] = min[axis].min(vertex.position[axis]);
+                max[axis] = max[axis].max(vertex.position[axis]);
+                bin.extend_from_slice(&vertex.position[axis].to_le_bytes());
+            }
+        }
+        views.push((0, bin.len(), 34962));
+
+        let start = bin.len();
+        for vertex in self.vertices.iter(){
+            //glTF puts the texture origin at the top left rather than the bottom left
+            bin.extend_from_slice(&vertex.tex_coord[0].to_le_bytes());
+            bin.extend_from_slice(&(1.0 - vertex.tex_coord[1]).to_le_bytes());
+        }
+        views.push((start, bin.len() - start, 34962));
+
+        for (_, indices) in materials.iter(){
+            let start = bin.len();
+            for index in indices.iter(){
+                bin.extend_from_slice(&index.to_le_bytes());
+            }
+            views.push((start, bin.len() - start, 34963));
+        }
+
+        let mut json = String::from(r#"{"asset":{"version":"2.0","generator":"v_game"},"scene":0,"#);
+        if self.vertices.is_empty(){
+            json.push_str(r#""scenes":[{"nodes":[]}]}"#);
+        } else {
+            let list = |items: Vec<String>| items.join(",");
+            let primitives = list(materials.iter().enumerate().map(|(i, _)|
+                format!(r#"{{"attributes":{{"POSITION":0,"TEXCOORD_0":1}},"indices":{},"material":{}}}"#, i + 2, i)).collect());
+            let material_list = list(materials.iter().enumerate().map(|(i, (tex_index, _))|
+                format!(r#"{{"name":"{}","pbrMetallicRoughness":{{"baseColorTexture":{{"index":{}}},"metallicFactor":0.0}}}}"#, Self::material_name(*tex_index), i)).collect());
+            let textures = list((0..materials.len()).map(|i| format!(r#"{{"sampler":0,"source":{}}}"#, i)).collect());
+            let images = list(materials.iter().map(|(tex_index, _)| format!(r#"{{"uri":"{}"}}"#, Self::texture_file(*tex_index))).collect());
+            let buffer_views = list(views.iter().map(|(offset, length, target)|
+                format!(r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":{}}}"#, offset, length, target)).collect());
+            let mut accessors = vec![
+                format!(r#"{{"bufferView":0,"componentType":5126,"count":{},"type":"VEC3","min":[{},{},{}],"max":[{},{},{}]}}"#,
+                        self.vertices.len(), min[0], min[1], min[2], max[0], max[1], max[2]),
+                format!(r#"{{"bufferView":1,"componentType":5126,"count":{},"type":"VEC2"}}"#, self.vertices.len()),
+            ];
+            for (i, (_, indices)) in materials.iter().enumerate(){
+                accessors.push(format!(r#"{{"bufferView":{},"componentType":5125,"count":{},"type":"SCALAR"}}"#, i + 2, indices.len()));
+            }
+
+            write!(json, r#""scenes":[{{"nodes":[0]}}],"nodes":[{{"mesh":0}}],"meshes":[{{"primitives":[{}]}}],"#, primitives).unwrap();
+            write!(json, r#""materials":[{}],"textures":[{}],"images":[{}],"samplers":[{{"magFilter":9728,"minFilter":9728}}],"#, material_list, textures, images).unwrap();
+            write!(json, r#""buffers":[{{"byteLength":{}}}],"bufferViews":[{}],"accessors":[{}]}}"#, bin.len(), buffer_views, list(accessors)).unwrap();
+        }
+
+        let mut json = json.into_bytes();
+        while json.len() % 4 != 0{
+            json.push(b' ');
+        }
+        while bin.len() % 4 != 0{
+            bin.push(0);
+        }
+
+        let total = 12 + 8 + json.len() + if bin.is_empty() {0} else {8 + bin.len()};
+        let mut glb = Vec::with_capacity(total);
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&(total as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(&json);
+        if !bin.is_empty(){
+            glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+            glb.extend_from_slice(b"BIN\0");
+            glb.extend_from_slice(&bin);
+        }
+        glb
+    }
+
+    /// Write an .obj and a .mtl with the same name next to it
+    pub fn save_obj<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()>{
+        let path = path.as_ref();
+        let mtl_path = path.with_extension("mtl");
+        let mtl_name = mtl_path.file_name().unwrap().to_string_lossy().to_string();
+        std::fs::write(path, self.to_obj(&mtl_name))?;
+        std::fs::write(mtl_path, self.to_mtl())
+    }
+
+    pub fn save_glb<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()>{
+        std::fs::write(path, self.to_glb())
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn single_block_exports(){
+        let mut chunks = ChunkStorage::new();
+        chunks.set_block_at(Vector3::new(33, 1, 1), &BlockType::Grass);
+        let export = MeshExport::from_region(&chunks, &Region::from_corners(Vector3::new(33, 1, 1), Vector3::new(33, 1, 1)));
+        assert_eq!(export.vertex_count(), 24);
+        assert_eq!(export.triangle_count(), 12);
+        assert!(export.vertices.iter().all(|vertex| vertex.position[0] > 16.0));
+
+        let obj = export.to_obj("grass.mtl");
+        assert_eq!(obj.lines().filter(|line| line.starts_with("f ")).count(), 12);
+        assert_eq!(obj.lines().filter(|line| line.starts_with("usemtl ")).count(), 2);
+        assert!(export.to_mtl().contains("map_Kd grass.png"));
+
+        let glb = export.to_glb();
+        assert_eq!(&glb[0..4], b"glTF");
+        assert_eq!(u32::from_le_bytes([glb[8], glb[9], glb[10], glb[11]]) as usize, glb.len());
+        let json_length = u32::from_le_bytes([glb[12], glb[13], glb[14], glb[15]]) as usize;
+        let json = std::str::from_utf8(&glb[20..20 + json_length]).unwrap();
+        assert!(json.contains(r#""uri":"dirt.png""#));
+        assert!(json.contains(r#""byteLength":624}"#));
+    }
+}
@@ -22,6 +22,21 @@ use specs::world::EntitiesRes;
 
 use rand::*;
 
+mod edit;
+mod vox;
+mod save;
+mod export;
+mod visibility;
+mod lod;
+mod blocks;
+pub use edit::*;
+pub use vox::*;
+pub use save::*;
+pub use export::*;
+pub use visibility::*;
+pub use lod::*;
+pub use blocks::*;
+
 const BLOCK_SIZE: f32 = 0.5;
 const CHUNK_SIZE: usize = 32;
 const CHUNK_SIZE_2: usize = CHUNK_SIZE * CHUNK_SIZE;
@@ -45,19 +60,52 @@ impl BlockType{
         }
     }
 
-    pub fn texture_index(&self, direction: Direction) -> Option<u32>{
+    /// The texture a face is drawn with unless blocks.cfg says otherwise
+    pub fn texture_file(&self, direction: Direction) -> Option<&'static str>{
         match self{
-            BlockType::Dirt => Some(0),
+            BlockType::Dirt => Some("dirt.png"),
             BlockType::Grass => match direction{
-                Direction::Top => Some(1),
-                _ => Some(0),
+                Direction::Top => Some("grass.png"),
+                _ => Some("dirt.png"),
             },
-            BlockType::Rock => Some(2),
+            BlockType::Rock => Some("rock.png"),
+            _ => None,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<BlockType>{
+        match id{
+            0 => Some(BlockType::Dynamic),
+            1 => Some(BlockType::Air),
+            2 => Some(BlockType::Dirt),
+            3 => Some(BlockType::Grass),
+            4 => Some(BlockType::Rock),
             _ => None,
         }
     }
+
+    pub fn from_name(name: &str) -> Option<BlockType>{
+        match name.to_lowercase().as_str(){
+            "air" => Some(BlockType::Air),
+            "dirt" => Some(BlockType::Dirt),
+            "grass" => Some(BlockType::Grass),
+            "rock" => Some(BlockType::Rock),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str{
+        match self{
+            BlockType::Dynamic => "dynamic",
+            BlockType::Air => "air",
+            BlockType::Dirt => "dirt",
+            BlockType::Grass => "grass",
+            BlockType::Rock => "rock",
+        }
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Direction{
     Top,
     Bottom,
@@ -95,11 +143,37 @@ impl Chunk{
         let index = Self::vec_to_index(position).unwrap();
         self.blocks.set(index, block);
     }
+
+    /// Build a chunk from compressed runs, they have to cover the chunk exactly
+    pub fn from_runs(runs: Vec<(usize, BlockType)>) -> Result<Self, VoxelError>{
+        let blocks = RLE::from_runs(runs);
+        if blocks.raw_length != CHUNK_SIZE_3{
+            return Err(VoxelError::ChunkLengthMismatch);
+        }
+        Ok(Chunk{blocks})
+    }
+
+    pub fn runs(&self) -> std::slice::Iter<(usize, BlockType)>{
+        self.blocks.runs()
+    }
+
+    /// Set length blocks along the x axis starting at position in one run level write
+    pub fn set_run(&mut self, position: Vector3<usize>, length: usize, block: &BlockType){
+        let index = Self::vec_to_index(position).unwrap();
+        self.blocks.set_range_singular(*block, index..index + length).unwrap();
+    }
+
+    /// Get the runs of blocks along the x axis starting at position
+    pub fn get_runs(&self, position: Vector3<usize>, length: usize) -> Vec<(usize, BlockType)>{
+        let index = Self::vec_to_index(position).unwrap();
+        self.blocks.get_runs(index..index + length).unwrap()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum VoxelError{
     ChunkCoordOutOfRange,
+    ChunkLengthMismatch,
 }
 
 pub struct ChunkStorage{
@@ -127,6 +201,27 @@ impl ChunkStorage{
         self.map.insert(chunk_coord, chunk);
     }
 
+    /// Global coordinate of the block containing a world space point
+    pub fn world_to_block(place: &Vector3<f32>) -> Vector3<i32>{
+        place.map(|x| (x / BLOCK_SIZE).floor() as i32)
+    }
+
+    /// Coordinate of the chunk containing a world space point
+    pub fn world_to_chunk(place: &Vector3<f32>) -> Vector3<i32>{
+        place.map(|x| (x / CHUNK_SIZE as f32 / BLOCK_SIZE).floor() as i32)
+    }
+
+    /// World space position of a global block coordinate
+    pub fn block_to_world(block: &Vector3<i32>) -> Vector3<f32>{
+        block.map(|x| x as f32 * BLOCK_SIZE)
+    }
+
+    fn block_to_chunk_block(block: &Vector3<i32>) -> (Vector3<i32>, Vector3<usize>){
+        let chunk_coord = block.map(|x| x.div_euclid(CHUNK_SIZE as i32));
+        let block_coord = block.map(|x| x.rem_euclid(CHUNK_SIZE as i32) as usize);
+        (chunk_coord, block_coord)
+    }
+
     fn world_point_to_chunk_block(place: &Vector3<f32>) -> (Vector3<i32>, Vector3<usize>){
         let chunk_coord = place.map(|x| (x as f32 / CHUNK_SIZE as f32 / BLOCK_SIZE).floor() as i32);
         let chunk_coord_f32 = chunk_coord.map(|x| x as f32);
@@ -191,6 +286,13 @@ pub struct ChunkMarker{
     pub coords: Vector3<i32>,
     pub renderable: bool,
     pub changed: bool,
+    /// Worked out again whenever the chunk is meshed
+    pub visibility: ChunkVisibility,
+    /// Blocks per cell of the chunk's mesh, one of LOD_SCALES
+    pub lod: usize,
+    /// Sides facing a chunk at another level of detail, indexed by Direction::index.
+    /// Faces on them are kept even against solid blocks so the two meshes meet without a crack.
+    pub skirts: [bool; 6],
 }
 
 impl Component for ChunkMarker{
@@ -203,17 +305,33 @@ impl Default for ChunkMarker{
             coords: Vector3::new(0,0,0),
             renderable: false,
             changed: false,
+            visibility: ChunkVisibility::ALL,
+            lod: 1,
+            skirts: [false; 6],
         }
     }
 }
 
+/// Whether a block is in the outer layer of its chunk on the side facing direction
+fn on_chunk_side(block: Vector3<usize>, direction: Direction) -> bool{
+    let last = CHUNK_SIZE - 1;
+    match direction{
+        Direction::Top => block[1] == last,
+        Direction::Bottom => block[1] == 0,
+        Direction::Right => block[0] == last,
+        Direction::Left => block[0] == 0,
+        Direction::Front => block[2] == last,
+        Direction::Back => block[2] == 0,
+    }
+}
 
 pub struct ChunkMesherSystem{}
 
 impl ChunkMesherSystem{
-    pub fn mesh_chunk(chunks: &ChunkStorage, marker: &mut ChunkMarker) -> (Vec<VoxelVertex>, Vec<u32>){
+    pub fn mesh_chunk(chunks: &ChunkStorage, marker: &mut ChunkMarker, textures: &BlockTextures) -> (Vec<VoxelVertex>, Vec<u32>){
 
         let dimension = (0..CHUNK_SIZE).into_par_iter();
+        let skirts = marker.skirts;
 
         let (sender, receiver):(Sender<Vec<VoxelVertex>>, std::sync::mpsc::Receiver<Vec<VoxelVertex>>) = channel();
         let (mesh_finished_sender, mesh_finished_receiver) = channel();
@@ -254,10 +372,12 @@ impl ChunkMesherSystem{
                                             + marker.coords.map(|x| x as f32) * CHUNK_SIZE as f32 * BLOCK_SIZE;
                             let block = chunks.get_block(&block_coord);
                             if block == BlockType::Air { continue }
+                            let block_position = Vector3::new(block_x, block_y, block_z);
+                            let skirt = |direction: Direction| skirts[direction.index()] && on_chunk_side(block_position, direction);
 
                             let top_block = chunks.get_block(&(block_coord + Vector3::new(0.0, 1.0, 0.0) * BLOCK_SIZE));
-                            if top_block.is_transparent() {
-                                let tex = block.texture_index(Direction::Top).unwrap();
+                            if top_block.is_transparent() || skirt(Direction::Top) {
+                                let tex = textures.get(block, Direction::Top).unwrap();
                                 verts.push(VoxelVertex::new(
                                     Vector3::new(
                                         block_x as f32 * BLOCK_SIZE + BLOCK_SIZE / 2.0,
@@ -306,8 +426,8 @@ impl ChunkMesherSystem{
 
 
                             let bottom_block = chunks.get_block(&(block_coord + Vector3::new(0.0, -1.0, 0.0) * BLOCK_SIZE));
-                            if bottom_block.is_transparent() {
-                                let tex = block.texture_index(Direction::Bottom).unwrap();
+                            if bottom_block.is_transparent() || skirt(Direction::Bottom) {
+                                let tex = textures.get(block, Direction::Bottom).unwrap();
                                 verts.push(VoxelVertex::new(
                                     Vector3::new(
                                         block_x as f32 * BLOCK_SIZE + BLOCK_SIZE / 2.0,
@@ -356,8 +476,8 @@ impl ChunkMesherSystem{
 
 
                             let right_block = chunks.get_block(&(block_coord + Vector3::new(1.0, 0.0, 0.0) * BLOCK_SIZE));
-                            if right_block.is_transparent() {
-                                let tex = block.texture_index(Direction::Right).unwrap();
+                            if right_block.is_transparent() || skirt(Direction::Right) {
+                                let tex = textures.get(block, Direction::Right).unwrap();
                                 verts.push(VoxelVertex::new(
                                     Vector3::new(
                                         block_x as f32 * BLOCK_SIZE + BLOCK_SIZE / 2.0,
@@ -405,8 +525,8 @@ impl ChunkMesherSystem{
                             }
 
                             let left_block = chunks.get_block(&(block_coord + Vector3::new(-1.0, 0.0, 0.0) * BLOCK_SIZE));
-                            if left_block.is_transparent() {
-                                let tex = block.texture_index(Direction::Left).unwrap();
+                            if left_block.is_transparent() || skirt(Direction::Left) {
+                                let tex = textures.get(block, Direction::Left).unwrap();
                                 verts.push(VoxelVertex::new(
                                     Vector3::new(
                                         block_x as f32 * BLOCK_SIZE - BLOCK_SIZE / 2.0,
@@ -455,8 +575,8 @@ impl ChunkMesherSystem{
 
 
                             let front_block = chunks.get_block(&(block_coord + Vector3::new(0.0, 0.0, 1.0) * BLOCK_SIZE));
-                            if front_block.is_transparent() {
-                                let tex = block.texture_index(Direction::Front).unwrap();
+                            if front_block.is_transparent() || skirt(Direction::Front) {
+                                let tex = textures.get(block, Direction::Front).unwrap();
                                 verts.push(VoxelVertex::new(
                                     Vector3::new(
                                         block_x as f32 * BLOCK_SIZE + BLOCK_SIZE / 2.0,
@@ -504,8 +624,8 @@ impl ChunkMesherSystem{
                             }
 
                             let back_block = chunks.get_block(&(block_coord + Vector3::new(0.0, 0.0, -1.0) * BLOCK_SIZE));
-                            if back_block.is_transparent() {
-                                let tex = block.texture_index(Direction::Back).unwrap();
+                            if back_block.is_transparent() || skirt(Direction::Back) {
+                                let tex = textures.get(block, Direction::Back).unwrap();
                                 verts.push(VoxelVertex::new(
                                     Vector3::new(
                                         block_x as f32 * BLOCK_SIZE - BLOCK_SIZE / 2.0,
@@ -568,27 +688,46 @@ impl<'a> System<'a> for ChunkMesherSystem{
     type SystemData = (
         Entities<'a>,
         Read<'a, ChunkStorage>,
+        Read<'a, BlockTextures>,
         Read<'a, WindowDisplay>,
+        Write<'a, ChunkGeometry>,
         WriteStorage<'a, ChunkMarker>,
         WriteStorage<'a, MeshRenderer<VoxelVertex>>
     );
 
-    fn run(&mut self, (entities, chunks, display, mut markers, mut renderers): Self::SystemData){
+    fn run(&mut self, (entities, chunks, textures, display, mut geometry, mut markers, mut renderers): Self::SystemData){
+        //Nothing to upload meshes to when running headless, chunks stay marked as changed
+        let display = match display.as_ref(){
+            Some(display) => display,
+            None => return,
+        };
+
         let (send, recieve) = channel();
         (&mut markers, &entities).par_join().for_each_with(send, |sender, (marker, entity)|{
                 if !marker.changed || !marker.renderable{return;}
                 marker.changed = false;
+                marker.visibility = chunks.chunk_visibility(&marker.coords);
 
-                let (verts, tris) = Self::mesh_chunk(chunks.deref(), marker);
+                let (verts, tris) = if marker.lod > 1{
+                    mesh_lod(chunks.deref(), marker, &textures)
+                } else {
+                    Self::mesh_chunk(chunks.deref(), marker, &textures)
+                };
 
                 sender.send((entity, verts, tris)).unwrap();
         });
 
-        let display = display.as_ref().unwrap().lock().unwrap();
+        let display = display.lock().unwrap();
         for (entity, verts, tris) in recieve.iter() {
-            let buffer = MeshBuffer::new(display.deref(), verts, tris);
-            renderers.insert(entity, MeshRenderer { mesh: Arc::new(Mutex::new(buffer)) });
+            let bounds = Aabb::from_points(verts.iter().map(|vert| Vector3::from(vert.position)))
+                .unwrap_or_else(|| Aabb::new(Vector3::zeros(), Vector3::zeros()));
+            //Remeshed chunks give their old space back first
+            let allocation = geometry.upload(display.deref(), BLOCK_SIZE, entity, &verts, &tris);
+            renderers.insert(entity, MeshRenderer::new(allocation, bounds));
         }
+
+        //Deleted chunks and ones whose mesh was taken away give their space back
+        geometry.free_unused(|entity, allocation| entities.is_alive(entity) && renderers.get(entity).map(|renderer| &renderer.allocation) == Some(allocation));
     }
 }
 
@@ -610,7 +749,7 @@ impl<'a> System<'a> for NewChunkPlacementSystem{
             lazy.create_entity(&entities)
                 .with(Position::new(chunk_pos[0], chunk_pos[1], chunk_pos[2]))
                 .with(TransformMatrix::default())
-                .with(ChunkMarker{coords:new_chunk_coord, changed:true, renderable:true})
+                .with(ChunkMarker{coords:new_chunk_coord, changed:true, renderable:true, visibility: ChunkVisibility::ALL, lod: 1, skirts: [false; 6]})
                 .build();
         }
 
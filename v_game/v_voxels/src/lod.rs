@@ -0,0 +1,302 @@
+use crate::*;
+use std::collections::HashMap;
+
+/// How many blocks wide a cell is at each level of detail, the first is the full detail mesh
+pub const LOD_SCALES: [usize; 4] = [1, 2, 4, 8];
+
+/// A corner of a face as signs along x, y and z with its texture coordinate
+type Corner = ([f32; 3], [f32; 2]);
+
+/// Each face's lighting value and corners, wound the same way as ChunkMesherSystem's faces
+const FACE_CORNERS: [(Direction, u32, [Corner; 4]); 6] = [
+    (Direction::Top, 0, [([1.0, 1.0, 1.0], [1.0, 1.0]), ([1.0, 1.0, -1.0], [1.0, 0.0]), ([-1.0, 1.0, -1.0], [0.0, 0.0]), ([-1.0, 1.0, 1.0], [0.0, 1.0])]),
+    (Direction::Bottom, 1, [([1.0, -1.0, 1.0], [1.0, 1.0]), ([-1.0, -1.0, 1.0], [1.0, 0.0]), ([-1.0, -1.0, -1.0], [0.0, 0.0]), ([1.0, -1.0, -1.0], [0.0, 1.0])]),
+    (Direction::Right, 2, [([1.0, 1.0, 1.0], [1.0, 1.0]), ([1.0, -1.0, 1.0], [1.0, 0.0]), ([1.0, -1.0, -1.0], [0.0, 0.0]), ([1.0, 1.0, -1.0], [0.0, 1.0])]),
+    (Direction::Left, 3, [([-1.0, -1.0, -1.0], [1.0, 1.0]), ([-1.0, -1.0, 1.0], [1.0, 0.0]), ([-1.0, 1.0, 1.0], [0.0, 0.0]), ([-1.0, 1.0, -1.0], [0.0, 1.0])]),
+    (Direction::Front, 4, [([1.0, 1.0, 1.0], [1.0, 1.0]), ([-1.0, 1.0, 1.0], [1.0, 0.0]), ([-1.0, -1.0, 1.0], [0.0, 0.0]), ([1.0, -1.0, 1.0], [0.0, 1.0])]),
+    (Direction::Back, 5, [([-1.0, -1.0, -1.0], [1.0, 1.0]), ([-1.0, 1.0, -1.0], [1.0, 0.0]), ([1.0, 1.0, -1.0], [0.0, 0.0]), ([1.0, -1.0, -1.0], [0.0, 1.0])]),
+];
+
+/// Number of block types, for counting them
+const BLOCK_TYPES: usize = 5;
+
+/// Shrink a chunk into cells scale blocks wide, indexed x first then y then z.
+/// A cell takes the most common solid block in it when at least half of it is solid, otherwise it's air.
+/// Ties go to the later block type.
+/// Counts are taken straight from the chunk's runs, a row of a run at a time.
+pub fn downsample(chunk: &Chunk, scale: usize) -> Vec<BlockType>{
+    let cells = CHUNK_SIZE / scale;
+    let mut counts = vec![[0usize; BLOCK_TYPES]; cells * cells * cells];
+    let mut index = 0;
+    for (count, block) in chunk.runs(){
+        let mut remaining = *count;
+        while remaining > 0{
+            let (x, y, z) = (index % CHUNK_SIZE, (index / CHUNK_SIZE) % CHUNK_SIZE, index / CHUNK_SIZE_2);
+            let row_end = x + remaining.min(CHUNK_SIZE - x);
+            let row_cell = (y / scale) * cells + (z / scale) * cells * cells;
+            let mut start = x;
+            while start < row_end{
+                let end = ((start / scale + 1) * scale).min(row_end);
+                counts[row_cell + start / scale][*block as usize] += end - start;
+                start = end;
+            }
+            remaining -= row_end - x;
+            index += row_end - x;
+        }
+    }
+
+    let volume = scale * scale * scale;
+    counts.iter().map(|counts|{
+        let solid = (0..BLOCK_TYPES).filter_map(|id| BlockType::from_id(id as u8).filter(|block| !block.is_transparent()).map(|block| (block, counts[id])));
+        match solid.clone().max_by_key(|(_, count)| *count){
+            Some((block, _)) if solid.map(|(_, count)| count).sum::<usize>() * 2 >= volume => block,
+            _ => BlockType::Air,
+        }
+    }).collect()
+}
+
+/// Mesh a chunk at its marker's lower level of detail, each cell of downsample drawn as one big block.
+/// Faces on the chunk's sides are culled against a neighbour at the same detail. On sides marked in
+/// ChunkMarker::skirts they're always kept, they act as skirts that fill the cracks where the chunk
+/// meets one at a different level of detail.
+pub fn mesh_lod(chunks: &ChunkStorage, marker: &ChunkMarker, textures: &BlockTextures) -> (Vec<VoxelVertex>, Vec<u32>){
+    let scale = marker.lod;
+    let cells = CHUNK_SIZE / scale;
+    //Chunks are downsampled one at a time so the map is never locked twice
+    let blocks = match chunks.map.get(&marker.coords){
+        Some(chunk) => downsample(&chunk, scale),
+        None => return (vec![], vec![]),
+    };
+    let neighbours: Vec<Option<Vec<BlockType>>> = Direction::ALL.iter().map(|direction|{
+        if marker.skirts[direction.index()]{
+            return None;
+        }
+        chunks.map.get(&(marker.coords + direction.offset())).map(|chunk| downsample(&chunk, scale))
+    }).collect();
+    let cell_index = |cell: Vector3<i32>| cell[0] as usize + cell[1] as usize * cells + cell[2] as usize * cells * cells;
+    let half_size = scale as f32 * BLOCK_SIZE / 2.0;
+
+    let mut verts = vec![];
+    let mut tris = vec![];
+    for z in 0..cells{
+        for y in 0..cells{
+            for x in 0..cells{
+                let cell = Vector3::new(x as i32, y as i32, z as i32);
+                let block = blocks[cell_index(cell)];
+                if block.is_transparent(){
+                    continue;
+                }
+                //Blocks are centred on their coordinate, so cells start half a block back
+                let center = cell.map(|x| x as f32) * scale as f32 * BLOCK_SIZE + Vector3::repeat(half_size - BLOCK_SIZE / 2.0);
+                for (direction, lighting, corners) in FACE_CORNERS.iter(){
+                    let neighbour = cell + direction.offset();
+                    let inside = neighbour.iter().all(|x| *x >= 0 && *x < cells as i32);
+                    let hidden = if inside{
+                        !blocks[cell_index(neighbour)].is_transparent()
+                    } else {
+                        //The cell across the side, in the neighbouring chunk
+                        match neighbours[direction.index()].as_ref(){
+                            Some(blocks) => !blocks[cell_index(neighbour.map(|x| x.rem_euclid(cells as i32)))].is_transparent(),
+                            None => false,
+                        }
+                    };
+                    if hidden{
+                        continue;
+                    }
+                    let tex = textures.get(block, *direction).unwrap();
+                    let start = verts.len() as u32;
+                    for (signs, tex_coord) in corners.iter(){
+                        verts.push(VoxelVertex::new(
+                            center + Vector3::from(*signs) * half_size,
+                            Vector2::from(*tex_coord),
+                            tex,
+                            *lighting));
+                    }
+                    tris.extend_from_slice(&[start, start + 1, start + 2, start + 2, start + 3, start]);
+                }
+            }
+        }
+    }
+    (verts, tris)
+}
+
+/// Scale of the level of detail to use for a chunk some number of chunks away from the camera
+pub fn lod_for_distance(distance: i32, distances: &[i32; 3]) -> usize{
+    let level = distances.iter().take_while(|limit| distance >= **limit).count();
+    LOD_SCALES[level]
+}
+
+/// Picks each chunk's level of detail from how many chunks it is from the nearest camera,
+/// marking chunks whose level or whose neighbours' levels changed so they get meshed again
+pub struct ChunkLodSystem{
+    /// Chunk distances at which the mesh drops to 2x, 4x and 8x blocks
+    pub distances: [i32; 3],
+}
+
+impl Default for ChunkLodSystem{
+    fn default() -> Self{
+        ChunkLodSystem{
+            distances: [4, 8, 16],
+        }
+    }
+}
+
+impl<'a> System<'a> for ChunkLodSystem{
+    type SystemData = (
+        ReadStorage<'a, Camera>,
+        ReadStorage<'a, TransformMatrix>,
+        WriteStorage<'a, ChunkMarker>);
+
+    fn run(&mut self, (cameras, transforms, mut markers): Self::SystemData){
+        let cameras: Vec<Vector3<i32>> = (&cameras, &transforms).join()
+            .map(|(_, transform)| ChunkStorage::world_to_chunk(&transform.matrix().column(3).xyz()))
+            .collect();
+        if cameras.is_empty(){
+            return;
+        }
+        let mut lods = HashMap::new();
+        for marker in (&mut markers).join(){
+            let distance = cameras.iter().map(|camera| (marker.coords - camera).abs().max()).min().unwrap_or(0);
+            let lod = lod_for_distance(distance, &self.distances);
+            if marker.lod != lod{
+                marker.lod = lod;
+                marker.changed = true;
+            }
+            lods.insert(marker.coords, lod);
+        }
+
+        //Both sides of a change in detail keep their faces there, or a full detail chunk hides its side behind blocks its neighbour doesn't draw
+        for marker in (&mut markers).join(){
+            let mut skirts = [false; 6];
+            for direction in Direction::ALL.iter(){
+                skirts[direction.index()] = matches!(lods.get(&(marker.coords + direction.offset())), Some(lod) if *lod != marker.lod);
+            }
+            if marker.skirts != skirts{
+                marker.skirts = skirts;
+                marker.changed = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn downsample_keeps_mostly_solid_cells(){
+        let mut chunk = Chunk::new();
+        //Bottom 3 layers are rock, the layer above is dirt
+        for y in 0..4{
+            for z in 0..CHUNK_SIZE{
+                let block = if y == 3 {BlockType::Dirt} else {BlockType::Rock};
+                chunk.set_run(Vector3::new(0, y, z), CHUNK_SIZE, &block);
+            }
+        }
+
+        let cells = downsample(&chunk, 2);
+        assert_eq!(cells.len(), 16 * 16 * 16);
+        assert_eq!(cells[0], BlockType::Rock);
+        //Half rock and half dirt, ties go to the later block type
+        assert_eq!(cells[16], BlockType::Rock);
+        assert_eq!(cells[2 * 16], BlockType::Air);
+
+        //At 8x a cell is 4 layers of 8 solid, exactly half
+        let cells = downsample(&chunk, 8);
+        assert_eq!(cells[0], BlockType::Rock);
+        assert_eq!(cells[4], BlockType::Air);
+    }
+
+    /// A storage with the chunks at coords, and a marker for the first at the given level of detail
+    fn storage(chunks: Vec<(Vector3<i32>, Chunk)>, lod: usize) -> (ChunkStorage, ChunkMarker){
+        let marker = ChunkMarker{coords: chunks[0].0, renderable: true, lod, ..ChunkMarker::default()};
+        let storage = ChunkStorage::new();
+        for (coords, chunk) in chunks{
+            storage.map.insert(coords, chunk);
+        }
+        (storage, marker)
+    }
+
+    #[test]
+    fn lod_mesh_closes_chunk_sides(){
+        let full = || Chunk::from_runs(vec![(CHUNK_SIZE_3, BlockType::Rock)]).unwrap();
+        for scale in LOD_SCALES.iter().skip(1){
+            let cells = CHUNK_SIZE / scale;
+            let (chunks, marker) = storage(vec![(Vector3::new(0, 0, 0), full())], *scale);
+            let (verts, tris) = mesh_lod(&chunks, &marker, &BlockTextures::default());
+            assert_eq!(verts.len(), 6 * cells * cells * 4);
+            assert_eq!(tris.len(), 6 * cells * cells * 6);
+        }
+
+        //A lone block still takes up its whole cell at 4x detail
+        let mut chunk = Chunk::new();
+        for y in 0..4{
+            for z in 0..4{
+                chunk.set_run(Vector3::new(0, y, z), 4, &BlockType::Grass);
+            }
+        }
+        let (chunks, marker) = storage(vec![(Vector3::new(0, 0, 0), chunk)], 4);
+        let (verts, _) = mesh_lod(&chunks, &marker, &BlockTextures::default());
+        assert_eq!(verts.len(), 24);
+        let low = verts.iter().map(|vert| vert.position[0]).fold(f32::MAX, f32::min);
+        let high = verts.iter().map(|vert| vert.position[0]).fold(f32::MIN, f32::max);
+        assert_eq!((low, high), (-BLOCK_SIZE / 2.0, 3.5 * BLOCK_SIZE));
+    }
+
+    #[test]
+    fn lod_sides_are_culled_against_neighbours_at_the_same_detail(){
+        let full = || Chunk::from_runs(vec![(CHUNK_SIZE_3, BlockType::Rock)]).unwrap();
+        let (chunks, mut marker) = storage(vec![(Vector3::new(0, 0, 0), full()), (Vector3::new(1, 0, 0), full())], 4);
+        let right_faces = |verts: &[VoxelVertex]| verts.iter().filter(|vert| vert.lighting == 2).count() / 4;
+        let (verts, _) = mesh_lod(&chunks, &marker, &BlockTextures::default());
+        assert_eq!(right_faces(&verts), 0);
+        assert_eq!(verts.len(), 5 * 8 * 8 * 4);
+
+        //The neighbour is at another level of detail, so the side is a skirt
+        marker.skirts[Direction::Right.index()] = true;
+        let (verts, _) = mesh_lod(&chunks, &marker, &BlockTextures::default());
+        assert_eq!(right_faces(&verts), 8 * 8);
+    }
+
+    #[test]
+    fn full_detail_chunk_keeps_faces_next_to_a_downsampled_one(){
+        let solid = || Chunk::from_runs(vec![(CHUNK_SIZE_3, BlockType::Rock)]).unwrap();
+        let mut world = World::new();
+        world.register::<Camera>();
+        world.register::<TransformMatrix>();
+        world.register::<ChunkMarker>();
+        world.create_entity().with(Camera::new(1.57, 0.001, 4096.0)).with(TransformMatrix::default()).build();
+        let near = world.create_entity().with(ChunkMarker{coords: Vector3::new(0, 0, 0), renderable: true, ..ChunkMarker::default()}).build();
+        let far = world.create_entity().with(ChunkMarker{coords: Vector3::new(1, 0, 0), renderable: true, ..ChunkMarker::default()}).build();
+        ChunkLodSystem{distances: [1, 8, 16]}.run_now(&world);
+
+        let mut markers = world.write_storage::<ChunkMarker>();
+        assert_eq!(markers.get(far).unwrap().lod, 2);
+        assert!(markers.get(far).unwrap().skirts[Direction::Left.index()]);
+        let near = markers.get_mut(near).unwrap();
+        assert_eq!(near.lod, 1);
+        assert!(near.changed);
+        assert_eq!(near.skirts, [false, false, true, false, false, false]);
+
+        //The side against the downsampled chunk is drawn even though its blocks are solid
+        let chunks = ChunkStorage::new();
+        chunks.map.insert(Vector3::new(0, 0, 0), solid());
+        chunks.map.insert(Vector3::new(1, 0, 0), solid());
+        let (verts, _) = ChunkMesherSystem::mesh_chunk(&chunks, near, &BlockTextures::default());
+        let right_faces = |verts: &[VoxelVertex]| verts.iter().filter(|vert| vert.lighting == 2).count() / 4;
+        assert_eq!(right_faces(&verts), CHUNK_SIZE * CHUNK_SIZE);
+
+        near.skirts = [false; 6];
+        let (verts, _) = ChunkMesherSystem::mesh_chunk(&chunks, near, &BlockTextures::default());
+        assert_eq!(right_faces(&verts), 0);
+    }
+
+    #[test]
+    fn lod_follows_distance(){
+        let distances = [4, 8, 16];
+        assert_eq!(lod_for_distance(0, &distances), 1);
+        assert_eq!(lod_for_distance(4, &distances), 2);
+        assert_eq!(lod_for_distance(12, &distances), 4);
+        assert_eq!(lod_for_distance(100, &distances), 8);
+    }
+}
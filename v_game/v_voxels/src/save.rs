@@ -0,0 +1,201 @@
+use crate::*;
+use std::path::Path;
+
+const WORLD_MAGIC: &[u8; 4] = b"VWLD";
+const WORLD_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorldFileError{
+    Io(std::io::ErrorKind),
+    InvalidHeader,
+    UnsupportedVersion(u32),
+    UnexpectedEnd,
+    InvalidBlock(u8),
+    InvalidChunk(Vector3<i32>),
+}
+
+impl From<std::io::Error> for WorldFileError{
+    fn from(error: std::io::Error) -> Self{
+        WorldFileError::Io(error.kind())
+    }
+}
+
+struct WorldReader<'a>{
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> WorldReader<'a>{
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], WorldFileError>{
+        if self.position + count > self.data.len(){
+            return Err(WorldFileError::UnexpectedEnd);
+        }
+        let bytes = &self.data[self.position..self.position + count];
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, WorldFileError>{
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn i32(&mut self) -> Result<i32, WorldFileError>{
+        Ok(self.u32()? as i32)
+    }
+
+    /// Room for a count read from the file, no more than the bytes left could hold so a bad count can't allocate too much
+    fn capacity(&self, count: usize, item_size: usize) -> usize{
+        count.min((self.data.len() - self.position) / item_size)
+    }
+}
+
+/// 64 bit FNV-1a, used where a hash has to stay the same between runs and builds
+pub struct Checksum(u64);
+
+impl Checksum{
+    pub fn new() -> Self{
+        Checksum(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub fn write(&mut self, bytes: &[u8]){
+        for byte in bytes{
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub fn finish(&self) -> u64{
+        self.0
+    }
+}
+
+impl Default for Checksum{
+    fn default() -> Self{
+        Self::new()
+    }
+}
+
+/// Worlds are saved as every chunk's coordinate followed by its runs of blocks,
+/// so a file is about as small as the chunks are in memory.
+impl ChunkStorage{
+    pub fn write_world(&self) -> Vec<u8>{
+        let mut data = WORLD_MAGIC.to_vec();
+        data.extend_from_slice(&WORLD_VERSION.to_le_bytes());
+        data.extend_from_slice(&(self.map.len() as u32).to_le_bytes());
+        for chunk in self.map.iter(){
+            for axis in 0..3{
+                data.extend_from_slice(&chunk.key()[axis].to_le_bytes());
+            }
+            data.extend_from_slice(&(chunk.value().runs().len() as u32).to_le_bytes());
+            for (count, block) in chunk.value().runs(){
+                data.extend_from_slice(&(*count as u32).to_le_bytes());
+                data.push(*block as u8);
+            }
+        }
+        data
+    }
+
+    /// Load every chunk in a world file into storage, replacing chunks that are already loaded.
+    /// Returns the number of chunks read.
+    pub fn read_world(&mut self, data: &[u8]) -> Result<usize, WorldFileError>{
+        let mut reader = WorldReader{data, position: 0};
+        if reader.bytes(4)? != WORLD_MAGIC{
+            return Err(WorldFileError::InvalidHeader);
+        }
+        let version = reader.u32()?;
+        if version != WORLD_VERSION{
+            return Err(WorldFileError::UnsupportedVersion(version));
+        }
+
+        let chunk_count = reader.u32()? as usize;
+        //A chunk takes at least its coordinate and run count
+        let mut chunks = Vec::with_capacity(reader.capacity(chunk_count, 16));
+        for _ in 0..chunk_count{
+            let coord = Vector3::new(reader.i32()?, reader.i32()?, reader.i32()?);
+            let run_count = reader.u32()? as usize;
+            let mut runs = Vec::with_capacity(reader.capacity(run_count, 5));
+            for _ in 0..run_count{
+                let count = reader.u32()? as usize;
+                let id = reader.bytes(1)?[0];
+                runs.push((count, BlockType::from_id(id).ok_or(WorldFileError::InvalidBlock(id))?));
+            }
+            chunks.push((coord, Chunk::from_runs(runs).map_err(|_| WorldFileError::InvalidChunk(coord))?));
+        }
+
+        for (coord, chunk) in chunks{
+            if self.map.insert(coord, chunk).is_some(){
+                self.changed_chunks.insert(coord);
+            } else {
+                self.needed_chunks.lock().unwrap().push(coord);
+            }
+        }
+        Ok(chunk_count)
+    }
+
+    pub fn save_world<P: AsRef<Path>>(&self, path: P) -> Result<(), WorldFileError>{
+        std::fs::write(path, self.write_world())?;
+        Ok(())
+    }
+
+    pub fn load_world<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, WorldFileError>{
+        let data = std::fs::read(path)?;
+        self.read_world(&data)
+    }
+
+    /// Hash of every chunk's blocks, the same for the same world no matter what order chunks were made in
+    pub fn checksum(&self) -> u64{
+        let mut coords = self.chunk_coords();
+        coords.sort_by_key(|coord| (coord[0], coord[1], coord[2]));
+        let mut checksum = Checksum::new();
+        for coord in coords{
+            for axis in 0..3{
+                checksum.write(&coord[axis].to_le_bytes());
+            }
+            for (count, block) in self.map.get(&coord).unwrap().runs(){
+                checksum.write(&(*count as u32).to_le_bytes());
+                checksum.write(&[*block as u8]);
+            }
+        }
+        checksum.finish()
+    }
+
+    /// Coordinates of every chunk currently in storage
+    pub fn chunk_coords(&self) -> Vec<Vector3<i32>>{
+        self.map.iter().map(|chunk| *chunk.key()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn save_and_load(){
+        let mut chunks = ChunkStorage::new();
+        chunks.fill(&Region::from_corners(Vector3::new(-3, 0, 0), Vector3::new(40, 2, 1)), &BlockType::Grass);
+        chunks.set_block_at(Vector3::new(5, 1, 1), &BlockType::Rock);
+
+        let mut loaded = ChunkStorage::new();
+        assert_eq!(loaded.read_world(&chunks.write_world()).unwrap(), chunks.chunk_coords().len());
+        assert_eq!(loaded.read_row(Vector3::new(-4, 1, 1), 46), chunks.read_row(Vector3::new(-4, 1, 1), 46));
+        assert_eq!(loaded.get_block_at(Vector3::new(5, 1, 1)), BlockType::Rock);
+
+        assert_eq!(loaded.checksum(), chunks.checksum());
+        loaded.set_block_at(Vector3::new(5, 1, 1), &BlockType::Dirt);
+        assert_ne!(loaded.checksum(), chunks.checksum());
+
+        let mut truncated = chunks.write_world();
+        truncated.pop();
+        assert_eq!(ChunkStorage::new().read_world(&truncated).unwrap_err(), WorldFileError::UnexpectedEnd);
+
+        //Counts far bigger than the file are an error, not a huge allocation
+        let mut huge = WORLD_MAGIC.to_vec();
+        huge.extend_from_slice(&WORLD_VERSION.to_le_bytes());
+        huge.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(ChunkStorage::new().read_world(&huge).unwrap_err(), WorldFileError::UnexpectedEnd);
+        huge.extend_from_slice(&[0; 12]);
+        huge.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(ChunkStorage::new().read_world(&huge).unwrap_err(), WorldFileError::UnexpectedEnd);
+    }
+}
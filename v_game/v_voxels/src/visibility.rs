@@ -0,0 +1,256 @@
+use crate::*;
+use std::collections::{HashMap, VecDeque};
+
+impl Direction{
+    pub const ALL: [Direction; 6] = [Direction::Top, Direction::Bottom, Direction::Right, Direction::Left, Direction::Front, Direction::Back];
+
+    pub(crate) fn index(self) -> usize{
+        match self{
+            Direction::Top => 0,
+            Direction::Bottom => 1,
+            Direction::Right => 2,
+            Direction::Left => 3,
+            Direction::Front => 4,
+            Direction::Back => 5,
+        }
+    }
+
+    pub fn opposite(self) -> Direction{
+        match self{
+            Direction::Top => Direction::Bottom,
+            Direction::Bottom => Direction::Top,
+            Direction::Right => Direction::Left,
+            Direction::Left => Direction::Right,
+            Direction::Front => Direction::Back,
+            Direction::Back => Direction::Front,
+        }
+    }
+
+    /// One step in this direction
+    pub fn offset(self) -> Vector3<i32>{
+        match self{
+            Direction::Top => Vector3::new(0, 1, 0),
+            Direction::Bottom => Vector3::new(0, -1, 0),
+            Direction::Right => Vector3::new(1, 0, 0),
+            Direction::Left => Vector3::new(-1, 0, 0),
+            Direction::Front => Vector3::new(0, 0, 1),
+            Direction::Back => Vector3::new(0, 0, -1),
+        }
+    }
+}
+
+/// Which pairs of a chunk's faces can see each other through transparent blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkVisibility(u64);
+
+impl ChunkVisibility{
+    /// No face sees any other, a chunk of solid rock
+    pub const NONE: ChunkVisibility = ChunkVisibility(0);
+    /// Every face sees every other, an empty chunk or one that hasn't been worked out yet
+    pub const ALL: ChunkVisibility = ChunkVisibility((1 << 36) - 1);
+
+    fn bit(from: Direction, to: Direction) -> u64{
+        1 << (from.index() * 6 + to.index())
+    }
+
+    pub fn connect(&mut self, from: Direction, to: Direction){
+        self.0 |= Self::bit(from, to) | Self::bit(to, from);
+    }
+
+    pub fn connected(&self, from: Direction, to: Direction) -> bool{
+        self.0 & Self::bit(from, to) != 0
+    }
+
+    /// Flood fill the chunk's transparent blocks, faces touched by the same fill can see each other
+    pub fn from_chunk(chunk: &Chunk) -> Self{
+        let mut open = Vec::with_capacity(CHUNK_SIZE_3);
+        for (count, block) in chunk.runs(){
+            open.resize(open.len() + *count, block.is_transparent());
+        }
+        if open.iter().all(|open| *open){
+            return Self::ALL;
+        }
+
+        let mut visibility = Self::NONE;
+        let mut visited = vec![false; CHUNK_SIZE_3];
+        let mut stack = Vec::new();
+        for start in 0..CHUNK_SIZE_3{
+            if !open[start] || visited[start]{
+                continue;
+            }
+            visited[start] = true;
+            stack.push(start);
+            let mut faces = Vec::new();
+            while let Some(index) = stack.pop(){
+                let position = Vector3::new(index % CHUNK_SIZE, (index / CHUNK_SIZE) % CHUNK_SIZE, index / CHUNK_SIZE_2);
+                for direction in Direction::ALL.iter(){
+                    let next = position.map(|x| x as i32) + direction.offset();
+                    if next.iter().any(|x| *x < 0 || *x >= CHUNK_SIZE as i32){
+                        if !faces.contains(direction){
+                            faces.push(*direction);
+                        }
+                        continue;
+                    }
+                    let next = Chunk::vec_to_index(next.map(|x| x as usize)).unwrap();
+                    if open[next] && !visited[next]{
+                        visited[next] = true;
+                        stack.push(next);
+                    }
+                }
+            }
+            for from in faces.iter(){
+                for to in faces.iter(){
+                    visibility.connect(*from, *to);
+                }
+            }
+        }
+        visibility
+    }
+}
+
+impl Default for ChunkVisibility{
+    fn default() -> Self{
+        Self::ALL
+    }
+}
+
+impl ChunkStorage{
+    /// Face connectivity of a stored chunk, chunks that don't exist are empty so every face sees every other
+    pub fn chunk_visibility(&self, coord: &Vector3<i32>) -> ChunkVisibility{
+        match self.map.get(coord){
+            Some(chunk) => ChunkVisibility::from_chunk(&chunk),
+            None => ChunkVisibility::ALL,
+        }
+    }
+}
+
+/// Chunks that could be seen from the camera's chunk, found by walking out through faces that connect.
+/// The walk never turns back on a direction it has already gone in and stays within min and max, grown to hold the camera.
+/// Chunks missing from the map are treated as empty.
+pub fn visible_chunks(camera: Vector3<i32>, min: Vector3<i32>, max: Vector3<i32>, chunks: &HashMap<Vector3<i32>, ChunkVisibility>) -> HashSet<Vector3<i32>>{
+    let min = min.inf(&camera);
+    let max = max.sup(&camera);
+    let in_bounds = |coord: &Vector3<i32>| (0..3).all(|axis| coord[axis] >= min[axis] && coord[axis] <= max[axis]);
+
+    let mut visible = HashSet::new();
+    visible.insert(camera);
+    //Chunk, the face it was entered through and the directions the walk has taken to get there
+    let mut queue = VecDeque::new();
+    queue.push_back((camera, None, 0u8));
+    while let Some((coord, entered, travelled)) = queue.pop_front(){
+        let visibility = chunks.get(&coord).copied().unwrap_or(ChunkVisibility::ALL);
+        for direction in Direction::ALL.iter(){
+            if travelled & (1 << direction.opposite().index()) != 0{
+                continue;
+            }
+            if let Some(entered) = entered{
+                if !visibility.connected(entered, *direction){
+                    continue;
+                }
+            }
+            let next = coord + direction.offset();
+            if !in_bounds(&next) || visible.contains(&next){
+                continue;
+            }
+            visible.insert(next);
+            queue.push_back((next, Some(direction.opposite()), travelled | 1 << direction.index()));
+        }
+    }
+    visible
+}
+
+/// Hides chunk meshes none of the cameras can see through caves and open space
+#[derive(Default)]
+pub struct ChunkOcclusionSystem{}
+
+impl<'a> System<'a> for ChunkOcclusionSystem{
+    type SystemData = (
+        ReadStorage<'a, Camera>,
+        ReadStorage<'a, TransformMatrix>,
+        ReadStorage<'a, ChunkMarker>,
+        WriteStorage<'a, MeshRenderer<VoxelVertex>>);
+
+    fn run(&mut self, (cameras, transforms, markers, mut renderers): Self::SystemData){
+        let cameras: Vec<Vector3<i32>> = (&cameras, &transforms).join()
+            .map(|(_, transform)| ChunkStorage::world_to_chunk(&transform.matrix().column(3).xyz()))
+            .collect();
+        let mut min = match cameras.first(){
+            Some(camera) => *camera,
+            None => return,
+        };
+        let mut max = min;
+
+        let mut chunks = HashMap::new();
+        for marker in markers.join(){
+            chunks.insert(marker.coords, marker.visibility);
+            min = min.inf(&marker.coords);
+            max = max.sup(&marker.coords);
+        }
+        //Split screen and render texture cameras each see their own chunks
+        let mut visible = HashSet::new();
+        for camera in cameras{
+            visible.extend(visible_chunks(camera, min, max, &chunks));
+        }
+        for (marker, renderer) in (&markers, &mut renderers).join(){
+            renderer.visible = visible.contains(&marker.coords);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn solid_chunk() -> Chunk{
+        Chunk::from_runs(vec![(CHUNK_SIZE_3, BlockType::Rock)]).unwrap()
+    }
+
+    #[test]
+    fn tunnel_connects_its_ends(){
+        assert_eq!(ChunkVisibility::from_chunk(&Chunk::new()), ChunkVisibility::ALL);
+        assert_eq!(ChunkVisibility::from_chunk(&solid_chunk()), ChunkVisibility::NONE);
+
+        //A tunnel along x through solid rock
+        let mut chunk = solid_chunk();
+        chunk.set_run(Vector3::new(0, 5, 5), CHUNK_SIZE, &BlockType::Air);
+        let visibility = ChunkVisibility::from_chunk(&chunk);
+        assert!(visibility.connected(Direction::Left, Direction::Right));
+        assert!(visibility.connected(Direction::Right, Direction::Left));
+        assert!(!visibility.connected(Direction::Left, Direction::Top));
+        assert!(!visibility.connected(Direction::Front, Direction::Back));
+    }
+
+    #[test]
+    fn walk_stops_at_solid_chunks(){
+        let solid = ChunkVisibility::NONE;
+        let mut tunnel = ChunkVisibility::NONE;
+        tunnel.connect(Direction::Left, Direction::Right);
+
+        //A row of chunks along x: the camera's, a tunnel, solid rock, then one behind the rock
+        let mut chunks = HashMap::new();
+        chunks.insert(Vector3::new(1, 0, 0), tunnel);
+        chunks.insert(Vector3::new(2, 0, 0), solid);
+        chunks.insert(Vector3::new(3, 0, 0), ChunkVisibility::ALL);
+        chunks.insert(Vector3::new(4, 0, 0), ChunkVisibility::ALL);
+        //Rock all around the row so nothing can go around
+        for x in 0..5{
+            for (y, z) in [(1, 0), (-1, 0), (0, 1), (0, -1)].iter(){
+                chunks.insert(Vector3::new(x, *y, *z), ChunkVisibility::NONE);
+            }
+        }
+
+        let visible = visible_chunks(Vector3::new(0, 0, 0), Vector3::new(0, -1, -1), Vector3::new(4, 1, 1), &chunks);
+        assert!(visible.contains(&Vector3::new(1, 0, 0)));
+        assert!(visible.contains(&Vector3::new(2, 0, 0)));
+        assert!(!visible.contains(&Vector3::new(3, 0, 0)));
+        assert!(!visible.contains(&Vector3::new(4, 0, 0)));
+        //Walls next to the camera can be seen, walls next to the tunnel can't
+        assert!(visible.contains(&Vector3::new(0, 1, 0)));
+        assert!(!visible.contains(&Vector3::new(1, 1, 0)));
+
+        //With the rock gone the walk goes all the way
+        chunks.insert(Vector3::new(2, 0, 0), ChunkVisibility::ALL);
+        let visible = visible_chunks(Vector3::new(0, 0, 0), Vector3::new(0, -1, -1), Vector3::new(4, 1, 1), &chunks);
+        assert!(visible.contains(&Vector3::new(4, 0, 0)));
+    }
+}
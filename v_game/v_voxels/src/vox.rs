@@ -0,0 +1,422 @@
+use crate::*;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Maps MagicaVoxel palette entries to block types and back.
+/// Exact palette index matches win, otherwise the nearest colour in the table is used.
+#[derive(Debug, Clone)]
+pub struct VoxBlockTable{
+    colors: Vec<([u8; 3], BlockType)>,
+    indices: HashMap<u8, BlockType>,
+}
+
+impl VoxBlockTable{
+    pub fn new() -> Self{
+        VoxBlockTable{
+            colors: Vec::new(),
+            indices: HashMap::new(),
+        }
+    }
+
+    pub fn with_color(mut self, color: [u8; 3], block: BlockType) -> Self{
+        self.colors.push((color, block));
+        self
+    }
+
+    pub fn with_index(mut self, index: u8, block: BlockType) -> Self{
+        self.indices.insert(index, block);
+        self
+    }
+
+    /// Read a table from text, one mapping per line as either `<block> <r> <g> <b>` or `<block> index <n>`.
+    /// Blank lines and lines starting with # are skipped.
+    pub fn parse(text: &str) -> Result<Self, VoxError>{
+        let mut table = Self::new();
+        for (line_number, line) in text.lines().enumerate(){
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#'){
+                continue;
+            }
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let invalid = VoxError::InvalidTable(line_number + 1);
+            let block = BlockType::from_name(parts[0]).ok_or(invalid.clone())?;
+            table = match parts.as_slice(){
+                [_, "index", index] => table.with_index(index.parse().map_err(|_| invalid)?, block),
+                [_, r, g, b] => {
+                    let mut color = [0; 3];
+                    for (channel, value) in color.iter_mut().zip([r, g, b].iter()){
+                        *channel = value.parse().map_err(|_| invalid.clone())?;
+                    }
+                    table.with_color(color, block)
+                },
+                _ => return Err(invalid),
+            };
+        }
+        Ok(table)
+    }
+
+    /// Pick the block for a voxel with the given palette index and colour
+    pub fn block_for(&self, index: u8, color: [u8; 4]) -> BlockType{
+        if let Some(block) = self.indices.get(&index){
+            return *block;
+        }
+        let distance = |other: &[u8; 3]| (0..3).map(|i| (color[i] as i32 - other[i] as i32).pow(2)).sum::<i32>();
+        self.colors.iter()
+            .min_by_key(|(other, _)| distance(other))
+            .map(|(_, block)| *block)
+            .unwrap_or(BlockType::Rock)
+    }
+
+    /// The colour a block is written out with, the first colour listed for it
+    pub fn color_for(&self, block: &BlockType) -> [u8; 3]{
+        self.colors.iter()
+            .find(|(_, other)| other == block)
+            .map(|(color, _)| *color)
+            .unwrap_or([128, 128, 128])
+    }
+}
+
+impl Default for VoxBlockTable{
+    fn default() -> Self{
+        Self::new()
+            .with_color([134, 96, 67], BlockType::Dirt)
+            .with_color([89, 157, 54], BlockType::Grass)
+            .with_color([128, 128, 128], BlockType::Rock)
+    }
+}
+
+/// The palette MagicaVoxel uses for files without an RGBA chunk, as 0xAABBGGRR indexed by colour index
+const DEFAULT_PALETTE: [u32; 256] = [
+    0x00000000, 0xffffffff, 0xffccffff, 0xff99ffff, 0xff66ffff, 0xff33ffff, 0xff00ffff, 0xffffccff,
+    0xffccccff, 0xff99ccff, 0xff66ccff, 0xff33ccff, 0xff00ccff, 0xffff99ff, 0xffcc99ff, 0xff9999ff,
+    0xff6699ff, 0xff3399ff, 0xff0099ff, 0xffff66ff, 0xffcc66ff, 0xff9966ff, 0xff6666ff, 0xff3366ff,
+    0xff0066ff, 0xffff33ff, 0xffcc33ff, 0xff9933ff, 0xff6633ff, 0xff3333ff, 0xff0033ff, 0xffff00ff,
+    0xffcc00ff, 0xff9900ff, 0xff6600ff, 0xff3300ff, 0xff0000ff, 0xffffffcc, 0xffccffcc, 0xff99ffcc,
+    0xff66ffcc, 0xff33ffcc, 0xff00ffcc, 0xffffcccc, 0xffcccccc, 0xff99cccc, 0xff66cccc, 0xff33cccc,
+    0xff00cccc, 0xffff99cc, 0xffcc99cc, 0xff9999cc, 0xff6699cc, 0xff3399cc, 0xff0099cc, 0xffff66cc,
+    0xffcc66cc, 0xff9966cc, 0xff6666cc, 0xff3366cc, 0xff0066cc, 0xffff33cc, 0xffcc33cc, 0xff9933cc,
+    0xff6633cc, 0xff3333cc, 0xff0033cc, 0xffff00cc, 0xffcc00cc, 0xff9900cc, 0xff6600cc, 0xff3300cc,
+    0xff0000cc, 0xffffff99, 0xffccff99, 0xff99ff99, 0xff66ff99, 0xff33ff99, 0xff00ff99, 0xffffcc99,
+    0xffcccc99, 0xff99cc99, 0xff66cc99, 0xff33cc99, 0xff00cc99, 0xffff9999, 0xffcc9999, 0xff999999,
+    0xff669999, 0xff339999, 0xff009999, 0xffff6699, 0xffcc6699, 0xff996699, 0xff666699, 0xff336699,
+    0xff006699, 0xffff3399, 0xffcc3399, 0xff993399, 0xff663399, 0xff333399, 0xff003399, 0xffff0099,
+    0xffcc0099, 0xff990099, 0xff660099, 0xff330099, 0xff000099, 0xffffff66, 0xffccff66, 0xff99ff66,
+    0xff66ff66, 0xff33ff66, 0xff00ff66, 0xffffcc66, 0xffcccc66, 0xff99cc66, 0xff66cc66, 0xff33cc66,
+    0xff00cc66, 0xffff9966, 0xffcc9966, 0xff999966, 0xff669966, 0xff339966, 0xff009966, 0xffff6666,
+    0xffcc6666, 0xff996666, 0xff666666, 0xff336666, 0xff006666, 0xffff3366, 0xffcc3366, 0xff993366,
+    0xff663366, 0xff333366, 0xff003366, 0xffff0066, 0xffcc0066, 0xff990066, 0xff660066, 0xff330066,
+    0xff000066, 0xffffff33, 0xffccff33, 0xff99ff33, 0xff66ff33, 0xff33ff33, 0xff00ff33, 0xffffcc33,
+    0xffcccc33, 0xff99cc33, 0xff66cc33, 0xff33cc33, 0xff00cc33, 0xffff9933, 0xffcc9933, 0xff999933,
+    0xff669933, 0xff339933, 0xff009933, 0xffff6633, 0xffcc6633, 0xff996633, 0xff666633, 0xff336633,
+    0xff006633, 0xffff3333, 0xffcc3333, 0xff993333, 0xff663333, 0xff333333, 0xff003333, 0xffff0033,
+    0xffcc0033, 0xff990033, 0xff660033, 0xff330033, 0xff000033, 0xffffff00, 0xffccff00, 0xff99ff00,
+    0xff66ff00, 0xff33ff00, 0xff00ff00, 0xffffcc00, 0xffcccc00, 0xff99cc00, 0xff66cc00, 0xff33cc00,
+    0xff00cc00, 0xffff9900, 0xffcc9900, 0xff999900, 0xff669900, 0xff339900, 0xff009900, 0xffff6600,
+    0xffcc6600, 0xff996600, 0xff666600, 0xff336600, 0xff006600, 0xffff3300, 0xffcc3300, 0xff993300,
+    0xff663300, 0xff333300, 0xff003300, 0xffff0000, 0xffcc0000, 0xff990000, 0xff660000, 0xff330000,
+    0xff0000ee, 0xff0000dd, 0xff0000bb, 0xff0000aa, 0xff000088, 0xff000077, 0xff000055, 0xff000044,
+    0xff000022, 0xff000011, 0xff00ee00, 0xff00dd00, 0xff00bb00, 0xff00aa00, 0xff008800, 0xff007700,
+    0xff005500, 0xff004400, 0xff002200, 0xff001100, 0xffee0000, 0xffdd0000, 0xffbb0000, 0xffaa0000,
+    0xff880000, 0xff770000, 0xff550000, 0xff440000, 0xff220000, 0xff110000, 0xffeeeeee, 0xffdddddd,
+    0xffbbbbbb, 0xffaaaaaa, 0xff888888, 0xff777777, 0xff555555, 0xff444444, 0xff222222, 0xff111111,
+];
+
+/// The largest a model can be along each axis, voxel coordinates are single bytes
+pub const MAX_MODEL_SIZE: usize = 256;
+
+/// DEFAULT_PALETTE as RGBA colours
+fn default_palette() -> [[u8; 4]; 256]{
+    let mut palette = [[0; 4]; 256];
+    for (color, value) in palette.iter_mut().zip(DEFAULT_PALETTE.iter()){
+        *color = value.to_le_bytes();
+    }
+    palette
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VoxError{
+    Io(std::io::ErrorKind),
+    InvalidHeader,
+    UnexpectedEnd,
+    MissingChunk(&'static str),
+    ModelTooLarge,
+    /// A model with an axis of 0 or more than MAX_MODEL_SIZE
+    InvalidSize,
+    InvalidTable(usize),
+}
+
+impl From<std::io::Error> for VoxError{
+    fn from(error: std::io::Error) -> Self{
+        VoxError::Io(error.kind())
+    }
+}
+
+/// A chunk's id, content and children
+type VoxChunk<'a> = (&'a [u8], &'a [u8], &'a [u8]);
+
+struct VoxReader<'a>{
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> VoxReader<'a>{
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], VoxError>{
+        if self.position + count > self.data.len(){
+            return Err(VoxError::UnexpectedEnd);
+        }
+        let bytes = &self.data[self.position..self.position + count];
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, VoxError>{
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Read a chunk header and return its id, content and children
+    fn chunk(&mut self) -> Result<VoxChunk<'a>, VoxError>{
+        let id = self.bytes(4)?;
+        let content_size = self.u32()? as usize;
+        let children_size = self.u32()? as usize;
+        Ok((id, self.bytes(content_size)?, self.bytes(children_size)?))
+    }
+
+    fn is_empty(&self) -> bool{
+        self.position >= self.data.len()
+    }
+}
+
+/// Read every model in a .vox file. MagicaVoxel is z up so models are turned to stand on the y axis.
+pub fn read_vox(data: &[u8], table: &VoxBlockTable) -> Result<Vec<BlockVolume>, VoxError>{
+    let mut reader = VoxReader{data, position: 0};
+    if reader.bytes(4)? != b"VOX "{
+        return Err(VoxError::InvalidHeader);
+    }
+    let _version = reader.u32()?;
+    let (id, _, children) = reader.chunk()?;
+    if id != b"MAIN"{
+        return Err(VoxError::MissingChunk("MAIN"));
+    }
+
+    let mut sizes = Vec::new();
+    let mut voxels = Vec::new();
+    let mut palette = None;
+    let mut reader = VoxReader{data: children, position: 0};
+    while !reader.is_empty(){
+        let (id, content, _) = reader.chunk()?;
+        let mut content = VoxReader{data: content, position: 0};
+        match id{
+            b"SIZE" => {
+                let size = Vector3::new(content.u32()? as usize, content.u32()? as usize, content.u32()? as usize);
+                if size.iter().any(|x| *x == 0 || *x > MAX_MODEL_SIZE){
+                    return Err(VoxError::InvalidSize);
+                }
+                sizes.push(size);
+            },
+            b"XYZI" => {
+                let count = content.u32()? as usize;
+                voxels.push(content.bytes(count * 4)?);
+            },
+            b"RGBA" => {
+                let mut colors = [[0; 4]; 256];
+                for color in colors.iter_mut().skip(1){
+                    let bytes = content.bytes(4)?;
+                    *color = [bytes[0], bytes[1], bytes[2], bytes[3]];
+                }
+                palette = Some(colors);
+            },
+            _ => {},
+        }
+    }
+
+    if sizes.is_empty() || sizes.len() != voxels.len(){
+        return Err(VoxError::MissingChunk("SIZE"));
+    }
+
+    let palette = palette.unwrap_or_else(default_palette);
+    let models = sizes.iter().zip(voxels.iter()).map(|(size, voxels)|{
+        let mut volume = BlockVolume::filled(Vector3::new(size[0], size[2], size[1]), BlockType::Air);
+        for voxel in voxels.chunks(4){
+            let (x, y, z) = (voxel[0] as usize, voxel[1] as usize, voxel[2] as usize);
+            if x >= size[0] || y >= size[1] || z >= size[2]{
+                continue;
+            }
+            volume.set(Vector3::new(x, z, size[1] - 1 - y), table.block_for(voxel[3], palette[voxel[3] as usize]));
+        }
+        volume
+    }).collect();
+    Ok(models)
+}
+
+/// Write a volume out as a single model .vox file, air is left empty
+pub fn write_vox(volume: &BlockVolume, table: &VoxBlockTable) -> Result<Vec<u8>, VoxError>{
+    let size = volume.size();
+    if size.iter().any(|x| *x > MAX_MODEL_SIZE){
+        return Err(VoxError::ModelTooLarge);
+    }
+
+    let mut blocks: Vec<BlockType> = Vec::new();
+    let mut voxels = Vec::new();
+    for z in 0..size[2]{
+        for y in 0..size[1]{
+            for x in 0..size[0]{
+                let block = volume.get(Vector3::new(x, y, z));
+                if block.is_transparent(){
+                    continue;
+                }
+                let index = match blocks.iter().position(|other| *other == block){
+                    Some(index) => index,
+                    None => {
+                        blocks.push(block);
+                        blocks.len() - 1
+                    },
+                };
+                voxels.extend_from_slice(&[x as u8, (size[2] - 1 - z) as u8, y as u8, index as u8 + 1]);
+            }
+        }
+    }
+
+    let mut palette = vec![0; 256 * 4];
+    for (index, block) in blocks.iter().enumerate(){
+        let color = table.color_for(block);
+        palette[index * 4..index * 4 + 4].copy_from_slice(&[color[0], color[1], color[2], 255]);
+    }
+
+    let mut children = Vec::new();
+    write_chunk(&mut children, b"SIZE", &[size[0] as u32, size[2] as u32, size[1] as u32].iter().flat_map(|x| x.to_le_bytes().to_vec()).collect::<Vec<u8>>(), &[]);
+    let mut xyzi = ((voxels.len() / 4) as u32).to_le_bytes().to_vec();
+    xyzi.extend_from_slice(&voxels);
+    write_chunk(&mut children, b"XYZI", &xyzi, &[]);
+    write_chunk(&mut children, b"RGBA", &palette, &[]);
+
+    let mut data = b"VOX ".to_vec();
+    data.extend_from_slice(&150u32.to_le_bytes());
+    write_chunk(&mut data, b"MAIN", &[], &children);
+    Ok(data)
+}
+
+fn write_chunk(data: &mut Vec<u8>, id: &[u8; 4], content: &[u8], children: &[u8]){
+    data.extend_from_slice(id);
+    data.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    data.extend_from_slice(&(children.len() as u32).to_le_bytes());
+    data.extend_from_slice(content);
+    data.extend_from_slice(children);
+}
+
+/// Load the first model in a .vox file
+pub fn load_vox<P: AsRef<Path>>(path: P, table: &VoxBlockTable) -> Result<BlockVolume, VoxError>{
+    let data = std::fs::read(path)?;
+    read_vox(&data, table)?.into_iter().next().ok_or(VoxError::MissingChunk("XYZI"))
+}
+
+pub fn save_vox<P: AsRef<Path>>(path: P, volume: &BlockVolume, table: &VoxBlockTable) -> Result<(), VoxError>{
+    std::fs::write(path, write_vox(volume, table)?)?;
+    Ok(())
+}
+
+impl ChunkStorage{
+    /// Stamp the first model of a .vox file into the world with its corner at position, returns the region written to
+    pub fn import_vox<P: AsRef<Path>>(&mut self, path: P, position: Vector3<i32>, table: &VoxBlockTable) -> Result<Region, VoxError>{
+        let mut volume = load_vox(path, table)?;
+        volume.set_offset(Vector3::new(0, 0, 0));
+        Ok(self.paste(&volume, position, true))
+    }
+
+    /// Save a region of the world as a .vox model
+    pub fn export_vox<P: AsRef<Path>>(&self, path: P, region: &Region, table: &VoxBlockTable) -> Result<(), VoxError>{
+        save_vox(path, &self.copy(region, region.min), table)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn round_trip(){
+        let mut volume = BlockVolume::filled(Vector3::new(3, 2, 4), BlockType::Air);
+        volume.set(Vector3::new(0, 0, 0), BlockType::Rock);
+        volume.set(Vector3::new(2, 1, 3), BlockType::Grass);
+        volume.set(Vector3::new(1, 0, 2), BlockType::Dirt);
+
+        let table = VoxBlockTable::default();
+        let models = read_vox(&write_vox(&volume, &table).unwrap(), &table).unwrap();
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].size(), volume.size());
+        for z in 0..4{
+            for y in 0..2{
+                for x in 0..3{
+                    let position = Vector3::new(x, y, z);
+                    assert_eq!(models[0].get(position), volume.get(position));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn skips_unknown_chunks_and_uses_index_overrides(){
+        let mut children = Vec::new();
+        write_chunk(&mut children, b"PACK", &1u32.to_le_bytes(), &[]);
+        write_chunk(&mut children, b"SIZE", &[1u8, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0], &[]);
+        write_chunk(&mut children, b"XYZI", &[2, 0, 0, 0, 0, 0, 0, 7, 0, 0, 1, 9], &[]);
+        write_chunk(&mut children, b"nTRN", &[0; 16], &[]);
+        let mut data = b"VOX ".to_vec();
+        data.extend_from_slice(&150u32.to_le_bytes());
+        write_chunk(&mut data, b"MAIN", &[], &children);
+
+        let table = VoxBlockTable::new().with_index(7, BlockType::Dirt).with_color([255, 255, 255], BlockType::Grass);
+        let model = &read_vox(&data, &table).unwrap()[0];
+        assert_eq!(model.size(), Vector3::new(1, 2, 1));
+        assert_eq!(model.get(Vector3::new(0, 0, 0)), BlockType::Dirt);
+        assert_eq!(model.get(Vector3::new(0, 1, 0)), BlockType::Grass);
+        assert_eq!(read_vox(b"NOPE", &table).unwrap_err(), VoxError::InvalidHeader);
+    }
+
+    #[test]
+    fn sizes_out_of_range_are_rejected(){
+        let table = VoxBlockTable::new();
+        for size in [[0u32, 1, 1], [1, 257, 1], [1, 1, u32::MAX]].iter(){
+            let mut children = Vec::new();
+            let content: Vec<u8> = size.iter().flat_map(|x| x.to_le_bytes().to_vec()).collect();
+            write_chunk(&mut children, b"SIZE", &content, &[]);
+            write_chunk(&mut children, b"XYZI", &0u32.to_le_bytes(), &[]);
+            let mut data = b"VOX ".to_vec();
+            data.extend_from_slice(&150u32.to_le_bytes());
+            write_chunk(&mut data, b"MAIN", &[], &children);
+            assert_eq!(read_vox(&data, &table).unwrap_err(), VoxError::InvalidSize);
+        }
+    }
+
+    #[test]
+    fn files_without_a_palette_use_the_default_one(){
+        let palette = default_palette();
+        assert_eq!(palette[1], [255, 255, 255, 255]);
+        assert_eq!(palette[2], [255, 255, 204, 255]);
+        assert_eq!(palette[7], [255, 204, 255, 255]);
+        assert_eq!(palette[215], [0, 0, 51, 255]);
+        assert_eq!(palette[216], [238, 0, 0, 255]);
+        assert_eq!(palette[255], [17, 17, 17, 255]);
+
+        //Index 36 is pure red and 226 green in the default palette
+        let mut children = Vec::new();
+        write_chunk(&mut children, b"SIZE", &[2u8, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0], &[]);
+        write_chunk(&mut children, b"XYZI", &[2, 0, 0, 0, 0, 0, 0, 36, 1, 0, 0, 226], &[]);
+        let mut data = b"VOX ".to_vec();
+        data.extend_from_slice(&150u32.to_le_bytes());
+        write_chunk(&mut data, b"MAIN", &[], &children);
+
+        let table = VoxBlockTable::new().with_color([255, 0, 0], BlockType::Dirt).with_color([0, 200, 0], BlockType::Grass);
+        let model = &read_vox(&data, &table).unwrap()[0];
+        assert_eq!(model.get(Vector3::new(0, 0, 0)), BlockType::Dirt);
+        assert_eq!(model.get(Vector3::new(1, 0, 0)), BlockType::Grass);
+    }
+
+    #[test]
+    fn parse_table(){
+        let table = VoxBlockTable::parse("# props\ngrass 0 255 0\nrock index 3\n").unwrap();
+        assert_eq!(table.block_for(3, [0, 255, 0, 255]), BlockType::Rock);
+        assert_eq!(table.block_for(4, [10, 240, 5, 255]), BlockType::Grass);
+        assert_eq!(VoxBlockTable::parse("lava 1 2 3").unwrap_err(), VoxError::InvalidTable(1));
+    }
+}
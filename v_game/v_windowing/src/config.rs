@@ -0,0 +1,332 @@
+use crate::*;
+use glutin::window::Fullscreen;
+use glutin::monitor::MonitorHandle;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FullscreenMode{
+    Windowed,
+    /// A window covering the whole monitor, switching to it is quick
+    Borderless,
+    /// Takes the monitor over at the video mode closest to the window size
+    Exclusive,
+}
+
+impl FullscreenMode{
+    pub fn name(self) -> &'static str{
+        match self{
+            FullscreenMode::Windowed => "windowed",
+            FullscreenMode::Borderless => "borderless",
+            FullscreenMode::Exclusive => "exclusive",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self>{
+        match name{
+            "windowed" => Some(FullscreenMode::Windowed),
+            "borderless" => Some(FullscreenMode::Borderless),
+            "exclusive" => Some(FullscreenMode::Exclusive),
+            _ => None,
+        }
+    }
+
+    /// What winit needs to put a window in this mode on the monitor, size picks the exclusive video mode
+    pub fn to_fullscreen(self, monitor: MonitorHandle, size: PhysicalSize<u32>) -> Option<Fullscreen>{
+        match self{
+            FullscreenMode::Windowed => None,
+            FullscreenMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+            FullscreenMode::Exclusive => {
+                let area = |mode_size: PhysicalSize<u32>| mode_size.width as i64 * mode_size.height as i64;
+                let wanted = area(size);
+                //The mode nearest in size to the window, the fastest refresh rate of those
+                monitor.video_modes()
+                    .min_by_key(|mode| ((area(mode.size()) - wanted).abs(), std::cmp::Reverse(mode.refresh_rate())))
+                    .map(Fullscreen::Exclusive)
+            },
+        }
+    }
+}
+
+/// How much work goes into the sun's shadows, Off for weak hardware
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadowQuality{
+    Off,
+    Low,
+    Medium,
+    High,
+}
+
+impl ShadowQuality{
+    pub fn name(self) -> &'static str{
+        match self{
+            ShadowQuality::Off => "off",
+            ShadowQuality::Low => "low",
+            ShadowQuality::Medium => "medium",
+            ShadowQuality::High => "high",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self>{
+        match name{
+            "off" => Some(ShadowQuality::Off),
+            "low" => Some(ShadowQuality::Low),
+            "medium" => Some(ShadowQuality::Medium),
+            "high" => Some(ShadowQuality::High),
+            _ => None,
+        }
+    }
+
+    /// Number of shadow map cascades
+    pub fn cascades(self) -> usize{
+        match self{
+            ShadowQuality::Off => 0,
+            ShadowQuality::Low => 2,
+            ShadowQuality::Medium => 3,
+            ShadowQuality::High => 4,
+        }
+    }
+
+    /// Width and height of each cascade's shadow map
+    pub fn resolution(self) -> u32{
+        match self{
+            ShadowQuality::Off => 0,
+            ShadowQuality::Low => 1024,
+            ShadowQuality::Medium | ShadowQuality::High => 2048,
+        }
+    }
+
+    /// How far from the camera shadows reach in world units
+    pub fn distance(self) -> f32{
+        match self{
+            ShadowQuality::Off => 0.0,
+            ShadowQuality::Low => 64.0,
+            ShadowQuality::Medium => 128.0,
+            ShadowQuality::High => 192.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WindowConfigError{
+    Io(std::io::ErrorKind),
+    InvalidLine(usize),
+}
+
+impl From<std::io::Error> for WindowConfigError{
+    fn from(error: std::io::Error) -> Self{
+        WindowConfigError::Io(error.kind())
+    }
+}
+
+/// How the window and its GL context are created
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowConfig{
+    pub title: String,
+    pub width: u32,
+    pub height: u32,
+    pub fullscreen: FullscreenMode,
+    pub vsync: bool,
+    pub resizable: bool,
+    pub shadows: ShadowQuality,
+}
+
+impl Default for WindowConfig{
+    fn default() -> Self{
+        WindowConfig{
+            title: "v_game".to_string(),
+            width: 1280,
+            height: 720,
+            fullscreen: FullscreenMode::Windowed,
+            vsync: true,
+            resizable: true,
+            shadows: ShadowQuality::Medium,
+        }
+    }
+}
+
+impl WindowConfig{
+    /// Read settings from "<name> <value>" lines, anything left out keeps its default. Lines starting with # are ignored.
+    pub fn parse(text: &str) -> Result<Self, WindowConfigError>{
+        let mut config = Self::default();
+        for (number, line) in text.lines().enumerate(){
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#'){
+                continue;
+            }
+            let invalid = WindowConfigError::InvalidLine(number + 1);
+            let (name, value) = line.split_once(' ').ok_or_else(|| invalid.clone())?;
+            let value = value.trim();
+            match name{
+                //Titles can have spaces in them so the rest of the line is used
+                "title" => config.title = value.to_string(),
+                "width" => config.width = value.parse().map_err(|_| invalid)?,
+                "height" => config.height = value.parse().map_err(|_| invalid)?,
+                "fullscreen" => config.fullscreen = FullscreenMode::from_name(value).ok_or(invalid)?,
+                "vsync" => config.vsync = value.parse().map_err(|_| invalid)?,
+                //Multisampling went in favour of the fxaa pass, files saved before still have the line
+                "samples" => {},
+                "resizable" => config.resizable = value.parse().map_err(|_| invalid)?,
+                "shadows" => config.shadows = ShadowQuality::from_name(value).ok_or(invalid)?,
+                _ => return Err(invalid),
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn to_text(&self) -> String{
+        format!("title {}\nwidth {}\nheight {}\nfullscreen {}\nvsync {}\nresizable {}\nshadows {}\n",
+            self.title, self.width, self.height, self.fullscreen.name(), self.vsync, self.resizable, self.shadows.name())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, WindowConfigError>{
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), WindowConfigError>{
+        std::fs::write(path, self.to_text())?;
+        Ok(())
+    }
+
+    pub fn size(&self) -> PhysicalSize<u32>{
+        PhysicalSize::new(self.width, self.height)
+    }
+}
+
+/// The window as it is now, kept up to date from resize and DPI events.
+/// Changing fullscreen or vsync here asks for the window to follow on the next frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowInfo{
+    size: PhysicalSize<u32>,
+    scale_factor: f64,
+    fullscreen: FullscreenMode,
+    vsync: bool,
+    shadows: ShadowQuality,
+}
+
+impl Default for WindowInfo{
+    fn default() -> Self{
+        Self::new(&WindowConfig::default(), 1.0)
+    }
+}
+
+impl WindowInfo{
+    pub fn new(config: &WindowConfig, scale_factor: f64) -> Self{
+        WindowInfo{
+            size: config.size(),
+            scale_factor,
+            fullscreen: config.fullscreen,
+            vsync: config.vsync,
+            shadows: config.shadows,
+        }
+    }
+
+    /// Size of the drawable area in pixels
+    pub fn size(&self) -> PhysicalSize<u32>{
+        self.size
+    }
+
+    pub fn set_size(&mut self, size: PhysicalSize<u32>){
+        self.size = size;
+    }
+
+    /// Width over height, 1 while the window is minimized to nothing
+    pub fn aspect_ratio(&self) -> f32{
+        if self.size.width == 0 || self.size.height == 0{
+            return 1.0;
+        }
+        self.size.width as f32 / self.size.height as f32
+    }
+
+    /// Physical pixels per logical pixel
+    pub fn scale_factor(&self) -> f64{
+        self.scale_factor
+    }
+
+    pub fn set_scale_factor(&mut self, scale_factor: f64){
+        self.scale_factor = scale_factor;
+    }
+
+    pub fn fullscreen(&self) -> FullscreenMode{
+        self.fullscreen
+    }
+
+    pub fn set_fullscreen(&mut self, fullscreen: FullscreenMode){
+        self.fullscreen = fullscreen;
+    }
+
+    /// Switch between windowed and borderless fullscreen, exclusive goes back to windowed
+    pub fn toggle_fullscreen(&mut self){
+        self.fullscreen = match self.fullscreen{
+            FullscreenMode::Windowed => FullscreenMode::Borderless,
+            _ => FullscreenMode::Windowed,
+        };
+    }
+
+    pub fn vsync(&self) -> bool{
+        self.vsync
+    }
+
+    pub fn set_vsync(&mut self, vsync: bool){
+        self.vsync = vsync;
+    }
+
+    pub fn toggle_vsync(&mut self){
+        self.vsync = !self.vsync;
+    }
+
+    /// The renderer picks up changes on the next frame
+    pub fn shadows(&self) -> ShadowQuality{
+        self.shadows
+    }
+
+    pub fn set_shadows(&mut self, shadows: ShadowQuality){
+        self.shadows = shadows;
+    }
+
+    /// Follow a window event that changes the window's size or scale
+    pub fn handle_event(&mut self, event: &ApplicationEvent){
+        match event{
+            ApplicationEvent::Resized(size) => self.size = *size,
+            ApplicationEvent::ScaleFactorChanged{scale_factor, new_inner_size} => {
+                self.scale_factor = *scale_factor;
+                self.size = *new_inner_size;
+            },
+            _ => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn parse_and_save(){
+        let config = WindowConfig::parse("# window\ntitle My Voxel Game\nwidth 800\nfullscreen borderless\nvsync false\nsamples 4\nshadows high\n").unwrap();
+        assert_eq!(config.title, "My Voxel Game");
+        assert_eq!(config.size(), PhysicalSize::new(800, 720));
+        assert_eq!(config.fullscreen, FullscreenMode::Borderless);
+        assert!(!config.vsync);
+        assert!(!config.to_text().contains("samples"));
+        assert_eq!(config.shadows, ShadowQuality::High);
+        assert_eq!(WindowConfig::parse(&config.to_text()).unwrap(), config);
+        assert_eq!(WindowConfig::parse("width wide").unwrap_err(), WindowConfigError::InvalidLine(1));
+        assert_eq!(WindowConfig::parse("\ncolour blue").unwrap_err(), WindowConfigError::InvalidLine(2));
+    }
+
+    #[test]
+    fn window_info_follows_events(){
+        let mut info = WindowInfo::default();
+        info.handle_event(&ApplicationEvent::Resized(PhysicalSize::new(1000, 500)));
+        assert_eq!(info.aspect_ratio(), 2.0);
+        info.handle_event(&ApplicationEvent::ScaleFactorChanged{scale_factor: 2.0, new_inner_size: PhysicalSize::new(2000, 1000)});
+        assert_eq!(info.scale_factor(), 2.0);
+        assert_eq!(info.size(), PhysicalSize::new(2000, 1000));
+        info.toggle_fullscreen();
+        assert_eq!(info.fullscreen(), FullscreenMode::Borderless);
+        info.toggle_fullscreen();
+        assert_eq!(info.fullscreen(), FullscreenMode::Windowed);
+        info.toggle_vsync();
+        assert!(!info.vsync());
+    }
+}
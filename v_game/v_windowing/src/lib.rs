@@ -1,13 +1,20 @@
 use glium::*;
-use glutin::event_loop::EventLoop;
+use glutin::event_loop::{EventLoop, EventLoopWindowTarget};
+use glutin::monitor::MonitorHandle;
 use glutin::event::*;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::path::PathBuf;
 use glutin::window::Theme;
 use glutin::window::Window;
 use glium::debug::Source::Application;
 use glutin::dpi::{PhysicalSize, PhysicalPosition};
 use std::ops::{Deref, DerefMut};
+use std::time::{Duration, Instant};
+use specs::{System, Read, Write};
+
+mod config;
+pub use config::*;
 
 pub struct GliumState{
     pub event_loop: EventLoop<()>,
@@ -20,10 +27,19 @@ pub struct GliumState{
 impl GliumState{
 
     pub fn new() -> Self{
+        Self::with_config(&WindowConfig::default())
+    }
+
+    pub fn with_config(config: &WindowConfig) -> Self{
         let event_loop = glutin::event_loop::EventLoop::new();
-        let wb = glutin::window::WindowBuilder::new();
-        let cb = glutin::ContextBuilder::new().with_depth_buffer(24);
-        let display = WindowDisplay{0:Arc::new(Some(Mutex::new(glium::Display::new(wb, cb, &event_loop).unwrap())))};
+        let wb = window_builder(config, event_loop.primary_monitor(), config.size());
+        let display = glium::Display::new(wb, context_builder(config), &event_loop)
+            .unwrap_or_else(|e| panic!("Couldn't create a window: {:?}", e));
+        let display = WindowDisplay{
+            display: Arc::new(Some(Mutex::new(display))),
+            rebuild: Arc::new(Mutex::new(None)),
+            rebuilds: Arc::new(AtomicUsize::new(0)),
+        };
         let window_inputs = Arc::new(Mutex::new(Vec::new()));
         let hardware_inputs = Arc::new(Mutex::new(Vec::new()));
 
@@ -50,7 +66,9 @@ impl GliumState{
         let mut display = self.display;
         let window_inputs = self.window_inputs;
         let hardware_inputs = self.hardware_inputs;
-        self.event_loop.run(move |e, _, flow|{
+        let mut timestep = FixedTimestep::new();
+        let mut last_frame = Instant::now();
+        self.event_loop.run(move |e, target, flow|{
             // Poll window events for window_inputs
             match e{
                 Event::WindowEvent{event, ..} => {
@@ -69,8 +87,16 @@ impl GliumState{
                 Event::MainEventsCleared => {
                     // Do logic
                     *flow = glutin::event_loop::ControlFlow::Poll;
+                    display.rebuild_if_requested(target);
 
-                    game.game_loop();
+                    let now = Instant::now();
+                    let frame_time = now - last_frame;
+                    last_frame = now;
+                    let (ticks, alpha) = timestep.advance(frame_time, game.tick_length());
+                    for _ in 0..ticks{
+                        game.fixed_update();
+                    }
+                    game.frame(frame_time, alpha);
                     return;
                 },
                 _ => *flow = glutin::event_loop::ControlFlow::Poll,
@@ -80,8 +106,91 @@ impl GliumState{
     }
 }
 
+fn window_builder(config: &WindowConfig, monitor: MonitorHandle, size: PhysicalSize<u32>) -> glutin::window::WindowBuilder{
+    glutin::window::WindowBuilder::new()
+        .with_title(config.title.clone())
+        .with_inner_size(size)
+        .with_resizable(config.resizable)
+        .with_fullscreen(config.fullscreen.to_fullscreen(monitor, size))
+}
+
+/// The scene is drawn into off screen targets and smoothed by the fxaa pass, so the window itself isn't multisampled
+fn context_builder(config: &WindowConfig) -> glutin::ContextBuilder<'static, glutin::NotCurrent>{
+    glutin::ContextBuilder::new()
+        .with_depth_buffer(24)
+        .with_vsync(config.vsync)
+}
+
+/// Runs a game without a window or GL context.
+/// The display stays empty so systems that need one skip themselves, inputs can still be pushed into the queues.
+pub struct HeadlessState{
+    pub display: WindowDisplay,
+    pub window_inputs: Arc<Mutex<Vec<ApplicationEvent>>>,
+    pub hardware_inputs: Arc<Mutex<Vec<DeviceEvent>>>,
+}
+
+impl HeadlessState{
+    pub fn new() -> Self{
+        Self{
+            display: WindowDisplay::default(),
+            window_inputs: Arc::new(Mutex::new(Vec::new())),
+            hardware_inputs: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn input_queues(&self) -> (Arc<Mutex<Vec<ApplicationEvent>>>, Arc<Mutex<Vec<DeviceEvent>>>){
+        (self.window_inputs.clone(), self.hardware_inputs.clone())
+    }
+
+    /// Run a fixed number of ticks back to back with a frame after each, for tests and tools
+    pub fn run_ticks<F>(&self, game: &mut F, ticks: u64)
+        where F: GameState
+    {
+        let mut timestep = FixedTimestep::new();
+        for _ in 0..ticks{
+            let tick = game.tick_length();
+            let (count, alpha) = timestep.advance(tick, tick);
+            for _ in 0..count{
+                game.fixed_update();
+            }
+            game.frame(tick, alpha);
+        }
+    }
+
+    /// Run forever in real time, sleeping between ticks
+    pub fn run_realtime<F>(&self, mut game: F) -> !
+        where F: GameState
+    {
+        let mut timestep = FixedTimestep::new();
+        let mut last_frame = Instant::now();
+        loop{
+            let now = Instant::now();
+            let frame_time = now - last_frame;
+            last_frame = now;
+            let tick = game.tick_length();
+            let (ticks, alpha) = timestep.advance(frame_time, tick);
+            for _ in 0..ticks{
+                game.fixed_update();
+            }
+            game.frame(frame_time, alpha);
+            std::thread::sleep(timestep.until_next_tick(tick));
+        }
+    }
+}
+
+impl Default for HeadlessState{
+    fn default() -> Self{
+        Self::new()
+    }
+}
+
 #[derive(Clone)]
-pub struct WindowDisplay(Arc<Option<Mutex<glium::Display>>>);
+pub struct WindowDisplay{
+    display: Arc<Option<Mutex<glium::Display>>>,
+    /// Settings to rebuild the GL context with before the next frame
+    rebuild: Arc<Mutex<Option<WindowConfig>>>,
+    rebuilds: Arc<AtomicUsize>,
+}
 
 unsafe impl Send for WindowDisplay{}
 unsafe impl Sync for WindowDisplay{}
@@ -91,13 +200,55 @@ impl Deref for WindowDisplay{
 
     ///Due to shared mutable state underlying display, this can stall or halt
     fn deref(&self) -> &Self::Target{
-        self.0.deref()
+        self.display.deref()
     }
 }
 
 impl Default for WindowDisplay{
     fn default() -> Self{
-        WindowDisplay{0: Arc::new(None)}
+        WindowDisplay{
+            display: Arc::new(None),
+            rebuild: Arc::new(Mutex::new(None)),
+            rebuilds: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+impl WindowDisplay{
+    /// Rebuild the window and GL context with config before the next frame, for settings like vsync
+    /// that can only be set when a context is made. Textures, buffers and programs survive it.
+    pub fn request_rebuild(&self, config: WindowConfig){
+        *self.rebuild.lock().unwrap() = Some(config);
+    }
+
+    /// Rebuilds need the event loop, so they're done by it between frames
+    fn rebuild_if_requested(&self, target: &EventLoopWindowTarget<()>){
+        let config = match self.rebuild.lock().unwrap().take(){
+            Some(config) => config,
+            None => return,
+        };
+        let display = match self.display.as_ref(){
+            Some(display) => display.lock().unwrap(),
+            None => return,
+        };
+        let (monitor, size) = {
+            let gl_window = display.gl_window();
+            let window = gl_window.window();
+            (window.current_monitor(), window.inner_size())
+        };
+        let wb = window_builder(&config, monitor, size);
+        match display.rebuild(wb, context_builder(&config), target){
+            Ok(()) => {
+                self.rebuilds.fetch_add(1, Ordering::SeqCst);
+            },
+            Err(e) => println!("Couldn't rebuild the window: {:?}", e),
+        }
+    }
+
+    /// How many times the window has been rebuilt. A rebuilt window starts out fresh,
+    /// so anything set on the window itself like the cursor grab has to be set again.
+    pub fn rebuilds(&self) -> usize{
+        self.rebuilds.load(Ordering::SeqCst)
     }
 }
 
@@ -186,8 +337,99 @@ impl From<WindowEvent<'_>> for ApplicationEvent{
     }
 }
 
+/// A game is stepped in fixed length ticks for simulation, with a frame after
+/// however many ticks fit into the real time that has passed
 pub trait GameState{
-    fn game_loop(&mut self);
+    fn tick_length(&self) -> Duration;
+
+    fn fixed_update(&mut self);
+
+    /// alpha is how far the frame is between the last tick and the next one, from 0 to 1
+    fn frame(&mut self, frame_time: Duration, alpha: f32);
+}
+
+/// Most ticks run for one frame, if the game falls further behind than this the rest are dropped
+const MAX_TICKS_PER_FRAME: u32 = 8;
+
+/// Splits real time into fixed length ticks, carrying the remainder over to the next frame
+#[derive(Default)]
+pub struct FixedTimestep{
+    accumulator: Duration,
+}
+
+impl FixedTimestep{
+    pub fn new() -> Self{
+        Self::default()
+    }
+
+    /// Returns how many ticks to run this frame and how far into the next tick the frame is
+    pub fn advance(&mut self, frame_time: Duration, tick: Duration) -> (u32, f32){
+        self.accumulator += frame_time;
+        let mut ticks = 0;
+        while self.accumulator >= tick{
+            self.accumulator -= tick;
+            ticks += 1;
+            if ticks == MAX_TICKS_PER_FRAME{
+                self.accumulator = Duration::from_secs(0);
+                break;
+            }
+        }
+        (ticks, self.accumulator.as_secs_f32() / tick.as_secs_f32())
+    }
+
+    pub fn until_next_tick(&self, tick: Duration) -> Duration{
+        tick.checked_sub(self.accumulator).unwrap_or_default()
+    }
+}
+
+/// Puts the window into the fullscreen mode WindowInfo asks for and keeps the settings file in step.
+/// Vsync belongs to the GL context, so turning it on or off rebuilds the window and context.
+pub struct WindowSettingsSystem{
+    config: WindowConfig,
+    path: PathBuf,
+}
+
+impl WindowSettingsSystem{
+    /// config is what the window was created with, changes are saved to path
+    pub fn new<P: Into<PathBuf>>(config: WindowConfig, path: P) -> Self{
+        WindowSettingsSystem{
+            config,
+            path: path.into(),
+        }
+    }
+}
+
+impl<'a> System<'a> for WindowSettingsSystem{
+    type SystemData = (
+        Read<'a, WindowInfo>,
+        Write<'a, WindowDisplay>);
+
+    fn run(&mut self, (window_info, window_display): Self::SystemData){
+        let display = match window_display.as_ref(){
+            Some(display) => display.lock().unwrap(),
+            None => return,
+        };
+        if window_info.fullscreen() == self.config.fullscreen && window_info.vsync() == self.config.vsync && window_info.shadows() == self.config.shadows{
+            return;
+        }
+
+        if window_info.fullscreen() != self.config.fullscreen{
+            let gl_window = display.gl_window();
+            let window = gl_window.window();
+            window.set_fullscreen(window_info.fullscreen().to_fullscreen(window.current_monitor(), self.config.size()));
+        }
+        let vsync_changed = window_info.vsync() != self.config.vsync;
+
+        self.config.fullscreen = window_info.fullscreen();
+        self.config.vsync = window_info.vsync();
+        self.config.shadows = window_info.shadows();
+        if vsync_changed{
+            window_display.request_rebuild(self.config.clone());
+        }
+        if let Err(e) = self.config.save(&self.path){
+            println!("Couldn't save {}: {:?}", self.path.display(), e);
+        }
+    }
 }
 
 pub enum GliumError{